@@ -1,4 +1,49 @@
+use std::env;
+use std::path::PathBuf;
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    tonic_build::compile_protos("proto/rpc.proto")?;
+    let out_dir = PathBuf::from(env::var("OUT_DIR")?);
+
+    tonic_build::configure()
+        // provisiond serves these over server reflection.
+        .file_descriptor_set_path(out_dir.join("provision_descriptor.bin"))
+        // Keeps map fields in a stable order when printed.
+        .btree_map(["."])
+        // provisionctl prints responses as json/yaml, so the proto is the schema.
+        .type_attribute(".provision", "#[derive(serde::Serialize)]")
+        .type_attribute(".provision", "#[serde(rename_all = \"snake_case\")]")
+        .field_attribute(
+            ".provision.Operation.status",
+            "#[serde(serialize_with = \"crate::serde_enums::operation_status\")]",
+        )
+        .field_attribute(
+            ".provision.OperationStep.status",
+            "#[serde(serialize_with = \"crate::serde_enums::operation_status\")]",
+        )
+        .field_attribute(
+            ".provision.ServiceChange.action",
+            "#[serde(serialize_with = \"crate::serde_enums::change_action\")]",
+        )
+        .field_attribute(
+            ".provision.Schedule.job",
+            "#[serde(serialize_with = \"crate::serde_enums::job_kind\")]",
+        )
+        .field_attribute(
+            ".provision.ScheduledJob.last_status",
+            "#[serde(serialize_with = \"crate::serde_enums::operation_status\")]",
+        )
+        .field_attribute(
+            ".provision.HealthCheckResult.status",
+            "#[serde(serialize_with = \"crate::serde_enums::health_status\")]",
+        )
+        .field_attribute(
+            ".provision.GetHealthResponse.status",
+            "#[serde(serialize_with = \"crate::serde_enums::health_status\")]",
+        )
+        .field_attribute(
+            ".provision.LogLine.source",
+            "#[serde(serialize_with = \"crate::serde_enums::log_source\")]",
+        )
+        .compile_protos(&["proto/rpc.proto"], &["proto"])?;
     Ok(())
-}
\ No newline at end of file
+}
@@ -1,18 +1,34 @@
+mod serde_enums;
+
+/// Descriptors of every proto file compiled here, for server reflection.
+pub const FILE_DESCRIPTOR_SET: &[u8] = tonic::include_file_descriptor_set!("provision_descriptor");
+
 pub mod hello_world {
     pub(super) mod proto {
         tonic::include_proto!("provision"); // The string specified here must match the proto package name
     }
 
     pub use proto::{
-        HelloReply, HelloRequest,
-        greeter_client::GreeterClient,
-        greeter_server::{Greeter, GreeterServer},
-    };
-
-    pub use proto::{
-        CreateRequest, CreateResponse, DeleteRequest, DeleteResponse, PullRequest, PullResponse,
-        RestartRequest, RestartResponse,
+        ApplyRequest, ApplyResponse, BackupInfo, BackupRequest, BackupResponse, ChangeAction, CreateRequest,
+        CreateResponse, DeleteRequest, DeleteResponse, DescribeRequest, ExecInput, ExecOutput, ExecStart,
+        FilePermission, GetHealthRequest, GetHealthResponse, GetOperationRequest, HealthCheckResult,
+        HealthStatus, JobKind, ListBackupsRequest, ListBackupsResponse, ListRequest, ListResponse,
+        ListSchedulesRequest, ListSchedulesResponse, LogLine, LogSource, LogsRequest, Operation,
+        OperationStatus, OperationStep, PublishedPort, PullRequest, PullResponse, ResourceLimits, RestartRequest,
+        RestartResponse, RestoreRequest, RestoreResponse, Schedule, ScheduledJob, ServiceChange,
+        ServiceDescription, ServiceSpec, ServiceSummary, TerminalSize, UpdateServiceRequest,
+        UpdateServiceResponse, exec_input, exec_output,
         provisioner_client::ProvisionerClient,
         provisioner_server::{Provisioner, ProvisionerServer},
     };
 }
+
+/// Names provisiond reports its dependencies under in the standard
+/// `grpc.health.v1.Health` service, next to "" for the daemon as a whole.
+pub mod dependencies {
+    pub const DOCKER: &str = "docker";
+    pub const SYSTEMD: &str = "systemd";
+    pub const PROVISIONING_ROOT: &str = "provisioning_root";
+
+    pub const ALL: [&str; 3] = [DOCKER, SYSTEMD, PROVISIONING_ROOT];
+}
@@ -0,0 +1,27 @@
+//! Serializers for enum fields, which prost stores as plain `i32`s. They are
+//! written out as the lower case value name without the enum prefix, so
+//! `OPERATION_STATUS_RUNNING` becomes `"running"`.
+
+use serde::Serializer;
+
+macro_rules! enum_serializer {
+    ($name:ident, $enum:ty, $prefix:literal) => {
+        pub fn $name<S: Serializer>(value: &i32, serializer: S) -> Result<S::Ok, S::Error> {
+            let name = <$enum>::try_from(*value)
+                .map(|v| v.as_str_name())
+                .unwrap_or("UNSPECIFIED");
+            serializer.serialize_str(
+                &name
+                    .strip_prefix($prefix)
+                    .unwrap_or(name)
+                    .to_ascii_lowercase(),
+            )
+        }
+    };
+}
+
+enum_serializer!(operation_status, crate::hello_world::OperationStatus, "OPERATION_STATUS_");
+enum_serializer!(change_action, crate::hello_world::ChangeAction, "CHANGE_ACTION_");
+enum_serializer!(job_kind, crate::hello_world::JobKind, "JOB_KIND_");
+enum_serializer!(health_status, crate::hello_world::HealthStatus, "HEALTH_STATUS_");
+enum_serializer!(log_source, crate::hello_world::LogSource, "LOG_SOURCE_");
@@ -0,0 +1,105 @@
+use std::fs;
+use std::path::Path;
+
+use libprovision::hello_world::ProvisionerClient;
+use log::info;
+use tonic::metadata::MetadataValue;
+use tonic::service::Interceptor;
+use tonic::service::interceptor::InterceptedService;
+use tonic::transport::{Certificate, Channel, ClientTlsConfig, Identity};
+use tonic::{Code, Request, Status};
+use tonic_health::pb::health_client;
+use uuid::Uuid;
+
+use crate::config::Context;
+
+pub type Client = ProvisionerClient<InterceptedService<Channel, TokenInterceptor>>;
+
+/// Talks to the standard gRPC health service of the same provisiond.
+pub type HealthClient = health_client::HealthClient<InterceptedService<Channel, TokenInterceptor>>;
+
+/// Header carrying the id provisiond logs every call of this invocation under.
+const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Header provisiond returns naming the operation a call started.
+pub const OPERATION_ID_HEADER: &str = "x-operation-id";
+
+/// Attaches the context's auth token and the invocation's request id to
+/// every request.
+#[derive(Clone)]
+pub struct TokenInterceptor {
+    token: Option<MetadataValue<tonic::metadata::Ascii>>,
+    request_id: MetadataValue<tonic::metadata::Ascii>,
+}
+
+impl Interceptor for TokenInterceptor {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        if let Some(token) = &self.token {
+            request.metadata_mut().insert("authorization", token.clone());
+        }
+        request
+            .metadata_mut()
+            .insert(REQUEST_ID_HEADER, self.request_id.clone());
+        Ok(request)
+    }
+}
+
+pub async fn connect(context: &Context) -> Result<Client, Status> {
+    let (channel, interceptor) = connect_channel(context).await?;
+    Ok(ProvisionerClient::with_interceptor(channel, interceptor))
+}
+
+pub async fn connect_health(context: &Context) -> Result<HealthClient, Status> {
+    let (channel, interceptor) = connect_channel(context).await?;
+    Ok(health_client::HealthClient::with_interceptor(channel, interceptor))
+}
+
+async fn connect_channel(context: &Context) -> Result<(Channel, TokenInterceptor), Status> {
+    let invalid = |e: String| Status::new(Code::InvalidArgument, e);
+
+    let mut endpoint = Channel::from_shared(context.endpoint.clone())
+        .map_err(|e| invalid(format!("invalid endpoint '{}': {}", context.endpoint, e)))?;
+
+    if let Some(ca_cert) = &context.ca_cert {
+        let mut tls = ClientTlsConfig::new().ca_certificate(Certificate::from_pem(read(ca_cert)?));
+
+        match (&context.client_cert, &context.client_key) {
+            (Some(cert), Some(key)) => tls = tls.identity(Identity::from_pem(read(cert)?, read(key)?)),
+            (None, None) => {}
+            _ => return Err(invalid("client_cert and client_key must be set together".to_owned())),
+        }
+
+        endpoint = endpoint
+            .tls_config(tls)
+            .map_err(|e| invalid(format!("invalid TLS config: {e}")))?;
+    }
+
+    let token = context
+        .token
+        .as_ref()
+        .map(|token| format!("Bearer {token}").parse())
+        .transpose()
+        .map_err(|_| invalid("auth token contains invalid characters".to_owned()))?;
+
+    let channel = endpoint.connect().await.map_err(|err| {
+        Status::new(
+            Code::Unavailable,
+            format!("could not connect to provisiond at {}: {}", context.endpoint, err),
+        )
+    })?;
+
+    let request_id = Uuid::new_v4().to_string();
+    info!("Sending requests to {} as {}", context.endpoint, request_id);
+
+    let request_id = request_id.parse().expect("uuid is valid metadata");
+    Ok((channel, TokenInterceptor { token, request_id }))
+}
+
+fn read(path: &Path) -> Result<Vec<u8>, Status> {
+    fs::read(path).map_err(|e| {
+        Status::new(
+            Code::InvalidArgument,
+            format!("could not read '{}': {}", path.display(), e),
+        )
+    })
+}
@@ -1,11 +1,38 @@
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
 use clap::*;
+use libprovision::hello_world::ResourceLimits;
+
+use crate::output::OutputFormat;
 
 #[derive(Parser, Debug)]
-#[command(version, about, long_about = None)]
+#[command(version, about, long_about = None, after_help = EXIT_CODES)]
 pub struct Command {
 
-    #[arg()]
-    pub name: String,
+    /// Address of the provisiond to talk to, overrides the context's endpoint
+    #[arg(long, global = true)]
+    pub endpoint: Option<String>,
+
+    /// Context from the config file to use instead of the current one
+    #[arg(long, global = true)]
+    pub context: Option<String>,
+
+    /// Run against every context in the config file, only supported by `list`
+    #[arg(long)]
+    pub all_contexts: bool,
+
+    /// Path of the config file, defaults to ~/.config/provisionctl/config.toml
+    #[arg(long, global = true)]
+    pub config: Option<PathBuf>,
+
+    /// Retrying with the same key returns the original result instead of re-running
+    #[arg(long, global = true, default_value = "")]
+    pub idempotency_key: String,
+
+    /// Format results and errors are printed in
+    #[arg(long, short, global = true, value_enum, default_value_t)]
+    pub output: OutputFormat,
 
     #[command(subcommand)]
     pub command: Commands,
@@ -14,8 +41,267 @@ pub struct Command {
 
 #[derive(Subcommand, Debug)]
 pub enum Commands {
-    Create,
-    Restart,
-    Pull,
-    Delete,
-}
\ No newline at end of file
+    /// Create a new service from a blueprint
+    Create {
+        name: String,
+
+        #[arg(long, default_value = "postgres")]
+        blueprint: String,
+
+        /// Run the service as its own svc-<name> user instead of the shared one
+        #[arg(long)]
+        dedicated_user: bool,
+
+        #[command(flatten)]
+        resources: ResourceArgs,
+    },
+    /// Change the configuration of a service and restart it
+    Update(UpdateArgs),
+    /// Restart a service
+    Restart {
+        name: String,
+    },
+    /// Pull the latest images for a service
+    Pull {
+        name: String,
+        /// Restart the service if a newer image was pulled
+        #[arg(long)]
+        restart_on_change: bool,
+    },
+    /// Delete a service
+    Delete {
+        name: String,
+
+        /// Also remove the service's volumes, they are kept by default
+        #[arg(long)]
+        purge_volumes: bool,
+    },
+    /// Create, update and delete services to match a manifest file
+    Apply {
+        /// YAML manifest listing the services
+        #[arg(long, short)]
+        file: PathBuf,
+
+        /// Also delete services that are not in the manifest
+        #[arg(long)]
+        prune: bool,
+    },
+    /// Show what `apply` would change without changing anything
+    Diff {
+        /// YAML manifest listing the services
+        #[arg(long, short)]
+        file: PathBuf,
+
+        /// Include the services `apply --prune` would delete
+        #[arg(long)]
+        prune: bool,
+    },
+    /// List the services on the host
+    List,
+    /// Show a service's configuration and check its file permissions
+    Describe {
+        name: String,
+    },
+    /// Print the logs of a service's container
+    Logs(LogsArgs),
+    /// Run a command in a service's container, for example `exec billing -- psql`
+    Exec(ExecArgs),
+    /// Check that provisiond answers and what it depends on works
+    Ping,
+    /// Show a service's health and its recent checks
+    Status {
+        name: String,
+    },
+    /// Dump a service's data into a compressed backup on the host
+    Backup {
+        name: String,
+    },
+    /// List the stored backups of a service
+    Backups {
+        name: String,
+    },
+    /// List scheduled jobs with their next and last runs
+    Schedules {
+        /// Only show this service's jobs
+        name: Option<String>,
+    },
+    /// Load a backup back into a service, replacing its current data
+    Restore {
+        name: String,
+        backup_id: String,
+    },
+    /// Show the status and steps of an operation
+    Operation {
+        operation_id: String,
+    },
+    /// Manage the provisiond hosts in the config file
+    Context {
+        #[command(subcommand)]
+        command: ContextCommands,
+    },
+}
+
+#[derive(Args, Debug)]
+pub struct UpdateArgs {
+    pub name: String,
+
+    /// Blueprint parameter to set, for example image=postgres:17
+    #[arg(long = "param", value_name = "KEY=VALUE", value_parser = parse_key_value)]
+    pub params: Vec<(String, String)>,
+
+    /// Blueprint parameter to reset to its default
+    #[arg(long = "unset-param", value_name = "KEY")]
+    pub unset_params: Vec<String>,
+
+    /// Environment variable to set in the service's .env
+    #[arg(long = "env", value_name = "KEY=VALUE", value_parser = parse_key_value)]
+    pub env: Vec<(String, String)>,
+
+    /// Environment variable to remove
+    #[arg(long = "unset-env", value_name = "KEY")]
+    pub unset_env: Vec<String>,
+
+    #[command(flatten)]
+    pub resources: ResourceArgs,
+
+    /// Rewrite the files without restarting the service
+    #[arg(long)]
+    pub no_restart: bool,
+}
+
+/// Limits for a service, unset flags leave a limit as it is.
+#[derive(Args, Debug)]
+pub struct ResourceArgs {
+    /// CPU limit, for example 0.5
+    #[arg(long)]
+    pub cpus: Option<String>,
+
+    /// Memory limit, for example 512m
+    #[arg(long)]
+    pub memory: Option<String>,
+
+    /// Maximum number of processes
+    #[arg(long)]
+    pub pids: Option<i64>,
+}
+
+impl ResourceArgs {
+    /// The limits to send, `None` if no flag was given.
+    pub fn into_limits(self) -> Option<ResourceLimits> {
+        match (&self.cpus, &self.memory, self.pids) {
+            (None, None, None) => None,
+            _ => Some(ResourceLimits {
+                cpus: self.cpus.unwrap_or_default(),
+                memory: self.memory.unwrap_or_default(),
+                pids: self.pids.unwrap_or_default(),
+            }),
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct LogsArgs {
+    pub name: String,
+
+    /// Keep printing new lines as they are logged
+    #[arg(long, short)]
+    pub follow: bool,
+
+    /// Only lines logged since then, a duration like 10m or a time like 2026-10-19T08:00:00Z
+    #[arg(long, value_parser = parse_since)]
+    pub since: Option<i64>,
+
+    /// Only the last this many lines
+    #[arg(long, short = 'n')]
+    pub tail: Option<u32>,
+
+    /// Merge in the journal of the service's systemd unit
+    #[arg(long)]
+    pub journal: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct ExecArgs {
+    pub name: String,
+
+    /// Compose service to run in, defaults to the blueprint's main container
+    #[arg(long)]
+    pub service: Option<String>,
+
+    /// Do not allocate a terminal even when run from one
+    #[arg(long, short = 'T')]
+    pub no_tty: bool,
+
+    /// The command and its arguments
+    #[arg(last = true, required = true)]
+    pub command: Vec<String>,
+}
+
+/// A relative duration or an RFC 3339 time, as unix seconds.
+fn parse_since(value: &str) -> Result<i64, String> {
+    let time = match humantime::parse_duration(value) {
+        Ok(ago) => SystemTime::now().checked_sub(ago),
+        Err(_) => humantime::parse_rfc3339_weak(value).ok(),
+    };
+
+    time.and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|since| since.as_secs() as i64)
+        .ok_or_else(|| format!("expected a duration like 10m or a time like 2026-10-19T08:00:00Z, got '{value}'"))
+}
+
+fn parse_key_value(value: &str) -> Result<(String, String), String> {
+    match value.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_owned(), value.to_owned())),
+        _ => Err(format!("expected KEY=VALUE, got '{value}'")),
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ContextCommands {
+    /// List the configured contexts
+    List,
+    /// Print the current context
+    Current,
+    /// Make a context the default for future invocations
+    Use {
+        name: String,
+    },
+    /// Create or update a context
+    Set {
+        name: String,
+
+        #[arg(long)]
+        endpoint: Option<String>,
+
+        /// CA certificate used to verify the daemon, enables TLS
+        #[arg(long)]
+        ca_cert: Option<PathBuf>,
+
+        #[arg(long, requires = "client_key")]
+        client_cert: Option<PathBuf>,
+
+        #[arg(long, requires = "client_cert")]
+        client_key: Option<PathBuf>,
+
+        /// Bearer token sent with every request
+        #[arg(long)]
+        token: Option<String>,
+    },
+    /// Remove a context
+    Delete {
+        name: String,
+    },
+}
+
+const EXIT_CODES: &str = "\
+Exit codes:
+  0  success
+  1  internal or unknown error
+  2  invalid command line usage
+  3  provisiond unreachable, timed out or not serving
+  4  service or operation not found
+  5  service already exists
+  6  permission denied
+  7  invalid argument or failed precondition
+  8  conflicting operation, safe to retry
+  9  not supported by this provisiond";
@@ -0,0 +1,188 @@
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::env;
+
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_ENDPOINT: &str = "http://[::1]:50051";
+
+/// A provisiond host and the credentials used to talk to it.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Context {
+    pub endpoint: String,
+
+    /// CA certificate used to verify the daemon, enables TLS when set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ca_cert: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_cert: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_key: Option<PathBuf>,
+
+    /// Sent as a bearer token with every request
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Config {
+    /// Endpoint used when no context is selected
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub current_context: Option<String>,
+
+    #[serde(default)]
+    pub contexts: BTreeMap<String, Context>,
+
+    #[serde(skip)]
+    path: Option<PathBuf>,
+}
+
+impl Config {
+    /// Loads the config file given on the command line, or the default one if
+    /// it exists. Only an explicitly requested file has to be present.
+    pub fn load(path: Option<&Path>) -> Result<Self, String> {
+        let (path, required) = match path {
+            Some(path) => (path.to_path_buf(), true),
+            None => match default_path() {
+                Some(path) => (path, false),
+                None => return Ok(Config::default()),
+            },
+        };
+
+        if !required && !path.exists() {
+            return Ok(Config {
+                path: Some(path),
+                ..Config::default()
+            });
+        }
+
+        let contents = fs::read_to_string(&path)
+            .map_err(|e| format!("could not read config file '{}': {}", path.display(), e))?;
+
+        let mut config: Config = toml::from_str(&contents)
+            .map_err(|e| format!("could not parse config file '{}': {}", path.display(), e))?;
+        config.path = Some(path);
+
+        Ok(config)
+    }
+
+    /// Writes the config back to where it was loaded from. The file can hold
+    /// auth tokens so it is only readable by its owner.
+    pub fn save(&self) -> Result<(), String> {
+        let path = self
+            .path
+            .as_ref()
+            .ok_or_else(|| "no config file path, pass --config".to_owned())?;
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("could not create '{}': {}", parent.display(), e))?;
+        }
+
+        let contents = toml::to_string_pretty(self)
+            .map_err(|e| format!("could not serialize config: {e}"))?;
+
+        OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(path)
+            .and_then(|mut file| file.write_all(contents.as_bytes()))
+            .map_err(|e| format!("could not write config file '{}': {}", path.display(), e))
+    }
+
+    /// Picks the context for this invocation: `--context`, then the current
+    /// context, then the top level endpoint. `--endpoint` overrides whichever
+    /// endpoint that context has.
+    pub fn resolve(&self, context: Option<&str>, endpoint: Option<String>) -> Result<Context, String> {
+        let mut resolved = match context.or(self.current_context.as_deref()) {
+            Some(name) => self.context(name)?.clone(),
+            None => Context {
+                endpoint: self
+                    .endpoint
+                    .clone()
+                    .unwrap_or_else(|| DEFAULT_ENDPOINT.to_owned()),
+                ..Context::default()
+            },
+        };
+
+        if let Some(endpoint) = endpoint {
+            resolved.endpoint = endpoint;
+        }
+
+        Ok(resolved)
+    }
+
+    pub fn context(&self, name: &str) -> Result<&Context, String> {
+        self.contexts
+            .get(name)
+            .ok_or_else(|| format!("no context named '{name}' in the config file"))
+    }
+}
+
+fn default_path() -> Option<PathBuf> {
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+
+    Some(config_home.join("provisionctl").join("config.toml"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> Config {
+        toml::from_str(
+            r#"
+            current_context = "a"
+
+            [contexts.a]
+            endpoint = "https://a:50051"
+            token = "secret"
+
+            [contexts.b]
+            endpoint = "http://b:50051"
+            "#,
+        )
+        .expect("Failed to parse config")
+    }
+
+    #[test]
+    pub fn test_current_context_used() {
+        let context = config().resolve(None, None).expect("Failed to resolve context");
+
+        assert_eq!(context.endpoint, "https://a:50051");
+        assert_eq!(context.token.as_deref(), Some("secret"));
+    }
+
+    #[test]
+    pub fn test_context_and_endpoint_overrides() {
+        let config = config();
+
+        let context = config.resolve(Some("b"), None).expect("Failed to resolve context");
+        assert_eq!(context.endpoint, "http://b:50051");
+
+        let context = config
+            .resolve(Some("a"), Some("http://other:1".to_owned()))
+            .expect("Failed to resolve context");
+        assert_eq!(context.endpoint, "http://other:1");
+        assert_eq!(context.token.as_deref(), Some("secret"), "Overriding the endpoint keeps the credentials");
+
+        assert!(config.resolve(Some("missing"), None).is_err());
+    }
+
+    #[test]
+    pub fn test_default_endpoint_without_contexts() {
+        let context = Config::default().resolve(None, None).expect("Failed to resolve context");
+
+        assert_eq!(context.endpoint, DEFAULT_ENDPOINT);
+    }
+}
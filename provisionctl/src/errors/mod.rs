@@ -0,0 +1,106 @@
+use tonic::{Code, Status};
+use tonic_types::StatusExt;
+
+use crate::client::OPERATION_ID_HEADER;
+
+/// provisiond could not be reached or did not answer in time.
+const EXIT_UNREACHABLE: u8 = 3;
+
+/// Exit code for a failed RPC, grouped by gRPC error class so scripts can
+/// branch on them. Keep in sync with the table in `cmd`.
+pub fn exit_code(code: Code) -> u8 {
+    match code {
+        Code::Ok => 0,
+        Code::Unavailable | Code::DeadlineExceeded | Code::Cancelled => EXIT_UNREACHABLE,
+        Code::NotFound => 4,
+        Code::AlreadyExists => 5,
+        Code::PermissionDenied | Code::Unauthenticated => 6,
+        Code::InvalidArgument | Code::FailedPrecondition | Code::OutOfRange => 7,
+        Code::Aborted | Code::ResourceExhausted => 8,
+        Code::Unimplemented => 9,
+        Code::Unknown | Code::Internal | Code::DataLoss => 1,
+    }
+}
+
+/// Canonical gRPC name of a status code, as used in json/yaml output.
+pub fn code_name(code: Code) -> &'static str {
+    match code {
+        Code::Ok => "OK",
+        Code::Cancelled => "CANCELLED",
+        Code::Unknown => "UNKNOWN",
+        Code::InvalidArgument => "INVALID_ARGUMENT",
+        Code::DeadlineExceeded => "DEADLINE_EXCEEDED",
+        Code::NotFound => "NOT_FOUND",
+        Code::AlreadyExists => "ALREADY_EXISTS",
+        Code::PermissionDenied => "PERMISSION_DENIED",
+        Code::ResourceExhausted => "RESOURCE_EXHAUSTED",
+        Code::FailedPrecondition => "FAILED_PRECONDITION",
+        Code::Aborted => "ABORTED",
+        Code::OutOfRange => "OUT_OF_RANGE",
+        Code::Unimplemented => "UNIMPLEMENTED",
+        Code::Internal => "INTERNAL",
+        Code::Unavailable => "UNAVAILABLE",
+        Code::DataLoss => "DATA_LOSS",
+        Code::Unauthenticated => "UNAUTHENTICATED",
+    }
+}
+
+/// Prints a failed RPC, including the structured `ErrorInfo` details the
+/// daemon attaches (reason, failed step, path) when they are present.
+pub fn print_status(status: &Status) {
+    eprintln!("error: {}", status.message());
+    eprintln!("  code:   {:?}", status.code());
+
+    let details = status.get_error_details();
+    if let Some(info) = details.error_info() {
+        eprintln!("  reason: {}", info.reason);
+
+        let mut metadata: Vec<_> = info.metadata.iter().collect();
+        metadata.sort();
+        for (key, value) in metadata {
+            eprintln!("  {:<7} {}", format!("{key}:"), value);
+        }
+    }
+
+    if let Some(operation) = operation_id(status) {
+        eprintln!("  operation: {operation}");
+    }
+}
+
+/// The operation provisiond started for the failed call, if any, so the
+/// error can be found in the daemon's logs.
+pub fn operation_id(status: &Status) -> Option<String> {
+    status
+        .metadata()
+        .get(OPERATION_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_owned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_exit_codes_distinct_per_class() {
+        assert_eq!(exit_code(Code::Ok), 0);
+        assert_eq!(exit_code(Code::Unavailable), EXIT_UNREACHABLE);
+
+        let classes = [
+            Code::Internal,
+            Code::Unavailable,
+            Code::NotFound,
+            Code::AlreadyExists,
+            Code::PermissionDenied,
+            Code::InvalidArgument,
+            Code::Aborted,
+            Code::Unimplemented,
+        ];
+        let mut codes: Vec<u8> = classes.iter().map(|c| exit_code(*c)).collect();
+        codes.sort();
+        codes.dedup();
+
+        assert_eq!(codes.len(), classes.len(), "Every error class should have its own exit code");
+        assert!(!codes.contains(&2), "2 is reserved for usage errors");
+    }
+}
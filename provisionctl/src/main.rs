@@ -1,30 +1,120 @@
+// tonic::Status is large, but it is what every RPC fails with anyway.
+#![allow(clippy::result_large_err)]
+
+mod client;
 mod cmd;
+mod config;
+mod errors;
+mod manifest;
 mod operations;
+mod output;
+
+use std::process::ExitCode;
 
 use crate::cmd::{Command as CmdArgs, Commands};
+use crate::config::Config;
+use crate::errors::exit_code;
 use clap::Parser;
-use libprovision::hello_world::ProvisionerClient;
 use log::{Level, info, log};
+use tonic::{Code, Status};
 
-use crate::operations::{handle_create, handle_delete, handle_pull, handle_restart};
+use crate::client::{connect, connect_health};
+use crate::operations::{
+    handle_apply, handle_backup, handle_context, handle_create, handle_delete, handle_describe, handle_exec, handle_get_operation, handle_list,
+    handle_list_all, handle_list_backups, handle_logs, handle_list_schedules, handle_ping, handle_pull, handle_restart, handle_restore, handle_status, handle_update,
+};
 
 #[tokio::main]
-async fn main() {
+async fn main() -> ExitCode {
     env_logger::init();
 
     let args = CmdArgs::parse();
     log!(Level::Debug, "{:?}", args);
 
-    info!("Creating client for connection to server");
-    let mut client = ProvisionerClient::connect("http://[::1]:50051")
-        .await
-        .unwrap();
+    let output = args.output;
+    match run(args).await {
+        Ok(code) => ExitCode::from(code),
+        Err(status) => {
+            output.print_error(&status);
+            ExitCode::from(exit_code(status.code()))
+        }
+    }
+}
+
+/// Runs the command and returns the exit code, commands that talk to a
+/// single host exit with 0 on success.
+async fn run(args: CmdArgs) -> Result<u8, Status> {
+    let invalid = |err: String| Status::new(Code::InvalidArgument, err);
 
-    info!("Sending request");
-    match args.command {
-        Commands::Create => handle_create(&mut client, args.name).await,
-        Commands::Restart => handle_restart(&mut client).await,
-        Commands::Pull => handle_pull(&mut client).await,
-        Commands::Delete => handle_delete(&mut client).await,
+    let mut config = Config::load(args.config.as_deref()).map_err(invalid)?;
+    let output = args.output;
+
+    if args.all_contexts {
+        return match args.command {
+            Commands::List => Ok(handle_list_all(&config, output).await),
+            _ => Err(invalid("--all-contexts is only supported by list".to_owned())),
+        };
+    }
+
+    if let Commands::Context { command } = args.command {
+        return handle_context(&mut config, output, command).map(|()| 0);
+    }
+
+    let context = config
+        .resolve(args.context.as_deref(), args.endpoint)
+        .map_err(invalid)?;
+
+    // Ping goes to the standard health service rather than the Provisioner.
+    if let Commands::Ping = args.command {
+        info!("Creating health client for connection to {}", context.endpoint);
+        let mut client = connect_health(&context).await?;
+        return handle_ping(&mut client, output, context.endpoint).await;
     }
+
+    info!("Creating client for connection to {}", context.endpoint);
+    let mut client = connect(&context).await?;
+
+    info!("Sending request");
+    let idempotency_key = args.idempotency_key;
+    let res = match args.command {
+        Commands::Create { name, blueprint, dedicated_user, resources } => {
+            handle_create(&mut client, output, name, blueprint, dedicated_user, resources, idempotency_key).await
+        }
+        Commands::Update(update) => handle_update(&mut client, output, update, idempotency_key).await,
+        Commands::Restart { name } => {
+            handle_restart(&mut client, output, name, idempotency_key).await
+        }
+        Commands::Pull { name, restart_on_change } => {
+            handle_pull(&mut client, output, name, restart_on_change, idempotency_key).await
+        }
+        Commands::Delete {
+            name,
+            purge_volumes,
+        } => handle_delete(&mut client, output, name, purge_volumes, idempotency_key).await,
+        Commands::Apply { file, prune } => {
+            handle_apply(&mut client, output, &file, prune, false, idempotency_key).await
+        }
+        Commands::Diff { file, prune } => {
+            handle_apply(&mut client, output, &file, prune, true, idempotency_key).await
+        }
+        Commands::List => handle_list(&mut client, output).await,
+        Commands::Describe { name } => handle_describe(&mut client, output, name).await,
+        Commands::Logs(logs) => handle_logs(&mut client, output, logs).await,
+        // Exits with the remote command's code rather than 0.
+        Commands::Exec(exec) => return handle_exec(&mut client, exec).await,
+        Commands::Status { name } => handle_status(&mut client, output, name).await,
+        Commands::Backup { name } => handle_backup(&mut client, output, name, idempotency_key).await,
+        Commands::Backups { name } => handle_list_backups(&mut client, output, name).await,
+        Commands::Schedules { name } => handle_list_schedules(&mut client, output, name).await,
+        Commands::Restore { name, backup_id } => {
+            handle_restore(&mut client, output, name, backup_id, idempotency_key).await
+        }
+        Commands::Operation { operation_id } => {
+            handle_get_operation(&mut client, output, operation_id).await
+        }
+        Commands::Context { .. } => unreachable!("context commands do not need a connection"),
+        Commands::Ping => unreachable!("ping uses the health client"),
+    };
+
+    res.map(|()| 0)
 }
@@ -0,0 +1,203 @@
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::path::Path;
+
+use libprovision::hello_world::{JobKind, ResourceLimits, Schedule, ServiceSpec};
+use serde::Deserialize;
+
+/// The services file read by `apply` and `diff`.
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    #[serde(default)]
+    pub services: Vec<ServiceManifest>,
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ServiceManifest {
+    pub name: String,
+    /// Left to the daemon's default when unset.
+    #[serde(default)]
+    pub blueprint: Option<String>,
+    #[serde(default)]
+    pub parameters: BTreeMap<String, Scalar>,
+    #[serde(default)]
+    pub env: BTreeMap<String, Scalar>,
+    #[serde(default)]
+    pub resources: Option<Resources>,
+    /// Run as a `svc-<name>` user of its own.
+    #[serde(default)]
+    pub dedicated_user: bool,
+    #[serde(default)]
+    pub schedules: Vec<ScheduleManifest>,
+}
+
+/// A job the daemon runs for the service, for example
+/// `{ job: backup, cron: "0 3 * * *" }`.
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ScheduleManifest {
+    pub job: Job,
+    pub cron: String,
+    #[serde(default)]
+    pub jitter_seconds: u32,
+    /// Pull jobs only, restart the service when a new image came down.
+    #[serde(default)]
+    pub restart_on_change: bool,
+}
+
+#[derive(Deserialize, Debug, PartialEq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum Job {
+    Backup,
+    Pull,
+    HealthCheck,
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Resources {
+    #[serde(default)]
+    pub cpus: Option<Scalar>,
+    #[serde(default)]
+    pub memory: Option<String>,
+    #[serde(default)]
+    pub pids: Option<i64>,
+}
+
+/// Lets values like `PORT: 5432` or `cpus: 0.5` be written without quotes.
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum Scalar {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+}
+
+impl Display for Scalar {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Scalar::Bool(value) => write!(f, "{value}"),
+            Scalar::Int(value) => write!(f, "{value}"),
+            Scalar::Float(value) => write!(f, "{value}"),
+            Scalar::String(value) => write!(f, "{value}"),
+        }
+    }
+}
+
+impl Manifest {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("failed to read manifest {path}: {e}", path = path.display()))?;
+
+        Self::parse(&contents).map_err(|e| format!("invalid manifest {path}: {e}", path = path.display()))
+    }
+
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        serde_yaml::from_str(contents).map_err(|e| e.to_string())
+    }
+
+    pub fn into_specs(self) -> Vec<ServiceSpec> {
+        self.services.into_iter().map(ServiceSpec::from).collect()
+    }
+}
+
+fn to_strings(values: BTreeMap<String, Scalar>) -> BTreeMap<String, String> {
+    values
+        .into_iter()
+        .map(|(key, value)| (key, value.to_string()))
+        .collect()
+}
+
+impl From<ServiceManifest> for ServiceSpec {
+    fn from(service: ServiceManifest) -> Self {
+        ServiceSpec {
+            name: service.name,
+            blueprint: service.blueprint.unwrap_or_default(),
+            parameters: to_strings(service.parameters),
+            env: to_strings(service.env),
+            resources: service.resources.map(|resources| ResourceLimits {
+                cpus: resources.cpus.map(|c| c.to_string()).unwrap_or_default(),
+                memory: resources.memory.unwrap_or_default(),
+                pids: resources.pids.unwrap_or_default(),
+            }),
+            dedicated_user: service.dedicated_user,
+            schedules: service.schedules.into_iter().map(Schedule::from).collect(),
+        }
+    }
+}
+
+impl From<ScheduleManifest> for Schedule {
+    fn from(schedule: ScheduleManifest) -> Self {
+        let job = match schedule.job {
+            Job::Backup => JobKind::Backup,
+            Job::Pull => JobKind::Pull,
+            Job::HealthCheck => JobKind::HealthCheck,
+        };
+
+        Schedule {
+            job: job as i32,
+            cron: schedule.cron,
+            jitter_seconds: schedule.jitter_seconds,
+            restart_on_change: schedule.restart_on_change,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_manifest_parsed() {
+        let manifest = Manifest::parse(
+            r#"
+services:
+  - name: billing
+    blueprint: postgres
+    parameters:
+      image: postgres:17
+    env:
+      TZ: UTC
+      PGPORT: 5433
+    resources:
+      cpus: 0.5
+      memory: 512m
+    schedules:
+      - job: backup
+        cron: "0 3 * * *"
+        jitter_seconds: 600
+  - name: reports
+"#,
+        )
+        .expect("Manifest should parse");
+
+        let specs = manifest.into_specs();
+
+        assert_eq!(specs.len(), 2);
+        assert_eq!(specs[0].parameters.get("image").map(String::as_str), Some("postgres:17"));
+        assert_eq!(specs[0].env.get("PGPORT").map(String::as_str), Some("5433"));
+
+        let resources = specs[0].resources.as_ref().expect("Resources should be set");
+        assert_eq!(resources.cpus, "0.5");
+        assert_eq!(resources.memory, "512m");
+
+        assert_eq!(specs[0].schedules.len(), 1);
+        assert_eq!(specs[0].schedules[0].job(), JobKind::Backup);
+        assert_eq!(specs[0].schedules[0].jitter_seconds, 600);
+
+        assert_eq!(specs[1].blueprint, "", "Blueprint should be left to the daemon");
+        assert!(specs[1].resources.is_none());
+    }
+
+    #[test]
+    pub fn test_unknown_fields_rejected() {
+        let err = Manifest::parse("services:\n  - name: billing\n    blueprnt: postgres\n")
+            .expect_err("Misspelled field should be rejected");
+
+        assert!(err.contains("blueprnt"), "Error should name the field: {err}");
+    }
+}
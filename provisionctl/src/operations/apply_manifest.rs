@@ -0,0 +1,70 @@
+use std::path::Path;
+
+use libprovision::hello_world::{ApplyRequest, ApplyResponse, ChangeAction};
+use log::info;
+use tonic::{Code, Request, Status};
+
+use crate::client::Client;
+use crate::manifest::Manifest;
+use crate::output::{OutputFormat, print_table};
+
+/// Sends the manifest to the daemon, `dry_run` only reports what would change.
+pub async fn handle_apply(
+    client: &mut Client,
+    output: OutputFormat,
+    file: &Path,
+    prune: bool,
+    dry_run: bool,
+    idempotency_key: String,
+) -> Result<(), Status> {
+    info!("handling apply request for {}", file.display());
+
+    let manifest = Manifest::load(file).map_err(|e| Status::new(Code::InvalidArgument, e))?;
+
+    let res = client
+        .apply(Request::new(ApplyRequest {
+            services: manifest.into_specs(),
+            prune,
+            dry_run,
+            idempotency_key,
+        }))
+        .await?;
+
+    info!("got apply response {:?}", res.get_ref());
+    output.print(res.get_ref(), print_changes);
+    Ok(())
+}
+
+fn print_changes(res: &ApplyResponse) {
+    let rows: Vec<Vec<String>> = res
+        .changes
+        .iter()
+        .map(|change| {
+            vec![
+                action_name(change.action).to_owned(),
+                change.service_name.clone(),
+                change.fields.join(", "),
+            ]
+        })
+        .collect();
+
+    if rows.is_empty() {
+        println!("No services in the manifest or on the host");
+    } else {
+        print_table(&["ACTION", "SERVICE", "CHANGES"], &rows);
+    }
+
+    if !res.operation_id.is_empty() {
+        println!("Applied (operation {operation_id})", operation_id = res.operation_id);
+    }
+}
+
+fn action_name(action: i32) -> &'static str {
+    match ChangeAction::try_from(action) {
+        Ok(ChangeAction::Unchanged) => "unchanged",
+        Ok(ChangeAction::Create) => "create",
+        Ok(ChangeAction::Update) => "update",
+        Ok(ChangeAction::Delete) => "delete",
+        _ => "unknown",
+    }
+}
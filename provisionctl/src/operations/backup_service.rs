@@ -0,0 +1,36 @@
+use libprovision::hello_world::BackupRequest;
+use log::info;
+use tonic::{Request, Status};
+
+use crate::client::Client;
+use crate::output::OutputFormat;
+
+pub async fn handle_backup(
+    client: &mut Client,
+    output: OutputFormat,
+    service_name: String,
+    idempotency_key: String,
+) -> Result<(), Status> {
+    info!("handling backup request");
+
+    let res = client
+        .backup(Request::new(BackupRequest {
+            idempotency_key,
+            service_name: service_name.clone(),
+        }))
+        .await?;
+
+    info!("got backup response {:?}", res.get_ref());
+    output.print(res.get_ref(), |res| match &res.backup {
+        Some(backup) => println!(
+            "Backed up service '{service_name}' as {backup_id} (operation {operation_id})",
+            backup_id = backup.backup_id,
+            operation_id = res.operation_id
+        ),
+        None => println!(
+            "Backed up service '{service_name}' (operation {operation_id})",
+            operation_id = res.operation_id
+        ),
+    });
+    Ok(())
+}
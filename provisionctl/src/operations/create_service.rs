@@ -1,14 +1,38 @@
-use libprovision::hello_world::{CreateRequest, ProvisionerClient};
+use libprovision::hello_world::CreateRequest;
 use log::info;
-use tonic::Request;
-use tonic::transport::Channel;
+use tonic::{Request, Status};
 
-pub async fn handle_create(client: &mut ProvisionerClient<Channel>, service_name: String) {
+use crate::client::Client;
+use crate::cmd::ResourceArgs;
+use crate::output::OutputFormat;
+
+pub async fn handle_create(
+    client: &mut Client,
+    output: OutputFormat,
+    service_name: String,
+    blueprint: String,
+    dedicated_user: bool,
+    resources: ResourceArgs,
+    idempotency_key: String,
+) -> Result<(), Status> {
     info!("handling create request");
     let res = client
-        .create(Request::new(CreateRequest { service_name }))
-        .await
-        .unwrap();
-    
+        .create(Request::new(CreateRequest {
+            service_name: service_name.clone(),
+            idempotency_key,
+            blueprint: blueprint.clone(),
+            dedicated_user,
+            resources: resources.into_limits(),
+            ..Default::default()
+        }))
+        .await?;
+
     info!("got create response {:?}", res.get_ref());
+    output.print(res.get_ref(), |res| {
+        println!(
+            "Created service '{service_name}' from blueprint '{blueprint}' (operation {operation_id})",
+            operation_id = res.operation_id
+        )
+    });
+    Ok(())
 }
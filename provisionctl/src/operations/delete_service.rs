@@ -1,14 +1,33 @@
-use libprovision::hello_world::{DeleteRequest, ProvisionerClient};
+use libprovision::hello_world::DeleteRequest;
 use log::info;
-use tonic::Request;
-use tonic::transport::Channel;
+use tonic::{Request, Status};
 
-pub async fn handle_delete(client: &mut ProvisionerClient<Channel>) {
+use crate::client::Client;
+use crate::output::OutputFormat;
+
+pub async fn handle_delete(
+    client: &mut Client,
+    output: OutputFormat,
+    service_name: String,
+    purge_volumes: bool,
+    idempotency_key: String,
+) -> Result<(), Status> {
     info!("handling delete request");
 
     let res = client
-        .delete(Request::new(DeleteRequest::default()))
-        .await
-        .unwrap();
+        .delete(Request::new(DeleteRequest {
+            idempotency_key,
+            service_name: service_name.clone(),
+            purge_volumes,
+        }))
+        .await?;
+
     info!("got delete response {:?}", res.get_ref());
+    output.print(res.get_ref(), |res| {
+        println!(
+            "Deleted service '{service_name}' (operation {operation_id})",
+            operation_id = res.operation_id
+        )
+    });
+    Ok(())
 }
@@ -0,0 +1,77 @@
+use libprovision::hello_world::{DescribeRequest, ServiceDescription};
+use log::info;
+use tonic::{Request, Status};
+
+use crate::client::Client;
+use crate::output::{OutputFormat, print_table};
+
+pub async fn handle_describe(
+    client: &mut Client,
+    output: OutputFormat,
+    service_name: String,
+) -> Result<(), Status> {
+    info!("handling describe request");
+
+    let res = client
+        .describe(Request::new(DescribeRequest { service_name }))
+        .await?;
+    info!("got description {:?}", res.get_ref());
+
+    output.print(res.get_ref(), print_description);
+    Ok(())
+}
+
+fn print_description(description: &ServiceDescription) {
+    let join = |pairs: Vec<String>| match pairs.is_empty() {
+        true => "-".to_owned(),
+        false => pairs.join(", "),
+    };
+
+    let parameters = description
+        .parameters
+        .iter()
+        .map(|(key, value)| format!("{key}={value}"))
+        .collect();
+
+    let resources = description.resources.clone().unwrap_or_default();
+    let limits = [
+        ("cpus", resources.cpus),
+        ("memory", resources.memory),
+        ("pids", match resources.pids {
+            0 => String::new(),
+            pids => pids.to_string(),
+        }),
+    ]
+    .into_iter()
+    .filter(|(_, value)| !value.is_empty())
+    .map(|(key, value)| format!("{key}={value}"))
+    .collect();
+
+    let ports = description
+        .ports
+        .iter()
+        .map(|port| format!("{}={}->{}", port.name, port.host_port, port.container_port))
+        .collect();
+
+    println!("Name:       {}", description.name);
+    println!("Blueprint:  {}", description.blueprint);
+    println!("Parameters: {}", join(parameters));
+    println!("Env:        {}", join(description.env_keys.clone()));
+    println!("Resources:  {}", join(limits));
+    println!("Ports:      {}", join(ports));
+    println!();
+
+    let rows: Vec<Vec<String>> = description
+        .files
+        .iter()
+        .map(|file| {
+            let status = match (file.ok, file.mode.as_str()) {
+                (true, _) => "ok".to_owned(),
+                (false, "") => "missing".to_owned(),
+                (false, _) => format!("expected {} {}", file.expected_mode, file.expected_owner),
+            };
+            vec![file.path.clone(), file.mode.clone(), file.owner.clone(), status]
+        })
+        .collect();
+    print_table(&["PATH", "MODE", "OWNER", "STATUS"], &rows);
+}
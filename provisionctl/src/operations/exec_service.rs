@@ -0,0 +1,147 @@
+use std::io::Read;
+use std::mem::MaybeUninit;
+
+use futures_util::stream;
+use libprovision::hello_world::{ExecInput, ExecStart, TerminalSize, exec_input, exec_output};
+use log::info;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::signal::unix::{SignalKind, signal};
+use tokio::sync::mpsc;
+use tonic::{Code, Request, Status};
+
+use crate::client::Client;
+use crate::cmd::ExecArgs;
+
+/// Puts the terminal in raw mode so keys go to the remote command as they
+/// are typed, and puts it back when dropped.
+struct RawMode(libc::termios);
+
+impl RawMode {
+    fn enable() -> Option<Self> {
+        let mut termios = MaybeUninit::uninit();
+        // SAFETY: tcgetattr fills the struct when it returns 0.
+        let original = unsafe {
+            if libc::tcgetattr(libc::STDIN_FILENO, termios.as_mut_ptr()) != 0 {
+                return None;
+            }
+            termios.assume_init()
+        };
+
+        let mut raw = original;
+        // SAFETY: raw is a valid termios and stdin a terminal.
+        unsafe {
+            libc::cfmakeraw(&mut raw);
+            if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw) != 0 {
+                return None;
+            }
+        }
+        Some(Self(original))
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        // SAFETY: restores the settings read in enable.
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.0);
+        }
+    }
+}
+
+fn is_terminal(fd: libc::c_int) -> bool {
+    // SAFETY: isatty only looks at the descriptor.
+    unsafe { libc::isatty(fd) == 1 }
+}
+
+fn terminal_size() -> Option<TerminalSize> {
+    let mut size = MaybeUninit::<libc::winsize>::uninit();
+    // SAFETY: TIOCGWINSZ fills the struct when it returns 0.
+    let size = unsafe {
+        if libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, size.as_mut_ptr()) != 0 {
+            return None;
+        }
+        size.assume_init()
+    };
+    Some(TerminalSize {
+        rows: size.ws_row.into(),
+        columns: size.ws_col.into(),
+    })
+}
+
+fn message(input: exec_input::Input) -> ExecInput {
+    ExecInput { input: Some(input) }
+}
+
+async fn write(out: &mut (impl AsyncWrite + Unpin), bytes: &[u8]) -> Result<(), Status> {
+    let failed = |e: std::io::Error| Status::new(Code::Internal, format!("failed to write output: {e}"));
+    out.write_all(bytes).await.map_err(failed)?;
+    out.flush().await.map_err(failed)
+}
+
+/// Runs the command and returns its exit code.
+pub async fn handle_exec(client: &mut Client, exec: ExecArgs) -> Result<u8, Status> {
+    info!("handling exec request");
+
+    let tty = !exec.no_tty && is_terminal(libc::STDIN_FILENO) && is_terminal(libc::STDOUT_FILENO);
+    let _raw_mode = tty.then(RawMode::enable).flatten();
+    let (sender, receiver) = mpsc::channel(16);
+    let _ = sender
+        .send(message(exec_input::Input::Start(ExecStart {
+            service_name: exec.name,
+            compose_service: exec.service.unwrap_or_default(),
+            command: exec.command,
+            tty,
+            size: tty.then(terminal_size).flatten(),
+        })))
+        .await;
+
+    // A plain thread rather than tokio's stdin, a read blocked on the
+    // terminal would otherwise keep the runtime from shutting down.
+    let stdin = sender.clone();
+    std::thread::spawn(move || {
+        let mut buffer = vec![0; 8 * 1024];
+        loop {
+            match std::io::stdin().read(&mut buffer) {
+                Ok(0) | Err(_) => break,
+                Ok(read) => {
+                    if stdin.blocking_send(message(exec_input::Input::Stdin(buffer[..read].to_vec()))).is_err() {
+                        return;
+                    }
+                }
+            }
+        }
+        let _ = stdin.blocking_send(message(exec_input::Input::CloseStdin(true)));
+    });
+
+    if tty {
+        let mut resized = signal(SignalKind::window_change())
+            .map_err(|e| Status::new(Code::Internal, format!("failed to watch the terminal size: {e}")))?;
+        tokio::spawn(async move {
+            while resized.recv().await.is_some() {
+                let Some(size) = terminal_size() else { continue };
+                if sender.send(message(exec_input::Input::Resize(size))).await.is_err() {
+                    break;
+                }
+            }
+        });
+    }
+
+    let input = stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|input| (input, receiver))
+    });
+    let mut output = client.exec(Request::new(input)).await?.into_inner();
+
+    let mut stdout = tokio::io::stdout();
+    let mut stderr = tokio::io::stderr();
+    while let Some(chunk) = output.message().await? {
+        match chunk.output {
+            Some(exec_output::Output::Stdout(bytes)) => write(&mut stdout, &bytes).await?,
+            Some(exec_output::Output::Stderr(bytes)) => write(&mut stderr, &bytes).await?,
+            // Codes outside of what a process can exit with still fail.
+            Some(exec_output::Output::ExitCode(code)) => return Ok(u8::try_from(code).unwrap_or(1)),
+            None => (),
+        }
+    }
+
+    Err(Status::new(Code::Unavailable, "the connection closed before the command exited"))
+}
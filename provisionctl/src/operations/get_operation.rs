@@ -0,0 +1,54 @@
+use libprovision::hello_world::{GetOperationRequest, Operation, OperationStatus};
+use log::info;
+use tonic::{Request, Status};
+
+use crate::client::Client;
+use crate::output::OutputFormat;
+
+pub async fn handle_get_operation(
+    client: &mut Client,
+    output: OutputFormat,
+    operation_id: String,
+) -> Result<(), Status> {
+    info!("handling get operation request");
+
+    let res = client
+        .get_operation(Request::new(GetOperationRequest { operation_id }))
+        .await?;
+    info!("got operation {:?}", res.get_ref());
+
+    output.print(res.get_ref(), print_operation);
+    Ok(())
+}
+
+fn print_operation(operation: &Operation) {
+    println!("Operation: {}", operation.operation_id);
+    println!("Kind:      {}", operation.kind);
+    println!("Service:   {}", operation.service_name);
+    println!("Status:    {}", status_name(operation.status));
+    if !operation.error.is_empty() {
+        println!("Error:     {}", operation.error);
+    }
+
+    println!("Steps:");
+    for step in &operation.steps {
+        // Apply operations span several services, so name the one each step is for.
+        let name = match step.service_name == operation.service_name {
+            true => step.name.clone(),
+            false => format!("{}: {}", step.service_name, step.name),
+        };
+        match step.error.as_str() {
+            "" => println!("  {:<10} {}", status_name(step.status), name),
+            err => println!("  {:<10} {} ({})", status_name(step.status), name, err),
+        }
+    }
+}
+
+fn status_name(status: i32) -> &'static str {
+    match OperationStatus::try_from(status) {
+        Ok(OperationStatus::Running) => "running",
+        Ok(OperationStatus::Succeeded) => "succeeded",
+        Ok(OperationStatus::Failed) => "failed",
+        _ => "unknown",
+    }
+}
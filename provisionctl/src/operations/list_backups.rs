@@ -0,0 +1,68 @@
+use std::time::{Duration, UNIX_EPOCH};
+
+use libprovision::hello_world::ListBackupsRequest;
+use log::info;
+use tonic::{Request, Status};
+
+use crate::client::Client;
+use crate::output::{OutputFormat, print_table};
+
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    match unit {
+        0 => format!("{bytes} B"),
+        _ => format!("{size:.1} {unit}", unit = UNITS[unit]),
+    }
+}
+
+pub async fn handle_list_backups(
+    client: &mut Client,
+    output: OutputFormat,
+    service_name: String,
+) -> Result<(), Status> {
+    info!("handling list backups request");
+
+    let res = client
+        .list_backups(Request::new(ListBackupsRequest { service_name }))
+        .await?;
+
+    info!("got list backups response {:?}", res.get_ref());
+    output.print(res.get_ref(), |res| {
+        let rows: Vec<Vec<String>> = res
+            .backups
+            .iter()
+            .map(|backup| {
+                let created_at = UNIX_EPOCH + Duration::from_secs(backup.created_at.max(0) as u64);
+                vec![
+                    backup.backup_id.clone(),
+                    humantime::format_rfc3339_seconds(created_at).to_string(),
+                    format_size(backup.size_bytes),
+                    backup.engine.clone(),
+                    backup.image.clone(),
+                ]
+            })
+            .collect();
+        print_table(&["BACKUP", "CREATED", "SIZE", "ENGINE", "IMAGE"], &rows)
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_sizes_formatted() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(5 * 1024 * 1024 * 1024 * 1024), "5120.0 GiB");
+    }
+}
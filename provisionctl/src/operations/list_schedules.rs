@@ -0,0 +1,64 @@
+use std::time::{Duration, UNIX_EPOCH};
+
+use libprovision::hello_world::{JobKind, ListSchedulesRequest, OperationStatus, ScheduledJob};
+use log::info;
+use tonic::{Request, Status};
+
+use crate::client::Client;
+use crate::output::{OutputFormat, print_table};
+
+fn format_time(secs: i64) -> String {
+    match secs {
+        0 => "-".to_owned(),
+        secs => humantime::format_rfc3339_seconds(UNIX_EPOCH + Duration::from_secs(secs.max(0) as u64)).to_string(),
+    }
+}
+
+fn row(job: &ScheduledJob) -> Vec<String> {
+    let schedule = job.schedule.clone().unwrap_or_default();
+    let job_name = match schedule.job() {
+        JobKind::Backup => "backup",
+        JobKind::Pull => "pull",
+        JobKind::HealthCheck => "health_check",
+        JobKind::Unspecified => "-",
+    };
+    let status = match job.last_status() {
+        OperationStatus::Succeeded => "ok".to_owned(),
+        OperationStatus::Failed => format!("failed: {error}", error = job.last_error),
+        OperationStatus::Running => "running".to_owned(),
+        OperationStatus::Unspecified => "-".to_owned(),
+    };
+
+    vec![
+        job.service_name.clone(),
+        match job.global {
+            true => format!("{job_name} (global)"),
+            false => job_name.to_owned(),
+        },
+        schedule.cron,
+        format_time(job.next_run),
+        format_time(job.last_run),
+        status,
+    ]
+}
+
+pub async fn handle_list_schedules(
+    client: &mut Client,
+    output: OutputFormat,
+    service_name: Option<String>,
+) -> Result<(), Status> {
+    info!("handling list schedules request");
+
+    let res = client
+        .list_schedules(Request::new(ListSchedulesRequest {
+            service_name: service_name.unwrap_or_default(),
+        }))
+        .await?;
+
+    info!("got list schedules response {:?}", res.get_ref());
+    output.print(res.get_ref(), |res| {
+        let rows: Vec<Vec<String>> = res.jobs.iter().map(row).collect();
+        print_table(&["SERVICE", "JOB", "CRON", "NEXT RUN", "LAST RUN", "STATUS"], &rows)
+    });
+    Ok(())
+}
@@ -0,0 +1,76 @@
+use libprovision::hello_world::{ListRequest, ServiceSummary};
+use log::info;
+use serde::Serialize;
+use tonic::{Request, Status};
+
+use crate::client::{Client, connect};
+use crate::config::Config;
+use crate::output::{ErrorBody, OutputFormat, print_table};
+
+#[derive(Serialize)]
+struct ContextServices {
+    context: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    services: Option<Vec<ServiceSummary>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<ErrorBody>,
+}
+
+pub async fn handle_list(client: &mut Client, output: OutputFormat) -> Result<(), Status> {
+    info!("handling list request");
+
+    let res = client.list(Request::new(ListRequest {})).await?;
+
+    info!("got list response {:?}", res.get_ref());
+    output.print(res.get_ref(), |res| {
+        let rows: Vec<Vec<String>> = res.services.iter().map(|s| vec![s.name.clone()]).collect();
+        print_table(&["SERVICE"], &rows)
+    });
+    Ok(())
+}
+
+/// Lists the services of every context in the config file. A host that fails
+/// does not stop the others, its error is reported in its place and the exit
+/// code is that of the first failure.
+pub async fn handle_list_all(config: &Config, output: OutputFormat) -> u8 {
+    info!("handling list request for all contexts");
+
+    let mut results = Vec::new();
+    for (name, context) in &config.contexts {
+        let res = match connect(context).await {
+            Ok(mut client) => client.list(Request::new(ListRequest {})).await,
+            Err(status) => Err(status),
+        };
+
+        results.push(match res {
+            Ok(res) => ContextServices {
+                context: name.clone(),
+                services: Some(res.into_inner().services),
+                error: None,
+            },
+            Err(status) => ContextServices {
+                context: name.clone(),
+                services: None,
+                error: Some(ErrorBody::new(&status)),
+            },
+        });
+    }
+
+    output.print(&results, |results| {
+        let mut rows = Vec::new();
+        for result in results {
+            if let Some(error) = &result.error {
+                rows.push(vec![result.context.clone(), format!("<error: {}>", error.message)]);
+            }
+            for service in result.services.iter().flatten() {
+                rows.push(vec![result.context.clone(), service.name.clone()]);
+            }
+        }
+        print_table(&["CONTEXT", "SERVICE"], &rows)
+    });
+
+    results
+        .iter()
+        .find_map(|result| result.error.as_ref().map(|e| e.exit_code))
+        .unwrap_or(0)
+}
@@ -0,0 +1,133 @@
+use serde::Serialize;
+use tonic::{Code, Status};
+
+use crate::cmd::ContextCommands;
+use crate::config::{Config, Context};
+use crate::output::{OutputFormat, print_table};
+
+#[derive(Serialize)]
+struct ContextSummary {
+    name: String,
+    endpoint: String,
+    current: bool,
+    tls: bool,
+    token: bool,
+}
+
+#[derive(Serialize)]
+struct CurrentContext {
+    current_context: Option<String>,
+}
+
+pub fn handle_context(
+    config: &mut Config,
+    output: OutputFormat,
+    command: ContextCommands,
+) -> Result<(), Status> {
+    match command {
+        ContextCommands::List => {
+            let contexts: Vec<ContextSummary> = config
+                .contexts
+                .iter()
+                .map(|(name, context)| ContextSummary {
+                    name: name.clone(),
+                    endpoint: context.endpoint.clone(),
+                    current: config.current_context.as_ref() == Some(name),
+                    tls: context.ca_cert.is_some(),
+                    token: context.token.is_some(),
+                })
+                .collect();
+
+            output.print(&contexts, |contexts| {
+                let rows: Vec<Vec<String>> = contexts
+                    .iter()
+                    .map(|c| {
+                        vec![
+                            if c.current { "*" } else { "" }.to_owned(),
+                            c.name.clone(),
+                            c.endpoint.clone(),
+                            if c.tls { "yes" } else { "no" }.to_owned(),
+                        ]
+                    })
+                    .collect();
+                print_table(&["CURRENT", "NAME", "ENDPOINT", "TLS"], &rows)
+            });
+        }
+        ContextCommands::Current => {
+            let current = CurrentContext {
+                current_context: config.current_context.clone(),
+            };
+            output.print(&current, |current| match &current.current_context {
+                Some(name) => println!("{name}"),
+                None => println!("No current context"),
+            });
+        }
+        ContextCommands::Use { name } => {
+            config.context(&name).map_err(not_found)?;
+            config.current_context = Some(name.clone());
+            config.save().map_err(failed)?;
+
+            output.print(&CurrentContext { current_context: Some(name) }, |current| {
+                println!("Switched to context '{}'", current.current_context.as_deref().unwrap_or_default())
+            });
+        }
+        ContextCommands::Set {
+            name,
+            endpoint,
+            ca_cert,
+            client_cert,
+            client_key,
+            token,
+        } => {
+            let context = match (config.contexts.get(&name), endpoint) {
+                (Some(existing), endpoint) => Context {
+                    endpoint: endpoint.unwrap_or_else(|| existing.endpoint.clone()),
+                    ..existing.clone()
+                },
+                (None, Some(endpoint)) => Context {
+                    endpoint,
+                    ..Context::default()
+                },
+                (None, None) => {
+                    return Err(Status::new(
+                        Code::InvalidArgument,
+                        format!("context '{name}' does not exist yet, --endpoint is required"),
+                    ));
+                }
+            };
+
+            let context = Context {
+                ca_cert: ca_cert.or(context.ca_cert),
+                client_cert: client_cert.or(context.client_cert),
+                client_key: client_key.or(context.client_key),
+                token: token.or(context.token),
+                ..context
+            };
+
+            config.contexts.insert(name.clone(), context);
+            config.save().map_err(failed)?;
+
+            output.print(&name, |name| println!("Saved context '{name}'"));
+        }
+        ContextCommands::Delete { name } => {
+            config.context(&name).map_err(not_found)?;
+            config.contexts.remove(&name);
+            if config.current_context.as_ref() == Some(&name) {
+                config.current_context = None;
+            }
+            config.save().map_err(failed)?;
+
+            output.print(&name, |name| println!("Deleted context '{name}'"));
+        }
+    }
+
+    Ok(())
+}
+
+fn not_found(err: String) -> Status {
+    Status::new(Code::NotFound, err)
+}
+
+fn failed(err: String) -> Status {
+    Status::new(Code::FailedPrecondition, err)
+}
@@ -1,9 +1,37 @@
+mod apply_manifest;
+mod backup_service;
 mod create_service;
 mod restart_service;
+mod update_service;
 pub(crate) mod pull_service;
 pub(crate) mod delete_service;
+mod describe_service;
+mod exec_service;
+mod get_operation;
+mod list_backups;
+mod list_schedules;
+mod list_services;
+mod manage_contexts;
+mod ping_daemon;
+mod restore_service;
+mod service_logs;
+mod service_status;
 
+pub use apply_manifest::handle_apply;
+pub use backup_service::handle_backup;
 pub use create_service::handle_create;
 pub use restart_service::handle_restart;
+pub use update_service::handle_update;
 pub use pull_service::handle_pull;
 pub use delete_service::handle_delete;
+pub use describe_service::handle_describe;
+pub use exec_service::handle_exec;
+pub use get_operation::handle_get_operation;
+pub use list_backups::handle_list_backups;
+pub use list_schedules::handle_list_schedules;
+pub use list_services::{handle_list, handle_list_all};
+pub use manage_contexts::handle_context;
+pub use ping_daemon::handle_ping;
+pub use restore_service::handle_restore;
+pub use service_logs::handle_logs;
+pub use service_status::handle_status;
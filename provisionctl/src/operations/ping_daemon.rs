@@ -0,0 +1,77 @@
+use std::collections::BTreeMap;
+use std::time::Instant;
+
+use libprovision::dependencies;
+use log::info;
+use serde::Serialize;
+use tonic::{Code, Request, Status};
+use tonic_health::pb::HealthCheckRequest;
+use tonic_health::pb::health_check_response::ServingStatus;
+
+use crate::client::HealthClient;
+use crate::errors::exit_code;
+use crate::output::{OutputFormat, print_table};
+
+#[derive(Serialize)]
+struct PingResult {
+    endpoint: String,
+    status: &'static str,
+    latency_ms: u128,
+    dependencies: BTreeMap<&'static str, &'static str>,
+}
+
+/// Status of `service` in provisiond's health service. Services it does not
+/// report, for example dependencies of an older provisiond, are
+/// `SERVICE_UNKNOWN`.
+async fn check(client: &mut HealthClient, service: &str) -> Result<ServingStatus, Status> {
+    let request = Request::new(HealthCheckRequest { service: service.to_owned() });
+    match client.check(request).await {
+        Ok(response) => Ok(response.get_ref().status()),
+        Err(status) if status.code() == Code::NotFound => Ok(ServingStatus::ServiceUnknown),
+        Err(status) => Err(status),
+    }
+}
+
+/// Exits with 0 only while provisiond reports itself as serving.
+pub async fn handle_ping(client: &mut HealthClient, output: OutputFormat, endpoint: String) -> Result<u8, Status> {
+    info!("handling ping request");
+
+    let started = Instant::now();
+    let status = check(client, "").await?;
+    let latency_ms = started.elapsed().as_millis();
+
+    let mut result = PingResult {
+        endpoint,
+        status: status.as_str_name(),
+        latency_ms,
+        dependencies: BTreeMap::new(),
+    };
+    for dependency in dependencies::ALL {
+        let status = check(client, dependency).await?;
+        result.dependencies.insert(dependency, status.as_str_name());
+    }
+    info!("got health {:?}", status);
+
+    output.print(&result, print_ping);
+    match status {
+        ServingStatus::Serving => Ok(0),
+        _ => Ok(exit_code(Code::Unavailable)),
+    }
+}
+
+fn print_ping(result: &PingResult) {
+    println!(
+        "provisiond at {endpoint} is {status} ({latency} ms)",
+        endpoint = result.endpoint,
+        status = result.status,
+        latency = result.latency_ms
+    );
+    println!();
+
+    let rows: Vec<Vec<String>> = result
+        .dependencies
+        .iter()
+        .map(|(dependency, status)| vec![dependency.to_string(), status.to_string()])
+        .collect();
+    print_table(&["DEPENDENCY", "STATUS"], &rows);
+}
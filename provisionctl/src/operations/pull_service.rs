@@ -1,14 +1,33 @@
-use libprovision::hello_world::{ProvisionerClient, PullRequest};
+use libprovision::hello_world::PullRequest;
 use log::info;
-use tonic::Request;
-use tonic::transport::Channel;
+use tonic::{Request, Status};
 
-pub async fn handle_pull(client: &mut ProvisionerClient<Channel>) {
+use crate::client::Client;
+use crate::output::OutputFormat;
+
+pub async fn handle_pull(
+    client: &mut Client,
+    output: OutputFormat,
+    service_name: String,
+    restart_on_change: bool,
+    idempotency_key: String,
+) -> Result<(), Status> {
     info!("handling pull request");
 
     let res = client
-        .pull(Request::new(PullRequest::default()))
-        .await
-        .unwrap();
+        .pull(Request::new(PullRequest {
+            idempotency_key,
+            service_name: service_name.clone(),
+            restart_on_change,
+        }))
+        .await?;
+
     info!("got pull response {:?}", res.get_ref());
+    output.print(res.get_ref(), |res| {
+        println!(
+            "Pulled images for service '{service_name}' (operation {operation_id})",
+            operation_id = res.operation_id
+        )
+    });
+    Ok(())
 }
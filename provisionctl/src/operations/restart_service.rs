@@ -1,14 +1,31 @@
-use libprovision::hello_world::{ProvisionerClient, RestartRequest};
+use libprovision::hello_world::RestartRequest;
 use log::info;
-use tonic::Request;
-use tonic::transport::Channel;
+use tonic::{Request, Status};
 
-pub async fn handle_restart(client: &mut ProvisionerClient<Channel>) {
+use crate::client::Client;
+use crate::output::OutputFormat;
+
+pub async fn handle_restart(
+    client: &mut Client,
+    output: OutputFormat,
+    service_name: String,
+    idempotency_key: String,
+) -> Result<(), Status> {
     info!("handling restart request");
 
     let res = client
-        .restart(Request::new(RestartRequest::default()))
-        .await
-        .unwrap();
+        .restart(Request::new(RestartRequest {
+            idempotency_key,
+            service_name: service_name.clone(),
+        }))
+        .await?;
+
     info!("got restart response {:?}", res.get_ref());
+    output.print(res.get_ref(), |res| {
+        println!(
+            "Restarted service '{service_name}' (operation {operation_id})",
+            operation_id = res.operation_id
+        )
+    });
+    Ok(())
 }
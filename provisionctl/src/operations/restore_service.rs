@@ -0,0 +1,33 @@
+use libprovision::hello_world::RestoreRequest;
+use log::info;
+use tonic::{Request, Status};
+
+use crate::client::Client;
+use crate::output::OutputFormat;
+
+pub async fn handle_restore(
+    client: &mut Client,
+    output: OutputFormat,
+    service_name: String,
+    backup_id: String,
+    idempotency_key: String,
+) -> Result<(), Status> {
+    info!("handling restore request");
+
+    let res = client
+        .restore(Request::new(RestoreRequest {
+            idempotency_key,
+            service_name: service_name.clone(),
+            backup_id: backup_id.clone(),
+        }))
+        .await?;
+
+    info!("got restore response {:?}", res.get_ref());
+    output.print(res.get_ref(), |res| {
+        println!(
+            "Restored service '{service_name}' from {backup_id} (operation {operation_id})",
+            operation_id = res.operation_id
+        )
+    });
+    Ok(())
+}
@@ -0,0 +1,32 @@
+use libprovision::hello_world::LogsRequest;
+use log::info;
+use tonic::{Request, Status};
+
+use crate::client::Client;
+use crate::cmd::LogsArgs;
+use crate::output::OutputFormat;
+
+pub async fn handle_logs(client: &mut Client, output: OutputFormat, logs: LogsArgs) -> Result<(), Status> {
+    info!("handling logs request");
+
+    let journal = logs.journal;
+    let mut lines = client
+        .logs(Request::new(LogsRequest {
+            service_name: logs.name,
+            follow: logs.follow,
+            since: logs.since.unwrap_or_default(),
+            tail: logs.tail.unwrap_or_default(),
+            journal,
+        }))
+        .await?
+        .into_inner();
+
+    while let Some(line) = lines.message().await? {
+        output.print_item(&line, |line| match journal {
+            // Says which of the two sources each line came from.
+            true => println!("{origin} | {message}", origin = line.origin, message = line.message),
+            false => println!("{}", line.message),
+        });
+    }
+    Ok(())
+}
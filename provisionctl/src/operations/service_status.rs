@@ -0,0 +1,80 @@
+use std::time::{Duration, UNIX_EPOCH};
+
+use libprovision::hello_world::{GetHealthRequest, GetHealthResponse, HealthCheckResult, HealthStatus};
+use log::info;
+use tonic::{Request, Status};
+
+use crate::client::Client;
+use crate::output::{OutputFormat, print_table};
+
+pub async fn handle_status(
+    client: &mut Client,
+    output: OutputFormat,
+    service_name: String,
+) -> Result<(), Status> {
+    info!("handling status request");
+
+    let res = client
+        .get_health(Request::new(GetHealthRequest { service_name }))
+        .await?;
+    info!("got health {:?}", res.get_ref());
+
+    output.print(res.get_ref(), print_health);
+    Ok(())
+}
+
+fn status_name(status: HealthStatus) -> &'static str {
+    match status {
+        HealthStatus::Healthy => "healthy",
+        HealthStatus::Unhealthy => "unhealthy",
+        HealthStatus::Unknown | HealthStatus::Unspecified => "unknown",
+    }
+}
+
+fn format_time(secs: i64) -> String {
+    humantime::format_rfc3339_seconds(UNIX_EPOCH + Duration::from_secs(secs.max(0) as u64)).to_string()
+}
+
+fn row(check: &HealthCheckResult) -> Vec<String> {
+    let or_dash = |value: &str| match value {
+        "" => "-".to_owned(),
+        value => value.to_owned(),
+    };
+    let container = match (check.container_state.as_str(), check.container_health.as_str()) {
+        ("", _) => "-".to_owned(),
+        (state, "") => state.to_owned(),
+        (state, health) => format!("{state} ({health})"),
+    };
+    let probe = match (check.probe.as_str(), check.probe_ok) {
+        ("", _) => "-".to_owned(),
+        (probe, true) => format!("{probe} ok"),
+        (probe, false) => format!("{probe} failed"),
+    };
+
+    vec![
+        format_time(check.checked_at),
+        status_name(check.status()).to_owned(),
+        match check.unit_active {
+            true => "active".to_owned(),
+            false => "inactive".to_owned(),
+        },
+        container,
+        probe,
+        // Errors from systemctl can span lines, which would break the table.
+        or_dash(&check.error.replace('\n', " ")),
+    ]
+}
+
+fn print_health(health: &GetHealthResponse) {
+    println!(
+        "{name} is {status} since {since}",
+        name = health.service_name,
+        status = status_name(health.status()),
+        since = format_time(health.since)
+    );
+    println!();
+
+    // Newest first, the way someone looking into a problem reads it.
+    let rows: Vec<Vec<String>> = health.history.iter().rev().map(row).collect();
+    print_table(&["CHECKED", "STATUS", "UNIT", "CONTAINER", "PROBE", "ERROR"], &rows);
+}
@@ -0,0 +1,44 @@
+use libprovision::hello_world::UpdateServiceRequest;
+use log::info;
+use tonic::{Request, Status};
+
+use crate::client::Client;
+use crate::cmd::UpdateArgs;
+use crate::output::OutputFormat;
+
+pub async fn handle_update(
+    client: &mut Client,
+    output: OutputFormat,
+    args: UpdateArgs,
+    idempotency_key: String,
+) -> Result<(), Status> {
+    info!("handling update request");
+
+    let service_name = args.name;
+    let res = client
+        .update_service(Request::new(UpdateServiceRequest {
+            idempotency_key,
+            service_name: service_name.clone(),
+            parameters: args.params.into_iter().collect(),
+            unset_parameters: args.unset_params,
+            env: args.env.into_iter().collect(),
+            unset_env: args.unset_env,
+            resources: args.resources.into_limits(),
+            skip_restart: args.no_restart,
+        }))
+        .await?;
+
+    info!("got update response {:?}", res.get_ref());
+    output.print(res.get_ref(), |res| match res.changed_fields.is_empty() {
+        true => println!(
+            "Service '{service_name}' already matches (operation {operation_id})",
+            operation_id = res.operation_id
+        ),
+        false => println!(
+            "Updated {fields} of service '{service_name}' (operation {operation_id})",
+            fields = res.changed_fields.join(", "),
+            operation_id = res.operation_id
+        ),
+    });
+    Ok(())
+}
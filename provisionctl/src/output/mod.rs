@@ -0,0 +1,150 @@
+use std::collections::BTreeMap;
+
+use clap::ValueEnum;
+use serde::Serialize;
+use tonic::Status;
+use tonic_types::StatusExt;
+
+use crate::errors::{code_name, exit_code, operation_id, print_status};
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq)]
+pub enum OutputFormat {
+    /// Human readable text
+    #[default]
+    Table,
+    Json,
+    Yaml,
+}
+
+#[derive(Serialize)]
+struct ErrorOutput {
+    error: ErrorBody,
+}
+
+#[derive(Serialize)]
+pub struct ErrorBody {
+    code: &'static str,
+    pub exit_code: u8,
+    pub message: String,
+    reason: Option<String>,
+    domain: Option<String>,
+    metadata: BTreeMap<String, String>,
+    operation_id: Option<String>,
+}
+
+impl ErrorBody {
+    pub fn new(status: &Status) -> Self {
+        let details = status.get_error_details();
+        let info = details.error_info();
+
+        ErrorBody {
+            code: code_name(status.code()),
+            exit_code: exit_code(status.code()),
+            message: status.message().to_owned(),
+            reason: info.map(|i| i.reason.clone()),
+            domain: info.map(|i| i.domain.clone()),
+            metadata: info
+                .map(|i| i.metadata.clone().into_iter().collect())
+                .unwrap_or_default(),
+            operation_id: operation_id(status),
+        }
+    }
+}
+
+impl OutputFormat {
+    /// Prints a response, `table` renders it for humans when that format is
+    /// selected, otherwise the response is serialized as is.
+    pub fn print<T: Serialize>(&self, value: &T, table: impl FnOnce(&T)) {
+        match self {
+            OutputFormat::Table => table(value),
+            OutputFormat::Json => println!("{}", to_json(value)),
+            OutputFormat::Yaml => print!("{}", to_yaml(value)),
+        }
+    }
+
+    /// Prints one item of a stream. Json is written one item per line and
+    /// yaml one document per item, so the output can be read as it comes.
+    pub fn print_item<T: Serialize>(&self, value: &T, text: impl FnOnce(&T)) {
+        match self {
+            OutputFormat::Table => text(value),
+            OutputFormat::Json => println!("{}", serde_json::to_string(value).expect("responses are always serializable")),
+            OutputFormat::Yaml => print!("---\n{}", to_yaml(value)),
+        }
+    }
+
+    /// Machine readable formats print errors to stdout in the same format as
+    /// results, so a script only has to parse one stream.
+    pub fn print_error(&self, status: &Status) {
+        if *self == OutputFormat::Table {
+            return print_status(status);
+        }
+
+        let output = ErrorOutput {
+            error: ErrorBody::new(status),
+        };
+
+        self.print(&output, |_| ());
+    }
+}
+
+/// Prints rows as left aligned columns under upper case headers.
+pub fn print_table(headers: &[&str], rows: &[Vec<String>]) {
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.len()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+
+    let print_row = |cells: Vec<&str>| {
+        let line: Vec<String> = cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{cell:<width$}"))
+            .collect();
+        println!("{}", line.join("  ").trim_end());
+    };
+
+    print_row(headers.to_vec());
+    for row in rows {
+        print_row(row.iter().map(String::as_str).collect());
+    }
+}
+
+fn to_json<T: Serialize>(value: &T) -> String {
+    serde_json::to_string_pretty(value).expect("responses are always serializable")
+}
+
+fn to_yaml<T: Serialize>(value: &T) -> String {
+    serde_yaml::to_string(value).expect("responses are always serializable")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libprovision::hello_world::{Operation, OperationStatus, OperationStep};
+
+    #[test]
+    pub fn test_operation_schema() {
+        let operation = Operation {
+            operation_id: "id".to_owned(),
+            kind: "create".to_owned(),
+            service_name: "test_service".to_owned(),
+            status: OperationStatus::Failed as i32,
+            steps: vec![OperationStep {
+                name: "Create Folder".to_owned(),
+                status: OperationStatus::Succeeded as i32,
+                error: String::new(),
+                service_name: "test_service".to_owned(),
+            }],
+            error: "boom".to_owned(),
+        };
+
+        let json: serde_json::Value = serde_json::from_str(&to_json(&operation)).unwrap();
+
+        assert_eq!(json["operation_id"], "id");
+        assert_eq!(json["status"], "failed");
+        assert_eq!(json["steps"][0]["status"], "succeeded");
+        assert_eq!(json["steps"][0]["name"], "Create Folder");
+    }
+}
@@ -0,0 +1,98 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use serde::Serialize;
+use tracing::{info, warn};
+
+/// One line of the audit log.
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct AuditEntry {
+    /// Unix time in seconds.
+    pub time: u64,
+    /// The policy subject, empty if the caller could not be authenticated.
+    pub subject: String,
+    pub action: &'static str,
+    pub service_name: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub container: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub command: Vec<String>,
+    pub allowed: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Appends entries to a file as JSON lines. Without a file they only go to
+/// the daemon's log.
+#[derive(Default)]
+pub struct AuditLog {
+    path: Option<PathBuf>,
+    lock: Mutex<()>,
+}
+
+impl AuditLog {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path: Some(path),
+            lock: Mutex::new(()),
+        }
+    }
+
+    pub fn record(&self, entry: &AuditEntry) {
+        let line = serde_json::to_string(entry).expect("audit entries are always serializable");
+        info!("audit: {line}");
+
+        let Some(path) = &self.path else {
+            return;
+        };
+        let _guard = self.lock.lock().unwrap();
+        let result = OpenOptions::new()
+            .append(true)
+            .create(true)
+            .mode(0o600)
+            .open(path)
+            .and_then(|mut file| writeln!(file, "{line}"));
+        if let Err(e) = result {
+            warn!("Failed to write to audit log {path}: {e}", path = path.display());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    pub fn test_entries_appended() {
+        let path = PathBuf::from("/tmp/provisiond_tests/test_entries_appended");
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).expect("Failed to create test path");
+
+        let log = AuditLog::new(path.join("audit.log"));
+        for allowed in [false, true] {
+            log.record(&AuditEntry {
+                subject: "ops".to_owned(),
+                action: "exec",
+                service_name: "billing".to_owned(),
+                allowed,
+                ..Default::default()
+            });
+        }
+
+        let contents = fs::read_to_string(path.join("audit.log")).unwrap();
+        let lines: Vec<serde_json::Value> = contents.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1]["allowed"], true);
+        assert!(lines[0].get("exit_code").is_none());
+
+        fs::remove_dir_all(&path)
+            .unwrap_or_else(|_| panic!("Failed to delete test path at {}", path.display()));
+    }
+}
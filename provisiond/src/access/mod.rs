@@ -0,0 +1,5 @@
+mod audit_log;
+mod policy;
+
+pub use audit_log::{AuditEntry, AuditLog};
+pub use policy::Policy;
@@ -0,0 +1,135 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+use serde::Deserialize;
+use tonic::metadata::MetadataMap;
+use tonic::{Code, Status};
+
+/// Who may do what, read from the daemon's policy file:
+///
+/// ```json
+/// {"subjects": [{"name": "ops", "token": "...", "exec": {"services": ["*"], "commands": ["psql"]}}]}
+/// ```
+///
+/// Callers present the token as `authorization: Bearer <token>`.
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Policy {
+    #[serde(default)]
+    subjects: Vec<Subject>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Subject {
+    pub name: String,
+    token: String,
+    /// Leaving it out forbids exec.
+    #[serde(default)]
+    exec: Option<ExecGrant>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct ExecGrant {
+    /// Service names, `*` for all of them.
+    services: Vec<String>,
+    /// Programs that may be run, `*` for any.
+    commands: Vec<String>,
+}
+
+fn allows(patterns: &[String], value: &str) -> bool {
+    patterns.iter().any(|pattern| pattern == "*" || pattern == value)
+}
+
+/// Compares without stopping at the first difference, so the time taken
+/// says nothing about how much of a token was right.
+fn same_token(a: &str, b: &str) -> bool {
+    a.len() == b.len() && a.bytes().zip(b.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+impl Policy {
+    /// A missing file is an empty policy, which allows nothing.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let json = match fs::read_to_string(path) {
+            Ok(json) => json,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(format!("failed to read {path}: {e}", path = path.display())),
+        };
+
+        let policy: Policy =
+            serde_json::from_str(&json).map_err(|e| format!("invalid policy in {path}: {e}", path = path.display()))?;
+        if let Some(subject) = policy.subjects.iter().find(|subject| subject.token.is_empty()) {
+            return Err(format!("subject '{name}' in {path} has an empty token", name = subject.name, path = path.display()));
+        }
+
+        Ok(policy)
+    }
+
+    /// The subject whose token the request carries.
+    pub fn authenticate(&self, metadata: &MetadataMap) -> Result<&Subject, Status> {
+        let token = metadata
+            .get("authorization")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or_else(|| Status::new(Code::Unauthenticated, "a bearer token is required"))?;
+
+        self.subjects
+            .iter()
+            .find(|subject| same_token(&subject.token, token))
+            .ok_or_else(|| Status::new(Code::Unauthenticated, "unknown token"))
+    }
+}
+
+impl Subject {
+    pub fn may_exec(&self, service_name: &str, command: &[String]) -> bool {
+        let (Some(grant), Some(program)) = (&self.exec, command.first()) else {
+            return false;
+        };
+        allows(&grant.services, service_name) && allows(&grant.commands, program)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> Policy {
+        serde_json::from_str(
+            r#"{"subjects": [
+                {"name": "ops", "token": "ops-token", "exec": {"services": ["*"], "commands": ["psql"]}},
+                {"name": "ci", "token": "ci-token"}
+            ]}"#,
+        )
+        .expect("Policy should parse")
+    }
+
+    fn metadata(authorization: &str) -> MetadataMap {
+        let mut metadata = MetadataMap::new();
+        metadata.insert("authorization", authorization.parse().unwrap());
+        metadata
+    }
+
+    #[test]
+    pub fn test_tokens_authenticated() {
+        let policy = policy();
+
+        assert_eq!(policy.authenticate(&metadata("Bearer ops-token")).unwrap().name, "ops");
+        assert_eq!(policy.authenticate(&metadata("Bearer nope")).unwrap_err().code(), Code::Unauthenticated);
+        assert_eq!(policy.authenticate(&metadata("ops-token")).unwrap_err().code(), Code::Unauthenticated);
+        assert_eq!(policy.authenticate(&MetadataMap::new()).unwrap_err().code(), Code::Unauthenticated);
+    }
+
+    #[test]
+    pub fn test_exec_grants() {
+        let policy = policy();
+        let ops = policy.authenticate(&metadata("Bearer ops-token")).unwrap();
+        let ci = policy.authenticate(&metadata("Bearer ci-token")).unwrap();
+
+        assert!(ops.may_exec("billing", &["psql".to_owned()]));
+        assert!(!ops.may_exec("billing", &["sh".to_owned()]));
+        assert!(!ops.may_exec("billing", &[]));
+        assert!(!ci.may_exec("billing", &["psql".to_owned()]), "Subjects without a grant may not exec");
+    }
+}
@@ -0,0 +1,214 @@
+use std::fs::{self, DirBuilder};
+use std::io::{self, ErrorKind};
+use std::os::unix::fs::DirBuilderExt;
+use std::path::PathBuf;
+
+use libprovision::hello_world::BackupInfo;
+use serde::{Deserialize, Serialize};
+
+use crate::io::{Backup, write_atomic};
+
+pub const DEFAULT_DIR: &str = "/var/lib/provisiond/backups";
+
+/// Backups kept per service when no retention is configured.
+pub const DEFAULT_KEEP: usize = 7;
+
+/// Stored next to each dump as `<backup_id>.json`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BackupMetadata {
+    pub backup_id: String,
+    pub service_name: String,
+    pub engine: String,
+    pub blueprint: String,
+    pub image: String,
+    /// Unix time in nanoseconds, so backups taken within a second still
+    /// prune in order.
+    pub created_at_nanos: u64,
+    pub size_bytes: u64,
+}
+
+/// Keeps dumps under `<root>/<service_name>/<backup_id>.sql.gz`, with the
+/// newest `keep` of each service kept when pruning.
+pub struct BackupStore {
+    root: PathBuf,
+    keep: usize,
+}
+
+impl Default for BackupStore {
+    fn default() -> Self {
+        Self::new(PathBuf::from(DEFAULT_DIR), DEFAULT_KEEP)
+    }
+}
+
+impl BackupStore {
+    pub fn new(root: PathBuf, keep: usize) -> Self {
+        Self { root, keep }
+    }
+
+    /// Ids are generated by the daemon, anything else could point outside
+    /// the service's folder.
+    pub fn is_valid_id(backup_id: &str) -> bool {
+        !backup_id.is_empty() && backup_id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+    }
+
+    fn folder(&self, service_name: &str) -> PathBuf {
+        self.root.join(service_name)
+    }
+
+    pub fn data_path(&self, service_name: &str, backup_id: &str) -> PathBuf {
+        self.folder(service_name).join(format!("{backup_id}.sql.gz"))
+    }
+
+    fn metadata_path(&self, service_name: &str, backup_id: &str) -> PathBuf {
+        self.folder(service_name).join(format!("{backup_id}.json"))
+    }
+
+    /// Makes the service's folder, readable by the daemon only.
+    pub fn create_folder(&self, service_name: &str) -> io::Result<()> {
+        DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(self.folder(service_name))
+    }
+
+    pub fn save(&self, metadata: &BackupMetadata) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(metadata).expect("metadata is always serializable");
+        write_atomic(
+            &self.metadata_path(&metadata.service_name, &metadata.backup_id),
+            json.as_bytes(),
+            Backup::Previous,
+        )
+    }
+
+    pub fn get(&self, service_name: &str, backup_id: &str) -> io::Result<Option<BackupMetadata>> {
+        if !Self::is_valid_id(backup_id) {
+            return Ok(None);
+        }
+
+        match fs::read_to_string(self.metadata_path(service_name, backup_id)) {
+            Ok(json) => serde_json::from_str(&json)
+                .map(Some)
+                .map_err(|e| io::Error::new(ErrorKind::InvalidData, e)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// The service's backups, oldest first. Dumps without metadata are
+    /// left out, they are what a crash in the middle of a backup leaves.
+    pub fn list(&self, service_name: &str) -> io::Result<Vec<BackupMetadata>> {
+        let entries = match fs::read_dir(self.folder(service_name)) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+
+        let mut backups = Vec::new();
+        for entry in entries {
+            let file_name = entry?.file_name();
+            let Some(backup_id) = file_name.to_str().and_then(|name| name.strip_suffix(".json")) else {
+                continue;
+            };
+            if let Some(metadata) = self.get(service_name, backup_id)? {
+                backups.push(metadata);
+            }
+        }
+        backups.sort_by(|a, b| (a.created_at_nanos, &a.backup_id).cmp(&(b.created_at_nanos, &b.backup_id)));
+
+        Ok(backups)
+    }
+
+    pub fn remove(&self, service_name: &str, backup_id: &str) -> io::Result<()> {
+        for path in [self.data_path(service_name, backup_id), self.metadata_path(service_name, backup_id)] {
+            match fs::remove_file(path) {
+                Err(e) if e.kind() != ErrorKind::NotFound => return Err(e),
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes all but the newest `keep` backups, returning the removed ids.
+    pub fn prune(&self, service_name: &str) -> io::Result<Vec<String>> {
+        let backups = self.list(service_name)?;
+        let excess = backups.len().saturating_sub(self.keep);
+
+        let mut removed = Vec::new();
+        for backup in &backups[..excess] {
+            self.remove(service_name, &backup.backup_id)?;
+            removed.push(backup.backup_id.clone());
+        }
+
+        Ok(removed)
+    }
+}
+
+impl From<BackupMetadata> for BackupInfo {
+    fn from(metadata: BackupMetadata) -> Self {
+        BackupInfo {
+            backup_id: metadata.backup_id,
+            service_name: metadata.service_name,
+            engine: metadata.engine,
+            image: metadata.image,
+            created_at: (metadata.created_at_nanos / 1_000_000_000) as i64,
+            size_bytes: metadata.size_bytes,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_root_path(test_name: &str) -> PathBuf {
+        let path = PathBuf::from(format!("/tmp/provisiond_tests/{}", test_name));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).expect("Failed to create test path");
+        path
+    }
+
+    fn metadata(backup_id: &str, created_at_nanos: u64) -> BackupMetadata {
+        BackupMetadata {
+            backup_id: backup_id.to_owned(),
+            service_name: "test_service".to_owned(),
+            engine: "postgres".to_owned(),
+            blueprint: "postgres".to_owned(),
+            image: "postgres:16".to_owned(),
+            created_at_nanos,
+            size_bytes: 4,
+        }
+    }
+
+    #[test]
+    pub fn test_backups_listed_and_pruned() {
+        let path = get_root_path("test_backups_listed_and_pruned");
+        let store = BackupStore::new(path.clone(), 2);
+        store.create_folder("test_service").expect("Failed to create backup folder");
+
+        for (backup_id, created_at_nanos) in [("b", 20), ("a", 10), ("c", 30)] {
+            fs::write(store.data_path("test_service", backup_id), "dump").unwrap();
+            store.save(&metadata(backup_id, created_at_nanos)).expect("Failed to save metadata");
+        }
+        // Left by a backup that never finished.
+        fs::write(store.data_path("test_service", "d"), "dump").unwrap();
+
+        let ids = |store: &BackupStore| -> Vec<String> {
+            store.list("test_service").unwrap().into_iter().map(|b| b.backup_id).collect()
+        };
+        assert_eq!(ids(&store), vec!["a", "b", "c"], "Backups should be listed oldest first");
+
+        assert_eq!(store.prune("test_service").unwrap(), vec!["a"]);
+        assert_eq!(ids(&store), vec!["b", "c"]);
+        assert!(!store.data_path("test_service", "a").exists(), "Pruned dumps should be removed");
+
+        fs::remove_dir_all(&path)
+            .unwrap_or_else(|_| panic!("Failed to delete test path at {}", path.display()));
+    }
+
+    #[test]
+    pub fn test_unsafe_ids_not_found() {
+        let store = BackupStore::new(PathBuf::from("/tmp/provisiond_tests/test_unsafe_ids_not_found"), 1);
+
+        assert_eq!(store.get("test_service", "../../etc/passwd").unwrap(), None);
+    }
+}
@@ -0,0 +1,3 @@
+mod backup_store;
+
+pub use backup_store::{BackupMetadata, BackupStore, DEFAULT_DIR, DEFAULT_KEEP};
@@ -0,0 +1,287 @@
+use std::fmt::Display;
+use std::fs;
+use std::io::ErrorKind;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use serde::{Deserialize, Deserializer};
+
+use crate::backups;
+use crate::io::Backup;
+use crate::metrics;
+use crate::permissions::ServiceOwner;
+use crate::ports::{self, PortRange};
+use crate::services::{self, ResourceLimits};
+use crate::shutdown;
+use crate::telemetry::LogFormat;
+
+/// Where the config is read from unless `PROVISIOND_CONFIG` says otherwise.
+pub const DEFAULT_PATH: &str = "/etc/provisiond/provisiond.json";
+
+/// Environment variables that override a setting of the config file, so a
+/// unit drop-in can change one without editing it.
+const ENV: [(&str, &str); 20] = [
+    ("RUST_LOG", "log_filter"),
+    ("PROVISIOND_LOG_FORMAT", "log_format"),
+    ("PROVISIOND_OTLP_ENDPOINT", "otlp_endpoint"),
+    ("PROVISIOND_BACKUP", "backup"),
+    ("PROVISIOND_SERVICE_USER", "service_user"),
+    ("PROVISIOND_SERVICE_GROUP", "service_group"),
+    ("PROVISIOND_BACKUP_DIR", "backup_dir"),
+    ("PROVISIOND_BACKUP_KEEP", "backup_keep"),
+    ("PROVISIOND_SCHEDULES", "schedules"),
+    ("PROVISIOND_SCHEDULE_STATE", "schedule_state"),
+    ("PROVISIOND_HEALTH_INTERVAL", "health_interval"),
+    ("PROVISIOND_RESTART_UNHEALTHY", "restart_unhealthy"),
+    ("PROVISIOND_POLICY", "policy"),
+    ("PROVISIOND_AUDIT_LOG", "audit_log"),
+    ("PROVISIOND_METRICS_ADDR", "metrics_addr"),
+    ("PROVISIOND_SHUTDOWN_TIMEOUT", "shutdown_timeout"),
+    ("PROVISIOND_PORT_POOL", "port_pool"),
+    ("PROVISIOND_PORT_STATE", "port_state"),
+    ("PROVISIOND_PORT_ADDRESS", "port_address"),
+    ("PROVISIOND_JOURNAL", "journal"),
+];
+
+/// Settings of the daemon itself, read from its config file. Every setting
+/// is optional:
+///
+/// ```json
+/// {"quota": {"cpus": "8", "memory": "16g", "pids": 4096}, "port_pool": "20000-20999"}
+/// ```
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct DaemonConfig {
+    /// Host-wide caps on what services' cpus, memory and pids limits may
+    /// add up to, unset means no cap.
+    pub quota: ResourceLimits,
+    /// What is logged, a `RUST_LOG` style filter defaulting to info.
+    pub log_filter: String,
+    #[serde(deserialize_with = "parsed")]
+    pub log_format: LogFormat,
+    /// Collector spans are sent to, such as `http://127.0.0.1:4318`.
+    pub otlp_endpoint: Option<String>,
+    /// `bak` keeps the previous version of every replaced file,
+    /// `history[:n]` also keeps the last n versions in the service's
+    /// `.history` folder.
+    #[serde(deserialize_with = "parsed")]
+    pub backup: Backup,
+    pub service_user: String,
+    pub service_group: String,
+    /// Where Backup RPC dumps go and how many are kept per service.
+    pub backup_dir: PathBuf,
+    pub backup_keep: usize,
+    /// Schedules every service gets, and where the run times are kept.
+    pub schedules: PathBuf,
+    pub schedule_state: PathBuf,
+    /// Seconds between health checks of every service, 0 turns them off.
+    pub health_interval: u64,
+    /// Unhealthy checks in a row that restart a service, 0 never does.
+    pub restart_unhealthy: u32,
+    /// Who may exec into services, and where exec attempts are recorded.
+    pub policy: PathBuf,
+    pub audit_log: PathBuf,
+    /// Where Prometheus scrapes /metrics, `off` turns it off.
+    #[serde(deserialize_with = "metrics_addr")]
+    pub metrics_addr: Option<SocketAddr>,
+    /// Seconds running operations get to finish on SIGTERM.
+    pub shutdown_timeout: u64,
+    /// Host ports handed out to services, where the assignments are kept,
+    /// and the address they are published on.
+    #[serde(deserialize_with = "parsed")]
+    pub port_pool: PortRange,
+    pub port_state: PathBuf,
+    pub port_address: IpAddr,
+    pub journal: PathBuf,
+}
+
+impl Default for DaemonConfig {
+    fn default() -> Self {
+        Self {
+            quota: ResourceLimits::default(),
+            log_filter: String::new(),
+            log_format: LogFormat::default(),
+            otlp_endpoint: None,
+            backup: Backup::default(),
+            service_user: ServiceOwner::default().user,
+            service_group: ServiceOwner::default().group,
+            backup_dir: PathBuf::from(backups::DEFAULT_DIR),
+            backup_keep: backups::DEFAULT_KEEP,
+            schedules: PathBuf::from("/etc/provisiond/schedules.json"),
+            schedule_state: PathBuf::from("/var/lib/provisiond/schedules.json"),
+            health_interval: 30,
+            restart_unhealthy: 0,
+            policy: PathBuf::from("/etc/provisiond/policy.json"),
+            audit_log: PathBuf::from("/var/log/provisiond/audit.log"),
+            metrics_addr: Some(metrics::DEFAULT_ADDR.parse().expect("the default metrics address is valid")),
+            shutdown_timeout: shutdown::DEFAULT_TIMEOUT.as_secs(),
+            port_pool: ports::DEFAULT_RANGE,
+            port_state: PathBuf::from("/var/lib/provisiond/ports.json"),
+            port_address: ports::DEFAULT_ADDRESS,
+            journal: PathBuf::from("/var/lib/provisiond/journal"),
+        }
+    }
+}
+
+/// Reads a setting written the way its environment variable is.
+fn parsed<'de, D: Deserializer<'de>, T: FromStr<Err: Display>>(deserializer: D) -> Result<T, D::Error> {
+    String::deserialize(deserializer)?.parse().map_err(serde::de::Error::custom)
+}
+
+fn parse_metrics_addr(value: &str) -> Result<Option<SocketAddr>, String> {
+    match value {
+        "off" => Ok(None),
+        addr => addr
+            .parse()
+            .map(Some)
+            .map_err(|_| format!("invalid metrics address '{addr}', expected an address like [::1]:9184 or off")),
+    }
+}
+
+fn metrics_addr<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<SocketAddr>, D::Error> {
+    parse_metrics_addr(&String::deserialize(deserializer)?).map_err(serde::de::Error::custom)
+}
+
+fn parse<T: FromStr<Err: Display>>(value: &str) -> Result<T, String> {
+    value.parse().map_err(|e: T::Err| e.to_string())
+}
+
+impl DaemonConfig {
+    /// A missing file is the defaults.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let json = match fs::read_to_string(path) {
+            Ok(json) => json,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(format!("failed to read {path}: {e}", path = path.display())),
+        };
+
+        let config: DaemonConfig =
+            serde_json::from_str(&json).map_err(|e| format!("invalid config in {path}: {e}", path = path.display()))?;
+        services::validate_limits(&config.quota)
+            .map_err(|e| format!("invalid quota in {path}: {e}", path = path.display()))?;
+
+        Ok(config)
+    }
+
+    /// The config with the settings `var` finds in the environment applied.
+    pub fn with_env(mut self, var: impl Fn(&str) -> Option<String>) -> Result<Self, String> {
+        for (name, setting) in ENV {
+            if let Some(value) = var(name) {
+                self.set(setting, &value).map_err(|e| format!("invalid {name}: {e}"))?;
+            }
+        }
+        Ok(self)
+    }
+
+    fn set(&mut self, setting: &str, value: &str) -> Result<(), String> {
+        let path = || PathBuf::from(value);
+        match setting {
+            "log_filter" => self.log_filter = value.to_owned(),
+            "log_format" => self.log_format = parse(value)?,
+            "otlp_endpoint" => self.otlp_endpoint = Some(value.to_owned()),
+            "backup" => self.backup = parse(value)?,
+            "service_user" => self.service_user = value.to_owned(),
+            "service_group" => self.service_group = value.to_owned(),
+            "backup_dir" => self.backup_dir = path(),
+            "backup_keep" => self.backup_keep = parse(value)?,
+            "schedules" => self.schedules = path(),
+            "schedule_state" => self.schedule_state = path(),
+            "health_interval" => self.health_interval = parse(value)?,
+            "restart_unhealthy" => self.restart_unhealthy = parse(value)?,
+            "policy" => self.policy = path(),
+            "audit_log" => self.audit_log = path(),
+            "metrics_addr" => self.metrics_addr = parse_metrics_addr(value)?,
+            "shutdown_timeout" => self.shutdown_timeout = parse(value)?,
+            "port_pool" => self.port_pool = parse(value)?,
+            "port_state" => self.port_state = path(),
+            "port_address" => self.port_address = parse(value)?,
+            "journal" => self.journal = path(),
+            _ => unreachable!("every variable in ENV names a setting"),
+        }
+        Ok(())
+    }
+
+    /// Checks what the types alone do not.
+    pub fn validate(self) -> Result<Self, String> {
+        if self.backup_keep == 0 {
+            return Err("backup_keep should be a positive number".to_owned());
+        }
+        Ok(self)
+    }
+
+    pub fn service_owner(&self) -> ServiceOwner {
+        ServiceOwner {
+            user: self.service_user.clone(),
+            group: self.service_group.clone(),
+        }
+    }
+
+    /// The collector to send spans to, an empty endpoint is none.
+    pub fn otlp_endpoint(&self) -> Option<&str> {
+        self.otlp_endpoint.as_deref().filter(|endpoint| !endpoint.is_empty())
+    }
+
+    pub fn shutdown_timeout(&self) -> Duration {
+        Duration::from_secs(self.shutdown_timeout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_config_loaded_and_checked() {
+        let path = PathBuf::from("/tmp/provisiond_tests/test_config_loaded_and_checked");
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        let file = path.join("provisiond.json");
+
+        assert_eq!(DaemonConfig::load(&file), Ok(DaemonConfig::default()));
+
+        fs::write(&file, r#"{"quota": {"cpus": "8", "pids": 4096}}"#).unwrap();
+        let quota = DaemonConfig::load(&file).unwrap().quota;
+        assert_eq!(quota.cpus.as_deref(), Some("8"));
+        assert_eq!(quota.pids, Some(4096));
+
+        fs::write(&file, r#"{"quota": {"memory": "lots"}}"#).unwrap();
+        let err = DaemonConfig::load(&file).expect_err("A bad quota should be rejected");
+        assert!(err.starts_with("invalid quota in "), "{err}");
+
+        fs::write(&file, r#"{"quotas": {}}"#).unwrap();
+        assert!(DaemonConfig::load(&file).is_err(), "Unknown settings should be rejected");
+
+        fs::write(&file, r#"{"port_pool": "20000-20099", "metrics_addr": "off", "backup": "history:3"}"#).unwrap();
+        let config = DaemonConfig::load(&file).unwrap();
+        assert_eq!(config.port_pool.to_string(), "20000-20099");
+        assert_eq!(config.metrics_addr, None);
+        assert_eq!(config.backup, Backup::History(3));
+
+        fs::write(&file, r#"{"port_pool": "20099-20000"}"#).unwrap();
+        assert!(DaemonConfig::load(&file).is_err(), "Settings should be parsed like their variables");
+
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    pub fn test_env_overrides_and_errors() {
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| vars.iter().find(|(var, _)| *var == name).map(|(_, value)| value.to_string())
+        };
+
+        let config = DaemonConfig::default()
+            .with_env(env(&[("PROVISIOND_HEALTH_INTERVAL", "5"), ("PROVISIOND_PORT_ADDRESS", "0.0.0.0")]))
+            .unwrap();
+        assert_eq!(config.health_interval, 5);
+        assert_eq!(config.port_address.to_string(), "0.0.0.0");
+        assert_eq!(config.journal, DaemonConfig::default().journal);
+
+        let err = DaemonConfig::default().with_env(env(&[("PROVISIOND_HEALTH_INTERVAL", "5s")])).unwrap_err();
+        assert!(err.starts_with("invalid PROVISIOND_HEALTH_INTERVAL: "), "{err}");
+
+        let config = DaemonConfig::default().with_env(env(&[("PROVISIOND_BACKUP_KEEP", "0")])).unwrap();
+        assert!(config.validate().is_err());
+    }
+}
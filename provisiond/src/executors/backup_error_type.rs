@@ -0,0 +1,64 @@
+use std::fmt::{Display, Formatter};
+
+use tonic::Code;
+
+use crate::executors::ErrorReason;
+
+#[derive(Debug)]
+pub enum BackupErrorType {
+    DockerUnavailable,
+    ContainerNotRunning,
+    ExecFailed,
+
+    DumpFailed,
+    RestoreFailed,
+
+    FileWriteFailed,
+    FileReadFailed,
+}
+
+impl Display for BackupErrorType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        use BackupErrorType::*;
+
+        match self {
+            DockerUnavailable => write!(f, "Docker is not reachable"),
+            ContainerNotRunning => write!(f, "The service's container is not running"),
+            ExecFailed => write!(f, "Running the command in the container failed"),
+
+            DumpFailed => write!(f, "Dumping the data failed"),
+            RestoreFailed => write!(f, "Restoring the data failed"),
+
+            FileWriteFailed => write!(f, "Writing the backup file failed"),
+            FileReadFailed => write!(f, "Reading the backup file failed"),
+        }
+    }
+}
+
+impl ErrorReason for BackupErrorType {
+    fn reason(&self) -> &'static str {
+        use BackupErrorType::*;
+
+        match self {
+            DockerUnavailable => "DOCKER_UNAVAILABLE",
+            ContainerNotRunning => "CONTAINER_NOT_RUNNING",
+            ExecFailed => "EXEC_FAILED",
+
+            DumpFailed => "DUMP_FAILED",
+            RestoreFailed => "RESTORE_FAILED",
+
+            FileWriteFailed => "BACKUP_FILE_WRITE_FAILED",
+            FileReadFailed => "BACKUP_FILE_READ_FAILED",
+        }
+    }
+
+    fn code(&self) -> Code {
+        use BackupErrorType::*;
+
+        match self {
+            DockerUnavailable => Code::Unavailable,
+            ContainerNotRunning => Code::FailedPrecondition,
+            ExecFailed | DumpFailed | RestoreFailed | FileWriteFailed | FileReadFailed => Code::Internal,
+        }
+    }
+}
@@ -0,0 +1,12 @@
+use std::net::IpAddr;
+
+/// What docker reports for a service's container.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ContainerStatus {
+    /// For example `running` or `exited`.
+    pub state: String,
+    /// Result of the image's own healthcheck, empty if it has none.
+    pub health: String,
+    /// Address on the first network the container is attached to.
+    pub address: Option<IpAddr>,
+}
@@ -1,17 +1,26 @@
 use std::fmt::{Display, Formatter};
 
+use tonic::Code;
+
+use crate::executors::ErrorReason;
+
 #[derive(Debug)]
 pub enum CreateErrorType {
     FolderExists,
     ComposeFileExists,
     EnvFileExists,
     UnitFileExists,
+    DefinitionFileExists,
+    UserExists,
     
     FolderCreateFailed,
     FileCreateFailed,
     FileWriteFailed,
 
     PermissionError,
+    SetPermissionsFailed,
+    UserCreateFailed,
+    PortsExhausted,
     
     OtherIO,
 }
@@ -25,8 +34,13 @@ impl Display for CreateErrorType {
             ComposeFileExists => write!(f, "Compose file already exists"),
             EnvFileExists => write!(f, "Env file already exists"),
             UnitFileExists => write!(f, "Unit file already exists"),
+            DefinitionFileExists => write!(f, "Definition file already exists"),
+            UserExists => write!(f, "Service user already exists"),
 
             PermissionError => write!(f, "Permission Error"),
+            SetPermissionsFailed => write!(f, "Setting ownership and permissions failed"),
+            UserCreateFailed => write!(f, "Service user creation failed"),
+            PortsExhausted => write!(f, "No free host port"),
 
             FolderCreateFailed => write!(f, "Folder creation failed"),
 
@@ -36,4 +50,45 @@ impl Display for CreateErrorType {
             OtherIO => write!(f, "IO operation failed"),
         }
     }
-}
\ No newline at end of file
+}
+
+impl ErrorReason for CreateErrorType {
+    fn reason(&self) -> &'static str {
+        use CreateErrorType::*;
+
+        match self {
+            FolderExists => "FOLDER_EXISTS",
+            ComposeFileExists => "COMPOSE_FILE_EXISTS",
+            EnvFileExists => "ENV_FILE_EXISTS",
+            UnitFileExists => "UNIT_FILE_EXISTS",
+            DefinitionFileExists => "DEFINITION_FILE_EXISTS",
+            UserExists => "USER_EXISTS",
+
+            FolderCreateFailed => "FOLDER_CREATE_FAILED",
+            FileCreateFailed => "FILE_CREATE_FAILED",
+            FileWriteFailed => "FILE_WRITE_FAILED",
+
+            PermissionError => "PERMISSION_ERROR",
+            SetPermissionsFailed => "SET_PERMISSIONS_FAILED",
+            UserCreateFailed => "USER_CREATE_FAILED",
+            PortsExhausted => "PORTS_EXHAUSTED",
+
+            OtherIO => "OTHER_IO",
+        }
+    }
+
+    fn code(&self) -> Code {
+        use CreateErrorType::*;
+
+        match self {
+            FolderExists | ComposeFileExists | EnvFileExists | UnitFileExists | DefinitionFileExists | UserExists => {
+                Code::AlreadyExists
+            },
+            PermissionError => Code::PermissionDenied,
+            PortsExhausted => Code::ResourceExhausted,
+            FolderCreateFailed | FileCreateFailed | FileWriteFailed | SetPermissionsFailed | UserCreateFailed | OtherIO => {
+                Code::Internal
+            }
+        }
+    }
+}
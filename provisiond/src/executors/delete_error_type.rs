@@ -1,16 +1,24 @@
 use std::fmt::{Display, Formatter};
 
+use tonic::Code;
+
+use crate::executors::ErrorReason;
+
 #[derive(Debug)]
 pub enum DeleteErrorType {
     FolderDoesNotExist,
     ComposeFileDoesNotExist,
     EnvFileDoesNotExist,
     UnitFileDoesNotExist,
+    DefinitionFileDoesNotExist,
+    UserDoesNotExist,
     
     FolderDeletionFailed,
     ComposeFileDeletionFailed,
     EnvFileDeletionFailed,
     UnitFileDeletionFailed,
+    DefinitionFileDeletionFailed,
+    UserDeletionFailed,
     
 }
 
@@ -21,12 +29,54 @@ impl Display for DeleteErrorType {
             DeleteErrorType::ComposeFileDoesNotExist => String::from("Environment file does not exist"),
             DeleteErrorType::EnvFileDoesNotExist => String::from("Environment file does not exist"),
             DeleteErrorType::UnitFileDoesNotExist => String::from("Unit file does not exist"),
+            DeleteErrorType::DefinitionFileDoesNotExist => String::from("Definition file does not exist"),
+            DeleteErrorType::UserDoesNotExist => String::from("Service user does not exist"),
 
             DeleteErrorType::FolderDeletionFailed => String::from("Folder deletion failed"),
             DeleteErrorType::ComposeFileDeletionFailed => String::from("Compose file deletion failed"),
             DeleteErrorType::EnvFileDeletionFailed => String::from("Environment file deletion failed"),
             DeleteErrorType::UnitFileDeletionFailed => String::from("Unit file deletion failed"),
+            DeleteErrorType::DefinitionFileDeletionFailed => String::from("Definition file deletion failed"),
+            DeleteErrorType::UserDeletionFailed => String::from("Service user deletion failed"),
         };
         write!(f, "{msg}")
     }
-}
\ No newline at end of file
+}
+
+impl ErrorReason for DeleteErrorType {
+    fn reason(&self) -> &'static str {
+        match self {
+            DeleteErrorType::FolderDoesNotExist => "FOLDER_DOES_NOT_EXIST",
+            DeleteErrorType::ComposeFileDoesNotExist => "COMPOSE_FILE_DOES_NOT_EXIST",
+            DeleteErrorType::EnvFileDoesNotExist => "ENV_FILE_DOES_NOT_EXIST",
+            DeleteErrorType::UnitFileDoesNotExist => "UNIT_FILE_DOES_NOT_EXIST",
+            DeleteErrorType::DefinitionFileDoesNotExist => "DEFINITION_FILE_DOES_NOT_EXIST",
+            DeleteErrorType::UserDoesNotExist => "USER_DOES_NOT_EXIST",
+
+            DeleteErrorType::FolderDeletionFailed => "FOLDER_DELETION_FAILED",
+            DeleteErrorType::ComposeFileDeletionFailed => "COMPOSE_FILE_DELETION_FAILED",
+            DeleteErrorType::EnvFileDeletionFailed => "ENV_FILE_DELETION_FAILED",
+            DeleteErrorType::UnitFileDeletionFailed => "UNIT_FILE_DELETION_FAILED",
+            DeleteErrorType::DefinitionFileDeletionFailed => "DEFINITION_FILE_DELETION_FAILED",
+            DeleteErrorType::UserDeletionFailed => "USER_DELETION_FAILED",
+        }
+    }
+
+    fn code(&self) -> Code {
+        match self {
+            DeleteErrorType::FolderDoesNotExist
+            | DeleteErrorType::ComposeFileDoesNotExist
+            | DeleteErrorType::EnvFileDoesNotExist
+            | DeleteErrorType::UnitFileDoesNotExist
+            | DeleteErrorType::DefinitionFileDoesNotExist
+            | DeleteErrorType::UserDoesNotExist => Code::NotFound,
+
+            DeleteErrorType::FolderDeletionFailed
+            | DeleteErrorType::ComposeFileDeletionFailed
+            | DeleteErrorType::EnvFileDeletionFailed
+            | DeleteErrorType::UnitFileDeletionFailed
+            | DeleteErrorType::DefinitionFileDeletionFailed
+            | DeleteErrorType::UserDeletionFailed => Code::Internal,
+        }
+    }
+}
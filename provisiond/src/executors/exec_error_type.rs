@@ -0,0 +1,46 @@
+use std::fmt::{Display, Formatter};
+
+use tonic::Code;
+
+use crate::executors::ErrorReason;
+
+#[derive(Debug)]
+pub enum ExecErrorType {
+    DockerUnavailable,
+    ContainerNotFound,
+    ExecFailed,
+}
+
+impl Display for ExecErrorType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        use ExecErrorType::*;
+
+        match self {
+            DockerUnavailable => write!(f, "Docker is not reachable"),
+            ContainerNotFound => write!(f, "Container does not exist or is not running"),
+            ExecFailed => write!(f, "Running the command in the container failed"),
+        }
+    }
+}
+
+impl ErrorReason for ExecErrorType {
+    fn reason(&self) -> &'static str {
+        use ExecErrorType::*;
+
+        match self {
+            DockerUnavailable => "DOCKER_UNAVAILABLE",
+            ContainerNotFound => "CONTAINER_NOT_FOUND",
+            ExecFailed => "EXEC_FAILED",
+        }
+    }
+
+    fn code(&self) -> Code {
+        use ExecErrorType::*;
+
+        match self {
+            DockerUnavailable => Code::Unavailable,
+            ContainerNotFound => Code::NotFound,
+            ExecFailed => Code::Internal,
+        }
+    }
+}
@@ -0,0 +1,17 @@
+use std::pin::Pin;
+
+use futures_util::Stream;
+use libprovision::hello_world::exec_output::Output;
+use tokio::io::AsyncWrite;
+
+use crate::executors::ExecExecutorError;
+
+type ExecOutputStream = Pin<Box<dyn Stream<Item = Result<Output, ExecExecutorError>> + Send>>;
+
+/// A command started in a container. The output ends when the command exits.
+pub struct ExecSession {
+    /// For resizing the terminal and looking up the exit code.
+    pub id: String,
+    pub output: ExecOutputStream,
+    pub input: Pin<Box<dyn AsyncWrite + Send>>,
+}
@@ -1,5 +1,15 @@
+use std::collections::HashMap;
 use std::error::Error;
 use std::fmt::{Debug, Display, Formatter};
+use std::path::Path;
+
+use tonic::Status;
+use tonic_types::{ErrorDetails, StatusExt};
+
+use crate::executors::ErrorReason;
+
+/// Domain attached to every `ErrorInfo` this daemon produces.
+pub const ERROR_DOMAIN: &str = "provisiond";
 
 #[derive(Debug)]
 pub struct ExecutorError<T>
@@ -9,6 +19,7 @@ where
     kind: T,
     // change this to be the username the dev is trying to modify
     message: String,
+    path: Option<String>,
 }
 
 impl<T> ExecutorError<T>
@@ -16,7 +27,44 @@ where
     T: Debug + Display,
 {
     pub fn new(kind: T, message: String) -> Self {
-        Self { kind, message }
+        Self {
+            kind,
+            message,
+            path: None,
+        }
+    }
+
+    /// Records the file or folder the executor was working on when it failed.
+    pub fn with_path(mut self, path: &Path) -> Self {
+        self.path = Some(path.display().to_string());
+        self
+    }
+
+    pub fn kind(&self) -> &T {
+        &self.kind
+    }
+}
+
+impl<T> ExecutorError<T>
+where
+    T: Debug + Display + ErrorReason,
+{
+    /// Maps the error onto the matching gRPC code and attaches an `ErrorInfo`
+    /// with the reason, the step that failed and the path involved.
+    pub fn to_status(&self, service_name: &str, step_name: &str) -> Status {
+        let mut metadata = HashMap::from([
+            ("service_name".to_owned(), service_name.to_owned()),
+            ("step".to_owned(), step_name.to_owned()),
+        ]);
+        if let Some(path) = &self.path {
+            metadata.insert("path".to_owned(), path.clone());
+        }
+
+        Status::with_error_details(
+            self.kind.code(),
+            format!("Error Running step '{step_name}' for {service_name} got error {err}", err = self),
+            ErrorDetails::with_error_info(self.kind.reason(), ERROR_DOMAIN, metadata),
+        )
     }
 }
 
@@ -31,3 +79,42 @@ where
 }
 
 impl<T> Error for ExecutorError<T> where T: Display + Debug {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executors::{CreateErrorType, DeleteErrorType};
+    use std::path::PathBuf;
+    use tonic::Code;
+
+    #[test]
+    pub fn test_folder_exists_maps_to_already_exists() {
+        let err = ExecutorError::new(CreateErrorType::FolderExists, "exists".to_owned())
+            .with_path(&PathBuf::from("/mnt/srv/test_service"));
+
+        let status = err.to_status("test_service", "Create Folder");
+        assert_eq!(status.code(), Code::AlreadyExists);
+
+        let details = status.get_error_details();
+        let info = details.error_info().expect("Status should carry ErrorInfo");
+
+        assert_eq!(info.reason, "FOLDER_EXISTS");
+        assert_eq!(info.domain, ERROR_DOMAIN);
+        assert_eq!(info.metadata.get("step").map(String::as_str), Some("Create Folder"));
+        assert_eq!(info.metadata.get("path").map(String::as_str), Some("/mnt/srv/test_service"));
+    }
+
+    #[test]
+    pub fn test_error_codes_mapped() {
+        let cases = [
+            (ExecutorError::new(CreateErrorType::PermissionError, String::new()).to_status("s", "step"), Code::PermissionDenied),
+            (ExecutorError::new(CreateErrorType::FileWriteFailed, String::new()).to_status("s", "step"), Code::Internal),
+            (ExecutorError::new(DeleteErrorType::FolderDoesNotExist, String::new()).to_status("s", "step"), Code::NotFound),
+            (ExecutorError::new(DeleteErrorType::FolderDeletionFailed, String::new()).to_status("s", "step"), Code::Internal),
+        ];
+
+        for (status, code) in cases {
+            assert_eq!(status.code(), code, "Unexpected code for '{}'", status.message());
+        }
+    }
+}
@@ -0,0 +1,54 @@
+use std::fmt::{Display, Formatter};
+
+use tonic::Code;
+
+use crate::executors::ErrorReason;
+
+#[derive(Debug)]
+pub enum HealthErrorType {
+    DockerUnavailable,
+    InspectFailed,
+    ProbeFailed,
+    SystemdUnavailable,
+    RootNotWritable,
+}
+
+impl Display for HealthErrorType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        use HealthErrorType::*;
+
+        match self {
+            DockerUnavailable => write!(f, "Docker is not reachable"),
+            InspectFailed => write!(f, "Inspecting the container failed"),
+            ProbeFailed => write!(f, "Probe failed"),
+            SystemdUnavailable => write!(f, "systemd is not reachable"),
+            RootNotWritable => write!(f, "The provisioning root is not writable"),
+        }
+    }
+}
+
+impl ErrorReason for HealthErrorType {
+    fn reason(&self) -> &'static str {
+        use HealthErrorType::*;
+
+        match self {
+            DockerUnavailable => "DOCKER_UNAVAILABLE",
+            InspectFailed => "CONTAINER_INSPECT_FAILED",
+            ProbeFailed => "PROBE_FAILED",
+            SystemdUnavailable => "SYSTEMD_UNAVAILABLE",
+            RootNotWritable => "ROOT_NOT_WRITABLE",
+        }
+    }
+
+    fn code(&self) -> Code {
+        use HealthErrorType::*;
+
+        match self {
+            DockerUnavailable => Code::Unavailable,
+            InspectFailed => Code::Internal,
+            ProbeFailed => Code::FailedPrecondition,
+            SystemdUnavailable => Code::Unavailable,
+            RootNotWritable => Code::Unavailable,
+        }
+    }
+}
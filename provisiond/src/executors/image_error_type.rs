@@ -0,0 +1,45 @@
+use std::fmt::{Display, Formatter};
+
+use tonic::Code;
+
+use crate::executors::ErrorReason;
+
+#[derive(Debug)]
+pub enum ImageErrorType {
+    DockerUnavailable,
+    PullFailed,
+    InspectFailed,
+}
+
+impl Display for ImageErrorType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        use ImageErrorType::*;
+
+        match self {
+            DockerUnavailable => write!(f, "Docker is not reachable"),
+            PullFailed => write!(f, "Pulling the image failed"),
+            InspectFailed => write!(f, "Inspecting the image failed"),
+        }
+    }
+}
+
+impl ErrorReason for ImageErrorType {
+    fn reason(&self) -> &'static str {
+        use ImageErrorType::*;
+
+        match self {
+            DockerUnavailable => "DOCKER_UNAVAILABLE",
+            PullFailed => "IMAGE_PULL_FAILED",
+            InspectFailed => "IMAGE_INSPECT_FAILED",
+        }
+    }
+
+    fn code(&self) -> Code {
+        use ImageErrorType::*;
+
+        match self {
+            DockerUnavailable => Code::Unavailable,
+            PullFailed | InspectFailed => Code::Internal,
+        }
+    }
+}
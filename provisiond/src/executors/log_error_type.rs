@@ -0,0 +1,49 @@
+use std::fmt::{Display, Formatter};
+
+use tonic::Code;
+
+use crate::executors::ErrorReason;
+
+#[derive(Debug)]
+pub enum LogErrorType {
+    DockerUnavailable,
+    ContainerNotFound,
+    LogsFailed,
+    JournalFailed,
+}
+
+impl Display for LogErrorType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        use LogErrorType::*;
+
+        match self {
+            DockerUnavailable => write!(f, "Docker is not reachable"),
+            ContainerNotFound => write!(f, "Container does not exist"),
+            LogsFailed => write!(f, "Reading the container logs failed"),
+            JournalFailed => write!(f, "Reading the journal failed"),
+        }
+    }
+}
+
+impl ErrorReason for LogErrorType {
+    fn reason(&self) -> &'static str {
+        use LogErrorType::*;
+
+        match self {
+            DockerUnavailable => "DOCKER_UNAVAILABLE",
+            ContainerNotFound => "CONTAINER_NOT_FOUND",
+            LogsFailed => "CONTAINER_LOGS_FAILED",
+            JournalFailed => "JOURNAL_FAILED",
+        }
+    }
+
+    fn code(&self) -> Code {
+        use LogErrorType::*;
+
+        match self {
+            DockerUnavailable => Code::Unavailable,
+            ContainerNotFound => Code::NotFound,
+            LogsFailed | JournalFailed => Code::Internal,
+        }
+    }
+}
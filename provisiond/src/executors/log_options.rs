@@ -0,0 +1,18 @@
+use std::pin::Pin;
+
+use futures_util::Stream;
+use libprovision::hello_world::LogLine;
+
+use crate::executors::LogExecutorError;
+
+pub type LogStream = Pin<Box<dyn Stream<Item = Result<LogLine, LogExecutorError>> + Send>>;
+
+/// Which lines a log stream starts with and whether it keeps going.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LogOptions {
+    pub follow: bool,
+    /// Unix time in seconds, 0 for everything.
+    pub since: i64,
+    /// Only the last this many lines, 0 for all of them.
+    pub tail: u32,
+}
@@ -1,32 +1,203 @@
+mod backup_error_type;
+mod container_status;
 mod create_error_type;
 mod delete_error_type;
+mod exec_error_type;
+mod exec_session;
 mod executor_error;
+mod health_error_type;
+mod image_error_type;
+mod log_error_type;
+mod log_options;
+mod real_backup_executor;
 mod real_create_executor;
 mod real_delete_executor;
+mod real_exec_executor;
+mod real_health_executor;
+mod real_image_executor;
+mod real_log_executor;
+mod real_unit_executor;
+mod real_volume_executor;
+mod service_file;
+mod unit_error_type;
+mod volume_error_type;
+
+use std::io;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::process::{Command, Output};
 
 use executor_error::ExecutorError;
-use tonic::async_trait;
+use mockall::automock;
+use crate::permissions::Owner;
+use crate::services::Probe;
+use tonic::{Code, async_trait};
 
-use crate::executors::create_error_type::CreateErrorType;
-use crate::executors::delete_error_type::DeleteErrorType;
+pub use backup_error_type::BackupErrorType;
+pub use container_status::ContainerStatus;
+pub use create_error_type::CreateErrorType;
+pub use delete_error_type::DeleteErrorType;
+pub use exec_error_type::ExecErrorType;
+pub use exec_session::ExecSession;
+pub use health_error_type::HealthErrorType;
+pub use image_error_type::ImageErrorType;
+pub use log_error_type::LogErrorType;
+pub use log_options::{LogOptions, LogStream};
+pub use real_backup_executor::RealBackupExecutor;
 pub use real_create_executor::RealCreateExecutor;
 pub use real_delete_executor::RealDeleteExecutor;
+pub use real_exec_executor::RealExecExecutor;
+pub use real_health_executor::RealHealthExecutor;
+pub use real_image_executor::RealImageExecutor;
+pub use real_log_executor::RealLogExecutor;
+pub use real_unit_executor::RealUnitExecutor;
+pub use real_volume_executor::RealVolumeExecutor;
+pub use service_file::ServiceFile;
+pub use unit_error_type::UnitErrorType;
+pub use volume_error_type::VolumeErrorType;
 
 pub type CreateExecutorError = ExecutorError<CreateErrorType>;
 pub type DeleteExecutorError = ExecutorError<DeleteErrorType>;
+pub type UnitExecutorError = ExecutorError<UnitErrorType>;
+pub type BackupExecutorError = ExecutorError<BackupErrorType>;
+pub type ImageExecutorError = ExecutorError<ImageErrorType>;
+pub type HealthExecutorError = ExecutorError<HealthErrorType>;
+pub type LogExecutorError = ExecutorError<LogErrorType>;
+pub type ExecExecutorError = ExecutorError<ExecErrorType>;
+pub type VolumeExecutorError = ExecutorError<VolumeErrorType>;
+
+/// Runs a command to completion without holding up the other tasks on the
+/// worker it is called from, the watchdog ping among them. Commands such as
+/// `systemctl restart` can take minutes while an image is pulled. Needs the
+/// multi-threaded runtime the daemon runs on.
+pub fn run_blocking(command: &mut Command) -> io::Result<Output> {
+    tokio::task::block_in_place(|| command.output())
+}
 
+/// Machine readable description of an executor error kind, used to build the
+/// gRPC status returned to clients.
+pub trait ErrorReason {
+    fn reason(&self) -> &'static str;
+    fn code(&self) -> Code;
+}
+
+#[automock]
 #[async_trait]
 pub trait CreateExecutor {
     fn create_folder(&self, service_name: String) -> Result<(), CreateExecutorError>;
-    fn create_compose_file(&self, service_name: String) -> Result<(), CreateExecutorError>;
-    fn create_env_file(&self, service_name: String) -> Result<(), CreateExecutorError>;
-    fn create_systemd_unit(&self, service_name: String) -> Result<(), CreateExecutorError>;
+    fn create_compose_file(&self, service_name: String, contents: &str) -> Result<(), CreateExecutorError>;
+    fn create_env_file(&self, service_name: String, contents: &str) -> Result<(), CreateExecutorError>;
+    fn create_systemd_unit(&self, service_name: String, contents: &str) -> Result<(), CreateExecutorError>;
+    fn create_definition_file(&self, service_name: String, contents: &str) -> Result<(), CreateExecutorError>;
+
+    /// Swaps in new contents for an existing file, keeping the old version
+    /// next to it as a `.bak`.
+    fn replace_file(&self, service_name: String, file: ServiceFile, contents: &str) -> Result<(), CreateExecutorError>;
+    /// Puts back the version `replace_file` kept, or removes the file if it
+    /// did not exist before.
+    fn restore_file(&self, service_name: String, file: ServiceFile) -> Result<(), CreateExecutorError>;
+    /// Keeps the current version of a file as its `.bak` ahead of replacing
+    /// it, so restoring is right even if the replace never ran.
+    fn back_up_file(&self, service_name: String, file: ServiceFile) -> Result<(), CreateExecutorError>;
+    /// Gives the folder and files to `owner` with the modes the files need.
+    fn set_permissions(&self, service_name: String, owner: Owner) -> Result<(), CreateExecutorError>;
 }
 
+#[automock]
 #[async_trait]
 pub trait DeleteExecutor {
     fn delete_folder(&self, service_name: String) -> Result<(), DeleteExecutorError>;
     fn delete_compose_file(&self, service_name: String) -> Result<(), DeleteExecutorError>;
     fn delete_env_file(&self, service_name: String) -> Result<(), DeleteExecutorError>;
     fn delete_systemd_unit(&self, service_name: String) -> Result<(), DeleteExecutorError>;
+    fn delete_definition_file(&self, service_name: String) -> Result<(), DeleteExecutorError>;
+}
+
+#[automock]
+#[async_trait]
+pub trait UnitExecutor {
+    fn reload_units(&self) -> Result<(), UnitExecutorError>;
+    fn restart_unit(&self, service_name: String) -> Result<(), UnitExecutorError>;
+    /// Links the unit file in the service folder into systemd and enables it,
+    /// systemd cannot start the unit before.
+    fn link_unit(&self, service_name: String) -> Result<(), UnitExecutorError>;
+    /// Disables the unit and removes the link. Does nothing for a unit
+    /// systemd does not know.
+    fn unlink_unit(&self, service_name: String) -> Result<(), UnitExecutorError>;
+    /// Stops the unit if it is running.
+    fn stop_unit(&self, service_name: String) -> Result<(), UnitExecutorError>;
+    /// Removes the service's containers with `docker compose down`, which
+    /// keeps its volumes. Does nothing without a compose file.
+    fn compose_down(&self, service_name: String) -> Result<(), UnitExecutorError>;
+    fn unit_active(&self, service_name: String) -> Result<bool, UnitExecutorError>;
+}
+
+/// Moves a service's data in and out of its container.
+#[automock]
+#[async_trait]
+pub trait BackupExecutor {
+    /// Runs `command` in `container` and writes what it prints, gzip
+    /// compressed, to `destination`. Returns the size of the written file.
+    async fn dump(&self, container: String, command: Vec<String>, destination: PathBuf) -> Result<u64, BackupExecutorError>;
+    /// Runs `command` in `container` with the decompressed `source` as its input.
+    async fn restore(&self, container: String, command: Vec<String>, source: PathBuf) -> Result<(), BackupExecutorError>;
+}
+
+/// Pulls the images services run.
+#[automock]
+#[async_trait]
+pub trait ImageExecutor {
+    /// Id of the local copy of `image`, `None` if it has not been pulled.
+    async fn image_id(&self, image: String) -> Result<Option<String>, ImageExecutorError>;
+    async fn pull_image(&self, image: String) -> Result<(), ImageExecutorError>;
+}
+
+/// Looks at a service's container from the outside.
+#[automock]
+#[async_trait]
+pub trait HealthExecutor {
+    /// `None` if there is no such container.
+    async fn container_status(&self, container: String) -> Result<Option<ContainerStatus>, HealthExecutorError>;
+    async fn probe(&self, address: IpAddr, probe: Probe) -> Result<(), HealthExecutorError>;
+
+    /// Pings the docker daemon.
+    async fn ping_docker(&self) -> Result<(), HealthExecutorError>;
+    /// Asks the systemd manager for its version.
+    async fn ping_systemd(&self) -> Result<(), HealthExecutorError>;
+    /// Writes and removes a file in the folder services are provisioned in.
+    async fn check_root_writable(&self) -> Result<(), HealthExecutorError>;
+}
+
+/// Streams what a service logs.
+#[automock]
+#[async_trait]
+pub trait LogExecutor {
+    fn container_logs(&self, container: String, options: LogOptions) -> LogStream;
+    /// The journal of the service's systemd unit.
+    fn journal(&self, service_name: String, options: LogOptions) -> LogStream;
+}
+
+/// Runs interactive commands in a service's containers.
+#[automock]
+#[async_trait]
+pub trait ExecExecutor {
+    /// The container compose runs for `compose_service` in `project`, `None`
+    /// if it is not running.
+    async fn find_container(&self, project: String, compose_service: String) -> Result<Option<String>, ExecExecutorError>;
+    async fn start(&self, container: String, command: Vec<String>, tty: bool) -> Result<ExecSession, ExecExecutorError>;
+    /// Sets the size of a started session's terminal.
+    async fn resize(&self, id: String, rows: u32, columns: u32) -> Result<(), ExecExecutorError>;
+    /// `None` while the command is still running.
+    async fn exit_code(&self, id: String) -> Result<Option<i64>, ExecExecutorError>;
+}
+
+/// Manages the named volumes services keep their data in.
+#[automock]
+#[async_trait]
+pub trait VolumeExecutor {
+    /// Names of the volumes compose created for `project`.
+    async fn project_volumes(&self, project: String) -> Result<Vec<String>, VolumeExecutorError>;
+    /// Removes a volume and the data in it, a volume that is already gone
+    /// is not an error.
+    async fn remove_volume(&self, name: String) -> Result<(), VolumeExecutorError>;
 }
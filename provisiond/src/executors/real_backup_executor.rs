@@ -0,0 +1,197 @@
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufReader, Read, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+
+use bollard::Docker;
+use bollard::container::LogOutput;
+use bollard::exec::{CreateExecOptions, StartExecResults};
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use futures_util::{Stream, StreamExt};
+use tokio::io::AsyncWriteExt;
+use tonic::async_trait;
+use tracing::info;
+
+use crate::executors::backup_error_type::BackupErrorType;
+use crate::executors::{BackupExecutor, BackupExecutorError};
+
+/// Dumps and restores through `docker exec`, using the daemon's socket.
+#[derive(Default)]
+pub struct RealBackupExecutor;
+
+type ExecOutput = Pin<Box<dyn Stream<Item = Result<LogOutput, bollard::errors::Error>> + Send>>;
+
+/// A started exec, with its id for looking up the exit code afterwards.
+struct Exec {
+    docker: Docker,
+    id: String,
+    output: ExecOutput,
+    input: Pin<Box<dyn tokio::io::AsyncWrite + Send>>,
+}
+
+async fn start_exec(container: &str, command: Vec<String>, stdin: bool) -> Result<Exec, BackupExecutorError> {
+    info!("Running {command} in {container}", command = command.join(" "));
+
+    let docker = Docker::connect_with_local_defaults()
+        .map_err(|e| BackupExecutorError::new(BackupErrorType::DockerUnavailable, e.to_string()))?;
+
+    let id = docker
+        .create_exec(
+            container,
+            CreateExecOptions {
+                attach_stdin: Some(stdin),
+                attach_stdout: Some(true),
+                attach_stderr: Some(true),
+                cmd: Some(command),
+                ..Default::default()
+            },
+        )
+        .await
+        .map_err(exec_failed)?
+        .id;
+
+    match docker.start_exec(&id, None).await.map_err(exec_failed)? {
+        StartExecResults::Attached { output, input } => Ok(Exec { docker, id, output, input }),
+        StartExecResults::Detached => Err(BackupExecutorError::new(
+            BackupErrorType::ExecFailed,
+            "exec started detached".to_owned(),
+        )),
+    }
+}
+
+fn exec_failed(err: bollard::errors::Error) -> BackupExecutorError {
+    use bollard::errors::Error::*;
+
+    let kind = match &err {
+        // 404 is a missing container, 409 one that is stopped.
+        DockerResponseServerError { status_code: 404 | 409, .. } => BackupErrorType::ContainerNotRunning,
+        IOError { .. } | HyperLegacyError { .. } | SocketNotFoundError(_) | RequestTimeoutError => {
+            BackupErrorType::DockerUnavailable
+        }
+        _ => BackupErrorType::ExecFailed,
+    };
+    BackupExecutorError::new(kind, err.to_string())
+}
+
+/// Fails with `kind` and whatever the command wrote to stderr if it did not
+/// exit cleanly.
+async fn check_exit(docker: &Docker, id: &str, stderr: &[u8], kind: BackupErrorType) -> Result<(), BackupExecutorError> {
+    let inspect = docker
+        .inspect_exec(id)
+        .await
+        .map_err(exec_failed)?;
+
+    match inspect.exit_code {
+        Some(0) => Ok(()),
+        code => Err(BackupExecutorError::new(
+            kind,
+            format!(
+                "exited with {code}: {stderr}",
+                code = code.map_or("no code".to_owned(), |c| c.to_string()),
+                stderr = String::from_utf8_lossy(stderr).trim()
+            ),
+        )),
+    }
+}
+
+fn temp_path(path: &Path) -> PathBuf {
+    let mut temp = OsString::from(path);
+    temp.push(".tmp");
+    PathBuf::from(temp)
+}
+
+fn write_failed(err: std::io::Error, path: &Path) -> BackupExecutorError {
+    BackupExecutorError::new(BackupErrorType::FileWriteFailed, err.to_string()).with_path(path)
+}
+
+#[async_trait]
+impl BackupExecutor for RealBackupExecutor {
+    async fn dump(&self, container: String, command: Vec<String>, destination: PathBuf) -> Result<u64, BackupExecutorError> {
+        let temp = temp_path(&destination);
+        let file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            // Dumps hold the service's data, only the daemon may read them.
+            .mode(0o600)
+            .open(&temp)
+            .map_err(|e| write_failed(e, &temp))?;
+        let mut encoder = GzEncoder::new(file, Compression::default());
+
+        let result = async {
+            let Exec { docker, id, mut output, .. } = start_exec(&container, command, false).await?;
+            let mut stderr = Vec::new();
+
+            while let Some(chunk) = output.next().await {
+                match chunk.map_err(|e| BackupExecutorError::new(BackupErrorType::DumpFailed, e.to_string()))? {
+                    LogOutput::StdOut { message } => encoder.write_all(&message).map_err(|e| write_failed(e, &temp))?,
+                    LogOutput::StdErr { message } => stderr.extend_from_slice(&message),
+                    _ => (),
+                }
+            }
+
+            check_exit(&docker, &id, &stderr, BackupErrorType::DumpFailed).await?;
+
+            // Only a finished dump is moved into place, a failed one never
+            // shows up as a backup.
+            let file = encoder.finish().map_err(|e| write_failed(e, &temp))?;
+            file.sync_all().map_err(|e| write_failed(e, &temp))?;
+            fs::rename(&temp, &destination).map_err(|e| write_failed(e, &destination))?;
+
+            fs::metadata(&destination)
+                .map(|metadata| metadata.len())
+                .map_err(|e| write_failed(e, &destination))
+        }
+        .await;
+
+        if result.is_err() {
+            let _ = fs::remove_file(&temp);
+        }
+        result
+    }
+
+    async fn restore(&self, container: String, command: Vec<String>, source: PathBuf) -> Result<(), BackupExecutorError> {
+        let read_failed = |e: std::io::Error| {
+            BackupExecutorError::new(BackupErrorType::FileReadFailed, e.to_string()).with_path(&source)
+        };
+        let mut decoder = GzDecoder::new(BufReader::new(File::open(&source).map_err(read_failed)?));
+
+        let Exec { docker, id, mut output, mut input } = start_exec(&container, command, true).await?;
+
+        // The output is drained while the input is written, the command
+        // could otherwise block on a full stdout and never read the rest.
+        let feed = async {
+            let mut buffer = vec![0; 64 * 1024];
+            loop {
+                let read = decoder.read(&mut buffer).map_err(read_failed)?;
+                if read == 0 {
+                    break;
+                }
+                // A command that exits early closes its input, its exit code
+                // says more than the broken pipe does.
+                if input.write_all(&buffer[..read]).await.is_err() {
+                    break;
+                }
+            }
+            let _ = input.shutdown().await;
+            Ok::<(), BackupExecutorError>(())
+        };
+        let drain = async {
+            let mut stderr = Vec::new();
+            while let Some(chunk) = output.next().await {
+                if let Ok(LogOutput::StdErr { message }) = chunk {
+                    stderr.extend_from_slice(&message);
+                }
+            }
+            stderr
+        };
+
+        let (fed, stderr) = tokio::join!(feed, drain);
+        fed?;
+
+        check_exit(&docker, &id, &stderr, BackupErrorType::RestoreFailed).await
+    }
+}
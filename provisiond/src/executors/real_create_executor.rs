@@ -1,13 +1,24 @@
 use crate::executors::create_error_type::CreateErrorType;
-use crate::executors::{CreateExecutor, CreateExecutorError};
-use std::fs::{File, create_dir};
-use std::io::{ErrorKind, Write};
-use std::path::PathBuf;
-use log::info;
+use crate::executors::{CreateExecutor, CreateExecutorError, ServiceFile};
+use crate::io::{Backup, back_up, restore_backup, write_atomic};
+use crate::permissions::{self, Owner};
+use std::fs::DirBuilder;
+use std::os::unix::fs::DirBuilderExt;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
 use tonic::async_trait;
+use tracing::info;
 
 #[derive(Default)]
-pub struct RealCreateExecutor;
+pub struct RealCreateExecutor {
+    backup: Backup,
+}
+
+impl RealCreateExecutor {
+    pub fn new(backup: Backup) -> Self {
+        Self { backup }
+    }
+}
 
 #[async_trait]
 impl CreateExecutor for RealCreateExecutor {
@@ -24,13 +35,19 @@ impl CreateExecutor for RealCreateExecutor {
             return Err(CreateExecutorError::new(
                 CreateErrorType::FolderExists,
                 format!("the folder at '{service_name}' already exists", service_name = service_name).to_string(),
-            ));
+            )
+            .with_path(&folder_path));
         }
 
         info!("Creating folder at {display_path}", display_path = display_path);
 
-        create_dir(&folder_path).map_err(|e| {
-            CreateExecutorError::new(CreateErrorType::FolderCreateFailed, e.to_string())
+        // Kept private until the files in it get their owner and modes.
+        DirBuilder::new().mode(permissions::FOLDER_MODE).create(&folder_path).map_err(|e| {
+            let kind = match e.kind() {
+                ErrorKind::PermissionDenied => CreateErrorType::PermissionError,
+                _ => CreateErrorType::FolderCreateFailed,
+            };
+            CreateExecutorError::new(kind, e.to_string()).with_path(&folder_path)
         })?;
 
         info!("Created folder {display_path}", display_path = display_path);
@@ -38,135 +55,109 @@ impl CreateExecutor for RealCreateExecutor {
         Ok(())
     }
 
-    fn create_compose_file(&self, service_name: String) -> Result<(), CreateExecutorError> {
-        let docker_compose_content = include_str!("../../res/template/docker-compose.yaml");
-        let docker_compose_file =
+    fn create_compose_file(&self, service_name: String, contents: &str) -> Result<(), CreateExecutorError> {
+        let docker_compose_path =
             PathBuf::from(format!("/mnt/srv/{}/docker-compose.yaml", service_name));
 
-        let display_path = docker_compose_file.display().to_string();
-
-        info!("Checking for compose file at path {display_path}", display_path = display_path);
+        self.create_file(&docker_compose_path, contents, CreateErrorType::ComposeFileExists)
+    }
 
-        let mut docker_compose_file =
-            File::create(docker_compose_file).map_err(|err| match err.kind() {
-                ErrorKind::AlreadyExists => {
+    fn create_env_file(&self, service_name: String, contents: &str) -> Result<(), CreateExecutorError> {
+        let env_path = PathBuf::from(format!("/mnt/srv/{}/.env", service_name));
 
-                    info!("The docker compose at '{display_path}' already exists", display_path = display_path);
+        self.create_file(&env_path, contents, CreateErrorType::EnvFileExists)
+    }
 
-                    CreateExecutorError::new(
-                        CreateErrorType::ComposeFileExists,
-                        "Docker compose file already exists?".to_owned(),
-                    )
-                },
-                _ => CreateExecutorError::new(CreateErrorType::OtherIO, err.to_string()),
-            })?;
+    fn create_systemd_unit(&self, service_name: String, contents: &str) -> Result<(), CreateExecutorError> {
+        let unit_file_path = PathBuf::from(format!(
+            "/mnt/srv/{}/{}.service",
+            service_name, service_name
+        ));
 
-        docker_compose_file
-            .write(docker_compose_content.as_bytes())
-            .map_err(|err| match err.kind() {
-                ErrorKind::PermissionDenied => {
+        self.create_file(&unit_file_path, contents, CreateErrorType::UnitFileExists)
+    }
 
-                    info!("Permission checks failed for file '{display_path}' please check SUID", display_path = display_path);
+    fn create_definition_file(&self, service_name: String, contents: &str) -> Result<(), CreateExecutorError> {
+        let definition_path = PathBuf::from(format!("/mnt/srv/{}/provision.json", service_name));
 
-                    CreateExecutorError::new(
-                        CreateErrorType::PermissionError,
-                        "Failed to write to docker compose due to incorrect permissions".to_owned(),
-                    )
-                }
-                _ => CreateExecutorError::new(CreateErrorType::OtherIO, err.to_string()),
-            })
-            .map(|_| ())
+        self.create_file(&definition_path, contents, CreateErrorType::DefinitionFileExists)
     }
 
-    fn create_env_file(&self, service_name: String) -> Result<(), CreateExecutorError> {
-        let env_content = format!(
-            include_str!("../../res/template/env"),
-            format!("{}-db", service_name),
-            format!("{}-service", service_name),
-            format!("{}-password", service_name)
-        );
+    fn replace_file(&self, service_name: String, file: ServiceFile, contents: &str) -> Result<(), CreateExecutorError> {
+        let path = file.path(&service_name);
 
-        let env_file = PathBuf::from(format!("/mnt/srv/{}/.env", service_name));
+        info!("Replacing file at path {}", path.display());
 
-        let display_path = env_file.display().to_string();
+        write_atomic(&path, contents.as_bytes(), self.backup).map_err(|err| io_error(err, &path))
+    }
 
-        info!("Checking for compose file at path {display_path}", display_path = display_path);
+    fn restore_file(&self, service_name: String, file: ServiceFile) -> Result<(), CreateExecutorError> {
+        let path = file.path(&service_name);
 
-        let mut env_file = File::create(env_file).map_err(|err| match err.kind() {
-            ErrorKind::AlreadyExists => {
+        info!("Restoring {} from its backup", path.display());
 
-                info!("The env file at '{display_path}' already exists", display_path = display_path);
+        restore_backup(&path).map_err(|err| io_error(err, &path))
+    }
 
-                CreateExecutorError::new(
-                    CreateErrorType::EnvFileExists,
-                    "Env file already exists?".to_owned(),
-                )
-            },
-            _ => CreateExecutorError::new(CreateErrorType::FileCreateFailed, err.to_string()),
-        })?;
+    fn back_up_file(&self, service_name: String, file: ServiceFile) -> Result<(), CreateExecutorError> {
+        let path = file.path(&service_name);
 
-        env_file
-            .write_all(env_content.as_bytes())
-            .map_err(|err| match err.kind() {
-                ErrorKind::PermissionDenied => {
-
-                    info!("Permission checks failed for file '{display_path}' please check SUID", display_path = display_path);
-
-                    CreateExecutorError::new(
-                        CreateErrorType::PermissionError,
-                        "Failed to write to docker compose due to incorrect permissions".to_owned(),
-                    )
-                },
-                _ => CreateExecutorError::new(CreateErrorType::FileWriteFailed, err.to_string()),
-            })
-            .map(|_| ())
+        info!("Backing up file at path {}", path.display());
+
+        back_up(&path).map_err(|err| io_error(err, &path))
     }
 
-    fn create_systemd_unit(&self, service_name: String) -> Result<(), CreateExecutorError> {
-        let unit_file_content = format!(
-            include_str!("../../res/template/unit.service"),
-            service_name = service_name,
-        );
+    fn set_permissions(&self, service_name: String, owner: Owner) -> Result<(), CreateExecutorError> {
+        let folder_path = ServiceFile::folder(&service_name);
 
-        let unit_file_path = PathBuf::from(format!(
-            "/mnt/srv/{}/{}.service",
-            service_name, service_name
-        ));
+        info!("Setting owner {}:{} on {}", owner.uid, owner.gid, folder_path.display());
 
-        let display_path = unit_file_path.display().to_string();
+        permissions::apply(&folder_path, &service_name, owner).map_err(|err| {
+            let kind = match err.kind() {
+                ErrorKind::PermissionDenied => CreateErrorType::PermissionError,
+                _ => CreateErrorType::SetPermissionsFailed,
+            };
+            CreateExecutorError::new(kind, err.to_string()).with_path(&folder_path)
+        })
+    }
+}
 
-        info!("Checking for compose file at path {display_path}", display_path = display_path);
-        
-        let mut unit_file = File::create(&unit_file_path).map_err(|err| match err.kind() {
-            ErrorKind::AlreadyExists => {
+impl RealCreateExecutor {
+    /// Writes a file that should not exist yet, `exists_kind` is reported if
+    /// it does.
+    fn create_file(
+        &self,
+        path: &Path,
+        contents: &str,
+        exists_kind: CreateErrorType,
+    ) -> Result<(), CreateExecutorError> {
+        let display_path = path.display().to_string();
 
-                info!("The env file at '{display_path}' already exists", display_path = display_path);
+        info!("Checking for file at path {display_path}", display_path = display_path);
 
-                CreateExecutorError::new(
-                    CreateErrorType::UnitFileExists,
-                    format!(
-                        "The unit file at '{}' already exists",
-                        unit_file_path.to_str().unwrap()
-                    ),
-                )
-            },
-            _ => CreateExecutorError::new(CreateErrorType::OtherIO, err.to_string()),
-        })?;
+        if path.exists() {
 
-        unit_file
-            .write(unit_file_content.as_bytes())
-            .map_err(|err| match err.kind() {
-                ErrorKind::PermissionDenied => {
-                    
-                    info!("Permission checks failed for file '{display_path}' please check SUID", display_path = display_path);
-                    
-                    CreateExecutorError::new(
-                        CreateErrorType::PermissionError,
-                        "Failed to write to docker compose due to incorrect permissions".to_owned(),
-                    )
-                },
-                _ => CreateExecutorError::new(CreateErrorType::OtherIO, err.to_string()),
-            })
-            .map(|_| Ok(()))?
+            info!("The file at '{display_path}' already exists", display_path = display_path);
+
+            return Err(CreateExecutorError::new(exists_kind, format!("the file at '{display_path}' already exists"))
+                .with_path(path));
+        }
+
+        write_atomic(path, contents.as_bytes(), self.backup).map_err(|err| io_error(err, path))
     }
 }
+
+fn io_error(err: std::io::Error, path: &Path) -> CreateExecutorError {
+    let kind = match err.kind() {
+        ErrorKind::PermissionDenied => {
+
+            info!("Permission checks failed for file '{}' please check SUID", path.display());
+
+            CreateErrorType::PermissionError
+        },
+        ErrorKind::NotFound => CreateErrorType::FileCreateFailed,
+        ErrorKind::StorageFull | ErrorKind::WriteZero => CreateErrorType::FileWriteFailed,
+        _ => CreateErrorType::OtherIO,
+    };
+    CreateExecutorError::new(kind, err.to_string()).with_path(path)
+}
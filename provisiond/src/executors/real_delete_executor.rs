@@ -1,7 +1,7 @@
 use crate::executors::delete_error_type::DeleteErrorType;
 use crate::executors::{DeleteExecutor, DeleteExecutorError};
-use log::info;
 use tonic::async_trait;
+use tracing::info;
 
 #[derive(Default)]
 pub struct RealDeleteExecutor;
@@ -20,14 +20,16 @@ impl DeleteExecutor for RealDeleteExecutor {
             return Err(DeleteExecutorError::new(
                 DeleteErrorType::FolderDoesNotExist,
                 format!("the folder at '{}' does not exist", folder_path.display()).to_string(),
-            ));
+            )
+            .with_path(&folder_path));
         }
 
         remove_dir_all(&folder_path).map_err(|e| {
             DeleteExecutorError::new(
                 DeleteErrorType::FolderDeletionFailed,
-                format!("Failed to delete folder '{}' with error: {}", folder_path.display(), e.to_string()),
+                format!("Failed to delete folder '{}' with error: {}", folder_path.display(), e),
             )
+            .with_path(&folder_path)
         })?;
 
         Ok(())
@@ -48,14 +50,16 @@ impl DeleteExecutor for RealDeleteExecutor {
                     file_path.display()
                 )
                 .to_string(),
-            ));
+            )
+            .with_path(&file_path));
         }
 
         remove_file(&file_path).map_err(|e| {
             DeleteExecutorError::new(
                 DeleteErrorType::ComposeFileDeletionFailed,
-                format!("Failed to delete compose file at '{}' with error: {}", file_path.display(), e.to_string()),
+                format!("Failed to delete compose file at '{}' with error: {}", file_path.display(), e),
             )
+            .with_path(&file_path)
         })?;
 
         Ok(())
@@ -75,14 +79,16 @@ impl DeleteExecutor for RealDeleteExecutor {
                     "the env file at '{}' does not exist",
                     file_path.display()
                 ),
-            ));
+            )
+            .with_path(&file_path));
         }
 
         remove_file(&file_path).map_err(|e| {
             DeleteExecutorError::new(
                 DeleteErrorType::EnvFileDeletionFailed,
-                format!("Failed to delete compose file at '{}' with error: {}", file_path.display(), e.to_string()),
+                format!("Failed to delete compose file at '{}' with error: {}", file_path.display(), e),
             )
+            .with_path(&file_path)
         })?;
 
         Ok(())
@@ -105,14 +111,45 @@ impl DeleteExecutor for RealDeleteExecutor {
                     "the unit file at '{}' does not exist",
                     file_path.display()
                 ),
-            ));
+            )
+            .with_path(&file_path));
         }
 
         remove_file(&file_path).map_err(|e| {
             DeleteExecutorError::new(
                 DeleteErrorType::UnitFileDeletionFailed,
-                format!("Failed to delete unit file at '{}' with error: {}", file_path.display(), e.to_string()),
+                format!("Failed to delete unit file at '{}' with error: {}", file_path.display(), e),
+            )
+            .with_path(&file_path)
+        })?;
+
+        Ok(())
+    }
+
+    fn delete_definition_file(&self, service_name: String) -> Result<(), DeleteExecutorError> {
+        use std::fs::remove_file;
+        use std::path::PathBuf;
+
+        let file_path = PathBuf::from(format!("/mnt/srv/{}/provision.json", service_name));
+        info!("Deleting definition file {}", file_path.display());
+
+        if !file_path.exists() {
+            return Err(DeleteExecutorError::new(
+                DeleteErrorType::DefinitionFileDoesNotExist,
+                format!(
+                    "the definition file at '{}' does not exist",
+                    file_path.display()
+                ),
+            )
+            .with_path(&file_path));
+        }
+
+        remove_file(&file_path).map_err(|e| {
+            DeleteExecutorError::new(
+                DeleteErrorType::DefinitionFileDeletionFailed,
+                format!("Failed to delete definition file at '{}' with error: {}", file_path.display(), e),
             )
+            .with_path(&file_path)
         })?;
 
         Ok(())
@@ -0,0 +1,119 @@
+use std::collections::HashMap;
+
+use bollard::Docker;
+use bollard::container::LogOutput;
+use bollard::exec::{CreateExecOptions, StartExecResults};
+use bollard::query_parameters::{ListContainersOptionsBuilder, ResizeExecOptionsBuilder};
+use futures_util::StreamExt;
+use libprovision::hello_world::exec_output::Output;
+use tonic::async_trait;
+use tracing::info;
+
+use crate::executors::exec_error_type::ExecErrorType;
+use crate::executors::exec_session::ExecSession;
+use crate::executors::{ExecExecutor, ExecExecutorError};
+
+/// Runs commands in containers through `docker exec`, using the daemon's
+/// socket.
+#[derive(Default)]
+pub struct RealExecExecutor;
+
+fn connect() -> Result<Docker, ExecExecutorError> {
+    Docker::connect_with_local_defaults()
+        .map_err(|e| ExecExecutorError::new(ExecErrorType::DockerUnavailable, e.to_string()))
+}
+
+fn exec_failed(err: bollard::errors::Error) -> ExecExecutorError {
+    use bollard::errors::Error::*;
+
+    let kind = match &err {
+        // 404 is a missing container, 409 one that is stopped.
+        DockerResponseServerError { status_code: 404 | 409, .. } => ExecErrorType::ContainerNotFound,
+        IOError { .. } | HyperLegacyError { .. } | SocketNotFoundError(_) | RequestTimeoutError => {
+            ExecErrorType::DockerUnavailable
+        }
+        _ => ExecErrorType::ExecFailed,
+    };
+    ExecExecutorError::new(kind, err.to_string())
+}
+
+#[async_trait]
+impl ExecExecutor for RealExecExecutor {
+    async fn find_container(&self, project: String, compose_service: String) -> Result<Option<String>, ExecExecutorError> {
+        let filters = HashMap::from([(
+            "label".to_owned(),
+            vec![
+                format!("com.docker.compose.project={project}"),
+                format!("com.docker.compose.service={compose_service}"),
+            ],
+        )]);
+        let containers = connect()?
+            .list_containers(Some(ListContainersOptionsBuilder::new().filters(&filters).build()))
+            .await
+            .map_err(exec_failed)?;
+
+        Ok(containers
+            .into_iter()
+            .filter_map(|container| container.names?.into_iter().next())
+            .map(|name| name.trim_start_matches('/').to_owned())
+            .next())
+    }
+
+    async fn start(&self, container: String, command: Vec<String>, tty: bool) -> Result<ExecSession, ExecExecutorError> {
+        info!("Running {command} in {container}", command = command.join(" "));
+
+        let docker = connect()?;
+        let id = docker
+            .create_exec(
+                &container,
+                CreateExecOptions {
+                    attach_stdin: Some(true),
+                    attach_stdout: Some(true),
+                    attach_stderr: Some(true),
+                    tty: Some(tty),
+                    cmd: Some(command),
+                    ..Default::default()
+                },
+            )
+            .await
+            .map_err(exec_failed)?
+            .id;
+
+        match docker.start_exec(&id, None).await.map_err(exec_failed)? {
+            StartExecResults::Attached { output, input } => {
+                let output = output.filter_map(|chunk| async move {
+                    match chunk {
+                        // With a terminal both streams arrive as console output.
+                        Ok(LogOutput::StdOut { message } | LogOutput::Console { message }) => {
+                            Some(Ok(Output::Stdout(message.to_vec())))
+                        }
+                        Ok(LogOutput::StdErr { message }) => Some(Ok(Output::Stderr(message.to_vec()))),
+                        Ok(LogOutput::StdIn { .. }) => None,
+                        Err(e) => Some(Err(exec_failed(e))),
+                    }
+                });
+                Ok(ExecSession {
+                    id,
+                    output: Box::pin(output),
+                    input,
+                })
+            }
+            StartExecResults::Detached => Err(ExecExecutorError::new(
+                ExecErrorType::ExecFailed,
+                "exec started detached".to_owned(),
+            )),
+        }
+    }
+
+    async fn resize(&self, id: String, rows: u32, columns: u32) -> Result<(), ExecExecutorError> {
+        let options = ResizeExecOptionsBuilder::new()
+            .h(rows.min(i32::MAX as u32) as i32)
+            .w(columns.min(i32::MAX as u32) as i32)
+            .build();
+        connect()?.resize_exec(&id, options).await.map_err(exec_failed)
+    }
+
+    async fn exit_code(&self, id: String) -> Result<Option<i64>, ExecExecutorError> {
+        Ok(connect()?.inspect_exec(&id).await.map_err(exec_failed)?.exit_code)
+    }
+}
@@ -0,0 +1,142 @@
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
+use std::time::Duration;
+
+use bollard::Docker;
+use bollard::models::HealthStatusEnum;
+use bollard::query_parameters::InspectContainerOptions;
+use tokio::fs;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::process::Command;
+use tonic::async_trait;
+
+use crate::executors::container_status::ContainerStatus;
+use crate::executors::health_error_type::HealthErrorType;
+use crate::executors::{HealthExecutor, HealthExecutorError};
+use crate::services::Probe;
+
+/// How long a probe may take before the service counts as not answering.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Where services are provisioned.
+const PROVISIONING_ROOT: &str = "/mnt/srv";
+
+/// Inspects containers through the docker daemon's socket and probes them
+/// over the network.
+#[derive(Default)]
+pub struct RealHealthExecutor;
+
+fn probe_failed(message: String) -> HealthExecutorError {
+    HealthExecutorError::new(HealthErrorType::ProbeFailed, message)
+}
+
+/// Sends a bare HTTP/1.0 GET and checks the status line, which is all a
+/// probe needs.
+async fn http_get(address: SocketAddr, path: &str) -> Result<(), HealthExecutorError> {
+    let stream = TcpStream::connect(address).await.map_err(|e| probe_failed(e.to_string()))?;
+    let mut stream = BufReader::new(stream);
+    let request = format!("GET {path} HTTP/1.0\r\nHost: {address}\r\nConnection: close\r\n\r\n");
+    stream.write_all(request.as_bytes()).await.map_err(|e| probe_failed(e.to_string()))?;
+
+    let mut status_line = String::new();
+    stream.read_line(&mut status_line).await.map_err(|e| probe_failed(e.to_string()))?;
+
+    let status = status_line.split_whitespace().nth(1).and_then(|code| code.parse::<u16>().ok());
+    match status {
+        Some(200..=399) => Ok(()),
+        Some(code) => Err(probe_failed(format!("GET {path} answered {code}"))),
+        None => Err(probe_failed(format!("GET {path} did not answer with HTTP"))),
+    }
+}
+
+#[async_trait]
+impl HealthExecutor for RealHealthExecutor {
+    async fn container_status(&self, container: String) -> Result<Option<ContainerStatus>, HealthExecutorError> {
+        use bollard::errors::Error::*;
+
+        let docker = Docker::connect_with_local_defaults()
+            .map_err(|e| HealthExecutorError::new(HealthErrorType::DockerUnavailable, e.to_string()))?;
+
+        let inspect = match docker.inspect_container(&container, None::<InspectContainerOptions>).await {
+            Ok(inspect) => inspect,
+            Err(DockerResponseServerError { status_code: 404, .. }) => return Ok(None),
+            Err(e @ (IOError { .. } | HyperLegacyError { .. } | SocketNotFoundError(_) | RequestTimeoutError)) => {
+                return Err(HealthExecutorError::new(HealthErrorType::DockerUnavailable, e.to_string()));
+            }
+            Err(e) => return Err(HealthExecutorError::new(HealthErrorType::InspectFailed, e.to_string())),
+        };
+
+        let state = inspect.state.unwrap_or_default();
+        let health = match state.health.and_then(|health| health.status) {
+            None | Some(HealthStatusEnum::EMPTY | HealthStatusEnum::NONE) => String::new(),
+            Some(status) => status.to_string(),
+        };
+        let address = inspect
+            .network_settings
+            .and_then(|settings| settings.networks)
+            .and_then(|networks| {
+                // Sorted so the same network is picked on every check.
+                let mut networks: Vec<_> = networks.into_iter().collect();
+                networks.sort_by(|a, b| a.0.cmp(&b.0));
+                networks.into_iter().find_map(|(_, endpoint)| endpoint.ip_address?.parse().ok())
+            });
+
+        Ok(Some(ContainerStatus {
+            state: state.status.map(|status| status.to_string()).unwrap_or_default(),
+            health,
+            address,
+        }))
+    }
+
+    async fn probe(&self, address: IpAddr, probe: Probe) -> Result<(), HealthExecutorError> {
+        let result = match probe {
+            Probe::Tcp { port } => tokio::time::timeout(PROBE_TIMEOUT, async {
+                TcpStream::connect((address, port)).await.map(|_| ()).map_err(|e| probe_failed(e.to_string()))
+            })
+            .await,
+            Probe::Http { port, path } => {
+                tokio::time::timeout(PROBE_TIMEOUT, http_get(SocketAddr::new(address, port), path)).await
+            }
+        };
+
+        result.unwrap_or_else(|_| Err(probe_failed(format!("{url} did not answer within {PROBE_TIMEOUT:?}", url = probe.url(address)))))
+    }
+
+    async fn ping_docker(&self) -> Result<(), HealthExecutorError> {
+        let unavailable = |message: String| HealthExecutorError::new(HealthErrorType::DockerUnavailable, message);
+
+        let docker = Docker::connect_with_local_defaults().map_err(|e| unavailable(e.to_string()))?;
+        match tokio::time::timeout(PROBE_TIMEOUT, docker.ping()).await {
+            Ok(Ok(_)) => Ok(()),
+            Ok(Err(e)) => Err(unavailable(e.to_string())),
+            Err(_) => Err(unavailable(format!("docker did not answer within {PROBE_TIMEOUT:?}"))),
+        }
+    }
+
+    async fn ping_systemd(&self) -> Result<(), HealthExecutorError> {
+        let unavailable = |message: String| HealthExecutorError::new(HealthErrorType::SystemdUnavailable, message);
+
+        let output = Command::new("systemctl").args(["show", "--property=Version", "--value"]).output();
+        let output = match tokio::time::timeout(PROBE_TIMEOUT, output).await {
+            Ok(Ok(output)) => output,
+            Ok(Err(e)) => return Err(unavailable(format!("failed to run systemctl: {e}"))),
+            Err(_) => return Err(unavailable(format!("systemctl did not answer within {PROBE_TIMEOUT:?}"))),
+        };
+
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(unavailable(String::from_utf8_lossy(&output.stderr).trim().to_owned()))
+        }
+    }
+
+    async fn check_root_writable(&self) -> Result<(), HealthExecutorError> {
+        let path = Path::new(PROVISIONING_ROOT).join(".provisiond-health");
+        let not_writable =
+            |e: std::io::Error| HealthExecutorError::new(HealthErrorType::RootNotWritable, e.to_string()).with_path(&path);
+
+        fs::write(&path, b"").await.map_err(not_writable)?;
+        fs::remove_file(&path).await.map_err(not_writable)
+    }
+}
@@ -0,0 +1,54 @@
+use bollard::Docker;
+use bollard::query_parameters::CreateImageOptionsBuilder;
+use futures_util::StreamExt;
+use tonic::async_trait;
+use tracing::info;
+
+use crate::executors::image_error_type::ImageErrorType;
+use crate::executors::{ImageExecutor, ImageExecutorError};
+
+/// Pulls and inspects images through the docker daemon's socket.
+#[derive(Default)]
+pub struct RealImageExecutor;
+
+fn docker() -> Result<Docker, ImageExecutorError> {
+    Docker::connect_with_local_defaults()
+        .map_err(|e| ImageExecutorError::new(ImageErrorType::DockerUnavailable, e.to_string()))
+}
+
+fn image_error(err: bollard::errors::Error, kind: ImageErrorType) -> ImageExecutorError {
+    use bollard::errors::Error::*;
+
+    let kind = match &err {
+        IOError { .. } | HyperLegacyError { .. } | SocketNotFoundError(_) | RequestTimeoutError => {
+            ImageErrorType::DockerUnavailable
+        }
+        _ => kind,
+    };
+    ImageExecutorError::new(kind, err.to_string())
+}
+
+#[async_trait]
+impl ImageExecutor for RealImageExecutor {
+    async fn image_id(&self, image: String) -> Result<Option<String>, ImageExecutorError> {
+        match docker()?.inspect_image(&image).await {
+            Ok(inspect) => Ok(inspect.id),
+            Err(bollard::errors::Error::DockerResponseServerError { status_code: 404, .. }) => Ok(None),
+            Err(e) => Err(image_error(e, ImageErrorType::InspectFailed)),
+        }
+    }
+
+    async fn pull_image(&self, image: String) -> Result<(), ImageExecutorError> {
+        info!("Pulling image {image}");
+
+        let options = CreateImageOptionsBuilder::new().from_image(&image).build();
+        let docker = docker()?;
+        let mut progress = docker.create_image(Some(options), None, None);
+
+        while let Some(update) = progress.next().await {
+            update.map_err(|e| image_error(e, ImageErrorType::PullFailed))?;
+        }
+
+        Ok(())
+    }
+}
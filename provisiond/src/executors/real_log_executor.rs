@@ -0,0 +1,189 @@
+use std::process::Stdio;
+use std::time::UNIX_EPOCH;
+
+use bollard::Docker;
+use bollard::container::LogOutput;
+use bollard::query_parameters::LogsOptionsBuilder;
+use futures_util::{StreamExt, stream};
+use libprovision::hello_world::{LogLine, LogSource};
+use serde_json::Value;
+use tokio::io::{AsyncBufReadExt, BufReader, Lines};
+use tokio::process::{Child, ChildStdout, Command};
+use tonic::async_trait;
+
+use crate::executors::log_error_type::LogErrorType;
+use crate::executors::log_options::{LogOptions, LogStream};
+use crate::executors::{LogExecutor, LogExecutorError};
+
+/// Reads container logs through the docker daemon's socket and the unit's
+/// journal through `journalctl`.
+#[derive(Default)]
+pub struct RealLogExecutor;
+
+fn failed(kind: LogErrorType, message: String) -> LogStream {
+    Box::pin(stream::once(async move { Err(LogExecutorError::new(kind, message)) }))
+}
+
+fn logs_failed(err: bollard::errors::Error) -> LogExecutorError {
+    use bollard::errors::Error::*;
+
+    let kind = match &err {
+        DockerResponseServerError { status_code: 404, .. } => LogErrorType::ContainerNotFound,
+        IOError { .. } | HyperLegacyError { .. } | SocketNotFoundError(_) | RequestTimeoutError => {
+            LogErrorType::DockerUnavailable
+        }
+        _ => LogErrorType::LogsFailed,
+    };
+    LogExecutorError::new(kind, err.to_string())
+}
+
+/// Splits docker's `<RFC 3339 timestamp> <message>` lines. Lines without a
+/// timestamp are kept with a time of 0.
+fn container_line(container: &str, source: LogSource, line: &str) -> LogLine {
+    let parsed = line.split_once(' ').and_then(|(timestamp, message)| {
+        let time = humantime::parse_rfc3339(timestamp).ok()?;
+        Some((time.duration_since(UNIX_EPOCH).ok()?.as_nanos() as i64, message))
+    });
+    let (timestamp_nanos, message) = parsed.unwrap_or((0, line));
+
+    LogLine {
+        timestamp_nanos,
+        source: source as i32,
+        origin: container.to_owned(),
+        message: message.to_owned(),
+    }
+}
+
+/// Turns one line of `journalctl --output=json` into a log line.
+fn journal_line(unit: &str, line: &str) -> Result<LogLine, LogExecutorError> {
+    let entry: Value = serde_json::from_str(line)
+        .map_err(|e| LogExecutorError::new(LogErrorType::JournalFailed, format!("invalid journal entry: {e}")))?;
+
+    let timestamp_micros: i64 = entry["__REALTIME_TIMESTAMP"].as_str().and_then(|t| t.parse().ok()).unwrap_or_default();
+    // Messages that are not valid UTF-8 come as an array of bytes.
+    let message = match &entry["MESSAGE"] {
+        Value::String(message) => message.clone(),
+        Value::Array(bytes) => {
+            let bytes: Vec<u8> = bytes.iter().filter_map(|b| b.as_u64().map(|b| b as u8)).collect();
+            String::from_utf8_lossy(&bytes).into_owned()
+        }
+        _ => String::new(),
+    };
+
+    Ok(LogLine {
+        timestamp_nanos: timestamp_micros * 1000,
+        source: LogSource::Journal as i32,
+        origin: unit.to_owned(),
+        message,
+    })
+}
+
+type JournalState = Option<(Child, Lines<BufReader<ChildStdout>>)>;
+
+#[async_trait]
+impl LogExecutor for RealLogExecutor {
+    fn container_logs(&self, container: String, options: LogOptions) -> LogStream {
+        let docker = match Docker::connect_with_local_defaults() {
+            Ok(docker) => docker,
+            Err(e) => return failed(LogErrorType::DockerUnavailable, e.to_string()),
+        };
+
+        let tail = match options.tail {
+            0 => "all".to_owned(),
+            tail => tail.to_string(),
+        };
+        let query = LogsOptionsBuilder::new()
+            .follow(options.follow)
+            .stdout(true)
+            .stderr(true)
+            .timestamps(true)
+            .since(options.since.clamp(0, i32::MAX as i64) as i32)
+            .tail(&tail)
+            .build();
+
+        let logs = docker.logs(&container, Some(query)).flat_map(move |output| {
+            let lines = match output {
+                Ok(LogOutput::StdOut { message } | LogOutput::Console { message }) => (LogSource::Stdout, message),
+                Ok(LogOutput::StdErr { message }) => (LogSource::Stderr, message),
+                Ok(LogOutput::StdIn { .. }) => return stream::iter(Vec::new()),
+                Err(e) => return stream::iter(vec![Err(logs_failed(e))]),
+            };
+
+            // A frame can hold more than one line.
+            let (source, message) = lines;
+            let lines: Vec<_> = String::from_utf8_lossy(&message)
+                .lines()
+                .map(|line| Ok(container_line(&container, source, line)))
+                .collect();
+            stream::iter(lines)
+        });
+
+        Box::pin(logs)
+    }
+
+    fn journal(&self, service_name: String, options: LogOptions) -> LogStream {
+        let unit = format!("{service_name}.service");
+        let mut command = Command::new("journalctl");
+        command.args(["--unit", &unit, "--output=json", "--no-pager", "--quiet"]);
+        if options.follow {
+            command.arg("--follow");
+        }
+        if options.since > 0 {
+            command.arg(format!("--since=@{since}", since = options.since));
+        }
+        if options.tail > 0 {
+            command.arg(format!("--lines={tail}", tail = options.tail));
+        }
+        // journalctl keeps following until the client goes away and the
+        // stream, with the child in it, is dropped.
+        command.stdout(Stdio::piped()).stderr(Stdio::piped()).kill_on_drop(true);
+
+        let mut child = match command.spawn() {
+            Ok(child) => child,
+            Err(e) => return failed(LogErrorType::JournalFailed, format!("failed to run journalctl: {e}")),
+        };
+        let lines = BufReader::new(child.stdout.take().expect("stdout is piped")).lines();
+
+        let state: JournalState = Some((child, lines));
+        Box::pin(stream::unfold(state, move |state| {
+            let unit = unit.clone();
+            async move {
+                let (child, mut lines) = state?;
+                match lines.next_line().await {
+                    Ok(Some(line)) => Some((journal_line(&unit, &line), Some((child, lines)))),
+                    Ok(None) => {
+                        let output = child.wait_with_output().await.ok()?;
+                        match output.status.success() {
+                            true => None,
+                            false => Some((
+                                Err(LogExecutorError::new(
+                                    LogErrorType::JournalFailed,
+                                    String::from_utf8_lossy(&output.stderr).trim().to_owned(),
+                                )),
+                                None,
+                            )),
+                        }
+                    }
+                    Err(e) => Some((Err(LogExecutorError::new(LogErrorType::JournalFailed, e.to_string())), None)),
+                }
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_lines_parsed() {
+        let line = container_line("db", LogSource::Stdout, "2026-10-19T00:00:01.5Z ready to accept connections");
+        assert_eq!(line.timestamp_nanos, 1_792_368_001_500_000_000);
+        assert_eq!(line.message, "ready to accept connections");
+
+        let line = journal_line("db.service", r#"{"__REALTIME_TIMESTAMP":"1792368001000000","MESSAGE":[104,105]}"#).unwrap();
+        assert_eq!(line.timestamp_nanos, 1_792_368_001_000_000_000);
+        assert_eq!(line.message, "hi");
+        assert_eq!(line.source(), LogSource::Journal);
+    }
+}
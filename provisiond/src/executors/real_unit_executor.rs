@@ -0,0 +1,94 @@
+use std::path::PathBuf;
+use std::process::Command;
+
+use tonic::async_trait;
+use tracing::info;
+
+use crate::executors::unit_error_type::UnitErrorType;
+use crate::executors::{UnitExecutor, UnitExecutorError, run_blocking};
+
+#[derive(Default)]
+pub struct RealUnitExecutor;
+
+/// Runs `systemctl` and returns what it printed. A non-zero exit that printed
+/// nothing is an error carrying stderr.
+fn systemctl(args: &[&str], kind: UnitErrorType) -> Result<String, UnitExecutorError> {
+    info!("Running systemctl {}", args.join(" "));
+
+    let output = match run_blocking(Command::new("systemctl").args(args)) {
+        Ok(output) => output,
+        Err(e) => return Err(UnitExecutorError::new(kind, format!("failed to run systemctl: {e}"))),
+    };
+
+    let stdout = String::from_utf8_lossy(&output.stdout).trim().to_owned();
+    if !output.status.success() && stdout.is_empty() {
+        return Err(UnitExecutorError::new(
+            kind,
+            String::from_utf8_lossy(&output.stderr).trim().to_owned(),
+        ));
+    }
+
+    Ok(stdout)
+}
+
+#[async_trait]
+impl UnitExecutor for RealUnitExecutor {
+    fn reload_units(&self) -> Result<(), UnitExecutorError> {
+        systemctl(&["daemon-reload"], UnitErrorType::ReloadFailed).map(|_| ())
+    }
+
+    fn restart_unit(&self, service_name: String) -> Result<(), UnitExecutorError> {
+        systemctl(&["restart", &format!("{service_name}.service")], UnitErrorType::RestartFailed).map(|_| ())
+    }
+
+    fn link_unit(&self, service_name: String) -> Result<(), UnitExecutorError> {
+        let unit = format!("/mnt/srv/{service_name}/{service_name}.service");
+        systemctl(&["enable", &unit], UnitErrorType::LinkFailed).map(|_| ())
+    }
+
+    fn unlink_unit(&self, service_name: String) -> Result<(), UnitExecutorError> {
+        // is-enabled prints the state of any unit file systemd knows, even a
+        // disabled one, and nothing for one it does not.
+        let unit = format!("{service_name}.service");
+        if systemctl(&["is-enabled", &unit], UnitErrorType::UnlinkFailed).is_err() {
+            return Ok(());
+        }
+        systemctl(&["disable", &unit], UnitErrorType::UnlinkFailed).map(|_| ())
+    }
+
+    fn stop_unit(&self, service_name: String) -> Result<(), UnitExecutorError> {
+        // A unit that was never loaded is not active either, so there is
+        // nothing to stop.
+        if !self.unit_active(service_name.clone())? {
+            return Ok(());
+        }
+        systemctl(&["stop", &format!("{service_name}.service")], UnitErrorType::StopFailed).map(|_| ())
+    }
+
+    fn compose_down(&self, service_name: String) -> Result<(), UnitExecutorError> {
+        let folder = PathBuf::from(format!("/mnt/srv/{service_name}"));
+        if !folder.join("docker-compose.yaml").exists() {
+            return Ok(());
+        }
+        info!("Running docker compose down in {}", folder.display());
+
+        let mut command = Command::new("docker");
+        command.args(["compose", "down", "--remove-orphans"]).current_dir(&folder);
+        let output = run_blocking(&mut command)
+            .map_err(|e| UnitExecutorError::new(UnitErrorType::ComposeDownFailed, format!("failed to run docker: {e}")))?;
+        match output.status.success() {
+            true => Ok(()),
+            false => Err(UnitExecutorError::new(
+                UnitErrorType::ComposeDownFailed,
+                String::from_utf8_lossy(&output.stderr).trim().to_owned(),
+            )),
+        }
+    }
+
+    fn unit_active(&self, service_name: String) -> Result<bool, UnitExecutorError> {
+        // is-active exits non-zero for anything but active, the state is still
+        // printed so only a missing state is treated as an error.
+        let state = systemctl(&["is-active", &format!("{service_name}.service")], UnitErrorType::StatusFailed)?;
+        Ok(state == "active")
+    }
+}
@@ -0,0 +1,59 @@
+use std::collections::HashMap;
+
+use bollard::Docker;
+use bollard::query_parameters::ListVolumesOptionsBuilder;
+use tonic::async_trait;
+use tracing::info;
+
+use crate::executors::volume_error_type::VolumeErrorType;
+use crate::executors::{VolumeExecutor, VolumeExecutorError};
+
+/// Finds and removes the volumes compose created, through the docker
+/// daemon's socket.
+#[derive(Default)]
+pub struct RealVolumeExecutor;
+
+fn docker() -> Result<Docker, VolumeExecutorError> {
+    Docker::connect_with_local_defaults()
+        .map_err(|e| VolumeExecutorError::new(VolumeErrorType::DockerUnavailable, e.to_string()))
+}
+
+fn volume_error(err: bollard::errors::Error, kind: VolumeErrorType) -> VolumeExecutorError {
+    use bollard::errors::Error::*;
+
+    let kind = match &err {
+        DockerResponseServerError { status_code: 409, .. } => VolumeErrorType::VolumeInUse,
+        IOError { .. } | HyperLegacyError { .. } | SocketNotFoundError(_) | RequestTimeoutError => {
+            VolumeErrorType::DockerUnavailable
+        }
+        _ => kind,
+    };
+    VolumeExecutorError::new(kind, err.to_string())
+}
+
+#[async_trait]
+impl VolumeExecutor for RealVolumeExecutor {
+    async fn project_volumes(&self, project: String) -> Result<Vec<String>, VolumeExecutorError> {
+        let filters = HashMap::from([("label".to_owned(), vec![format!("com.docker.compose.project={project}")])]);
+        let volumes = docker()?
+            .list_volumes(Some(ListVolumesOptionsBuilder::new().filters(&filters).build()))
+            .await
+            .map_err(|e| volume_error(e, VolumeErrorType::ListFailed))?;
+
+        Ok(volumes.volumes.unwrap_or_default().into_iter().map(|volume| volume.name).collect())
+    }
+
+    async fn remove_volume(&self, name: String) -> Result<(), VolumeExecutorError> {
+        info!("Removing volume {name}");
+
+        // bollard 0.19 still takes the deprecated options here, the defaults
+        // are all that is needed.
+        #[allow(deprecated)]
+        let options = None::<bollard::volume::RemoveVolumeOptions>;
+        match docker()?.remove_volume(&name, options).await {
+            // Already gone, which is what was asked for.
+            Err(bollard::errors::Error::DockerResponseServerError { status_code: 404, .. }) => Ok(()),
+            result => result.map_err(|e| volume_error(e, VolumeErrorType::RemoveFailed)),
+        }
+    }
+}
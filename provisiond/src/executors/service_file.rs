@@ -0,0 +1,36 @@
+use std::path::PathBuf;
+
+/// The generated files in a service's folder.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ServiceFile {
+    Compose,
+    Env,
+    Unit,
+    Definition,
+}
+
+impl ServiceFile {
+    pub const ALL: [ServiceFile; 4] = [
+        ServiceFile::Compose,
+        ServiceFile::Env,
+        ServiceFile::Unit,
+        ServiceFile::Definition,
+    ];
+
+    pub fn folder(service_name: &str) -> PathBuf {
+        PathBuf::from(format!("/mnt/srv/{service_name}"))
+    }
+
+    pub fn file_name(&self, service_name: &str) -> String {
+        match self {
+            ServiceFile::Compose => "docker-compose.yaml".to_owned(),
+            ServiceFile::Env => ".env".to_owned(),
+            ServiceFile::Unit => format!("{service_name}.service"),
+            ServiceFile::Definition => "provision.json".to_owned(),
+        }
+    }
+
+    pub fn path(&self, service_name: &str) -> PathBuf {
+        Self::folder(service_name).join(self.file_name(service_name))
+    }
+}
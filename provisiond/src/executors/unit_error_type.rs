@@ -0,0 +1,65 @@
+use std::fmt::{Display, Formatter};
+
+use tonic::Code;
+
+use crate::executors::ErrorReason;
+
+#[derive(Debug)]
+pub enum UnitErrorType {
+    ReloadFailed,
+    RestartFailed,
+    LinkFailed,
+    UnlinkFailed,
+    StopFailed,
+    ComposeDownFailed,
+    StatusFailed,
+
+    Unhealthy,
+}
+
+impl Display for UnitErrorType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        use UnitErrorType::*;
+
+        match self {
+            ReloadFailed => write!(f, "Reloading systemd units failed"),
+            RestartFailed => write!(f, "Unit restart failed"),
+            LinkFailed => write!(f, "Linking the unit failed"),
+            UnlinkFailed => write!(f, "Unlinking the unit failed"),
+            StopFailed => write!(f, "Unit stop failed"),
+            ComposeDownFailed => write!(f, "Taking the containers down failed"),
+            StatusFailed => write!(f, "Checking the unit status failed"),
+
+            Unhealthy => write!(f, "Unit did not become healthy"),
+        }
+    }
+}
+
+impl ErrorReason for UnitErrorType {
+    fn reason(&self) -> &'static str {
+        use UnitErrorType::*;
+
+        match self {
+            ReloadFailed => "UNIT_RELOAD_FAILED",
+            RestartFailed => "UNIT_RESTART_FAILED",
+            LinkFailed => "UNIT_LINK_FAILED",
+            UnlinkFailed => "UNIT_UNLINK_FAILED",
+            StopFailed => "UNIT_STOP_FAILED",
+            ComposeDownFailed => "COMPOSE_DOWN_FAILED",
+            StatusFailed => "UNIT_STATUS_FAILED",
+
+            Unhealthy => "UNIT_UNHEALTHY",
+        }
+    }
+
+    fn code(&self) -> Code {
+        use UnitErrorType::*;
+
+        match self {
+            ReloadFailed | RestartFailed | LinkFailed | UnlinkFailed | StopFailed | ComposeDownFailed | StatusFailed => {
+                Code::Internal
+            }
+            Unhealthy => Code::FailedPrecondition,
+        }
+    }
+}
@@ -0,0 +1,49 @@
+use std::fmt::{Display, Formatter};
+
+use tonic::Code;
+
+use crate::executors::ErrorReason;
+
+#[derive(Debug)]
+pub enum VolumeErrorType {
+    DockerUnavailable,
+    VolumeInUse,
+    ListFailed,
+    RemoveFailed,
+}
+
+impl Display for VolumeErrorType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        use VolumeErrorType::*;
+
+        match self {
+            DockerUnavailable => write!(f, "Docker is not reachable"),
+            VolumeInUse => write!(f, "The volume is still used by a container"),
+            ListFailed => write!(f, "Listing the volumes failed"),
+            RemoveFailed => write!(f, "Removing the volume failed"),
+        }
+    }
+}
+
+impl ErrorReason for VolumeErrorType {
+    fn reason(&self) -> &'static str {
+        use VolumeErrorType::*;
+
+        match self {
+            DockerUnavailable => "DOCKER_UNAVAILABLE",
+            VolumeInUse => "VOLUME_IN_USE",
+            ListFailed => "VOLUME_LIST_FAILED",
+            RemoveFailed => "VOLUME_REMOVE_FAILED",
+        }
+    }
+
+    fn code(&self) -> Code {
+        use VolumeErrorType::*;
+
+        match self {
+            DockerUnavailable => Code::Unavailable,
+            VolumeInUse => Code::FailedPrecondition,
+            ListFailed | RemoveFailed => Code::Internal,
+        }
+    }
+}
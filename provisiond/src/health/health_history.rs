@@ -0,0 +1,119 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::Mutex;
+
+use libprovision::hello_world::{GetHealthResponse, HealthCheckResult, HealthStatus};
+
+/// Checks kept per service.
+pub const HISTORY_LEN: usize = 20;
+
+#[derive(Default)]
+struct ServiceHealth {
+    /// When the status last changed, in unix seconds.
+    since: i64,
+    checks: VecDeque<HealthCheckResult>,
+    /// Unhealthy checks in a row. Unknown results neither add to it nor
+    /// reset it, docker being briefly away says nothing about the service.
+    unhealthy_streak: u32,
+}
+
+/// The last `HISTORY_LEN` health checks of every service.
+#[derive(Default)]
+pub struct HealthHistory {
+    services: Mutex<BTreeMap<String, ServiceHealth>>,
+}
+
+impl HealthHistory {
+    /// Adds a check, returning the status it replaced if it changed.
+    pub fn record(&self, service_name: &str, check: HealthCheckResult) -> Option<HealthStatus> {
+        let mut services = self.services.lock().unwrap();
+        let health = services.entry(service_name.to_owned()).or_default();
+
+        let previous = health.checks.back().map(|last| last.status());
+        if previous != Some(check.status()) {
+            health.since = check.checked_at;
+        }
+        match check.status() {
+            HealthStatus::Unhealthy => health.unhealthy_streak += 1,
+            HealthStatus::Healthy => health.unhealthy_streak = 0,
+            _ => (),
+        }
+
+        let changed = previous.filter(|previous| *previous != check.status());
+        health.checks.push_back(check);
+        if health.checks.len() > HISTORY_LEN {
+            health.checks.pop_front();
+        }
+        changed
+    }
+
+    pub fn unhealthy_streak(&self, service_name: &str) -> u32 {
+        self.services
+            .lock()
+            .unwrap()
+            .get(service_name)
+            .map_or(0, |health| health.unhealthy_streak)
+    }
+
+    /// `None` if the service has not been checked yet.
+    pub fn get(&self, service_name: &str) -> Option<GetHealthResponse> {
+        let services = self.services.lock().unwrap();
+        let health = services.get(service_name)?;
+
+        Some(GetHealthResponse {
+            service_name: service_name.to_owned(),
+            status: health.checks.back().map_or(HealthStatus::Unknown, |last| last.status()) as i32,
+            since: health.since,
+            history: health.checks.iter().cloned().collect(),
+        })
+    }
+
+    /// Forgets services that no longer exist.
+    pub fn retain(&self, service_names: &[String]) {
+        self.services.lock().unwrap().retain(|name, _| service_names.contains(name));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(checked_at: i64, status: HealthStatus) -> HealthCheckResult {
+        HealthCheckResult {
+            checked_at,
+            status: status as i32,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    pub fn test_transitions_and_streaks() {
+        let history = HealthHistory::default();
+
+        assert_eq!(history.record("test_service", check(1, HealthStatus::Healthy)), None);
+        assert_eq!(history.record("test_service", check(2, HealthStatus::Unhealthy)), Some(HealthStatus::Healthy));
+        assert_eq!(history.record("test_service", check(3, HealthStatus::Unknown)), Some(HealthStatus::Unhealthy));
+        assert_eq!(history.record("test_service", check(4, HealthStatus::Unhealthy)), Some(HealthStatus::Unknown));
+
+        assert_eq!(history.unhealthy_streak("test_service"), 2, "Unknown checks should not reset the streak");
+        let health = history.get("test_service").unwrap();
+        assert_eq!(health.status(), HealthStatus::Unhealthy);
+        assert_eq!(health.since, 4);
+
+        history.record("test_service", check(5, HealthStatus::Healthy));
+        assert_eq!(history.unhealthy_streak("test_service"), 0);
+    }
+
+    #[test]
+    pub fn test_history_is_bounded() {
+        let history = HealthHistory::default();
+
+        for checked_at in 0..(HISTORY_LEN as i64 + 5) {
+            history.record("test_service", check(checked_at, HealthStatus::Healthy));
+        }
+
+        let health = history.get("test_service").unwrap();
+        assert_eq!(health.history.len(), HISTORY_LEN);
+        assert_eq!(health.history[0].checked_at, 5, "The oldest checks should be dropped");
+        assert_eq!(health.since, 0);
+    }
+}
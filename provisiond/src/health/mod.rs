@@ -0,0 +1,3 @@
+mod health_history;
+
+pub use health_history::HealthHistory;
@@ -0,0 +1,300 @@
+use std::fs::{self, DirBuilder, OpenOptions, Permissions};
+use std::io::{self, ErrorKind, Write};
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use clap::Args;
+
+use crate::permissions::{DOCKER_GROUP, EtcPasswd, Passwd, ServiceOwner};
+
+const SERVICE_UNIT: &str = include_str!("../res/provisiond.service");
+const SOCKET_UNIT: &str = include_str!("../res/provisiond.socket");
+
+/// Folders the daemon expects, with their modes.
+const FOLDERS: [(&str, u32); 5] = [
+    ("/etc/provisiond", 0o755),
+    ("/var/lib/provisiond", 0o700),
+    ("/var/lib/provisiond/backups", 0o700),
+    ("/var/log/provisiond", 0o750),
+    ("/mnt/srv", 0o755),
+];
+
+/// Config files written only if missing, so local changes are kept. The
+/// policy holds tokens, hence the mode.
+const CONFIG: [(&str, &str, u32); 3] = [
+    ("/etc/provisiond/provisiond.json", "{\n  \"quota\": {}\n}\n", 0o644),
+    ("/etc/provisiond/policy.json", "{\n  \"subjects\": []\n}\n", 0o600),
+    ("/etc/provisiond/schedules.json", "[]\n", 0o644),
+];
+
+#[derive(Args, Debug)]
+pub struct InstallArgs {
+    /// Where the binary goes, as <prefix>/bin/provisiond
+    #[arg(long, default_value = "/usr/local")]
+    pub prefix: PathBuf,
+
+    /// Install into this folder instead of the running system, systemd is
+    /// then left alone
+    #[arg(long, default_value = "/")]
+    pub root: PathBuf,
+}
+
+#[derive(Args, Debug)]
+pub struct UninstallArgs {
+    #[command(flatten)]
+    pub location: InstallArgs,
+
+    /// Also remove the config, state, logs and the service user. Service
+    /// folders under /mnt/srv are never removed
+    #[arg(long)]
+    pub purge: bool,
+}
+
+/// Where an installation lives. Paths are given as on the installed host
+/// and resolved under `root`.
+pub struct Layout {
+    root: PathBuf,
+    prefix: PathBuf,
+}
+
+impl Layout {
+    pub fn new(root: PathBuf, prefix: PathBuf) -> Self {
+        Self { root, prefix }
+    }
+
+    fn path(&self, absolute: impl AsRef<Path>) -> PathBuf {
+        self.root.join(absolute.as_ref().strip_prefix("/").unwrap_or(absolute.as_ref()))
+    }
+
+    /// The binary as the unit refers to it.
+    fn binary(&self) -> PathBuf {
+        self.prefix.join("bin/provisiond")
+    }
+
+    fn on_host(&self) -> bool {
+        self.root == Path::new("/")
+    }
+}
+
+/// Lays down the binary, units, folders, config skeleton and the service
+/// user. Anything already in place is left as it is, so running it again
+/// changes nothing. Returns what it did.
+pub fn install(layout: &Layout, binary: &[u8], passwd: &dyn Passwd, owner: &ServiceOwner) -> io::Result<Vec<String>> {
+    let mut done = Vec::new();
+
+    for (folder, mode) in FOLDERS {
+        let path = layout.path(folder);
+        if !path.is_dir() {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            DirBuilder::new().mode(mode).create(&path)?;
+            done.push(format!("Created {}", path.display()));
+        }
+        // The umask may have taken bits away.
+        if fs::metadata(&path)?.permissions().mode() & 0o7777 != mode {
+            fs::set_permissions(&path, Permissions::from_mode(mode))?;
+            done.push(format!("Set mode {mode:o} on {}", path.display()));
+        }
+    }
+
+    let service_unit = SERVICE_UNIT.replace("/usr/local/bin/provisiond", &layout.binary().to_string_lossy());
+    let files: [(PathBuf, &[u8], u32); 3] = [
+        (layout.binary(), binary, 0o755),
+        (PathBuf::from("/etc/systemd/system/provisiond.service"), service_unit.as_bytes(), 0o644),
+        (PathBuf::from("/etc/systemd/system/provisiond.socket"), SOCKET_UNIT.as_bytes(), 0o644),
+    ];
+    for (file, contents, mode) in files {
+        let path = layout.path(file);
+        if fs::read(&path).is_ok_and(|current| current == contents) {
+            continue;
+        }
+        if let Some(folder) = path.parent() {
+            fs::create_dir_all(folder)?;
+        }
+        replace(&path, contents, mode)?;
+        done.push(format!("Wrote {}", path.display()));
+    }
+
+    for (file, contents, mode) in CONFIG {
+        let path = layout.path(file);
+        match OpenOptions::new().write(true).create_new(true).mode(mode).open(&path) {
+            Ok(mut config) => {
+                config.write_all(contents.as_bytes())?;
+                done.push(format!("Wrote {}", path.display()));
+            }
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    // Units run `docker compose` as the service user. A user from an
+    // earlier install that is missing the group gets it on a re-run.
+    if passwd.user_id(&owner.user)?.is_none() {
+        passwd.create_account(&owner.user, &[DOCKER_GROUP])?;
+        done.push(format!("Created user {}", owner.user));
+    } else if !passwd.user_groups(&owner.user)?.iter().any(|group| group == DOCKER_GROUP) {
+        passwd.add_to_group(&owner.user, DOCKER_GROUP)?;
+        done.push(format!("Added user {user} to group {DOCKER_GROUP}", user = owner.user));
+    }
+
+    if layout.on_host() {
+        if !done.is_empty() {
+            systemctl(&["daemon-reload"])?;
+        }
+        if systemctl(&["is-enabled", "--quiet", "provisiond.socket", "provisiond.service"]).is_err() {
+            systemctl(&["enable", "provisiond.socket", "provisiond.service"])?;
+            done.push("Enabled provisiond.socket and provisiond.service".to_owned());
+        }
+    }
+
+    Ok(done)
+}
+
+/// Removes what `install` laid down. The config, state, logs and service
+/// user only go with `purge`, and service folders never do, the service
+/// user stays while any service is left.
+pub fn uninstall(layout: &Layout, purge: bool, passwd: &dyn Passwd, owner: &ServiceOwner) -> io::Result<Vec<String>> {
+    let mut done = Vec::new();
+
+    if layout.on_host() {
+        // Fails when the units are already gone, which is what we want.
+        let _ = systemctl(&["disable", "--now", "provisiond.socket", "provisiond.service"]);
+    }
+
+    let files = [
+        PathBuf::from("/etc/systemd/system/provisiond.socket"),
+        PathBuf::from("/etc/systemd/system/provisiond.service"),
+        layout.binary(),
+    ];
+    for file in files {
+        let path = layout.path(file);
+        match fs::remove_file(&path) {
+            Ok(()) => done.push(format!("Removed {}", path.display())),
+            Err(e) if e.kind() == ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+    }
+    if layout.on_host() {
+        systemctl(&["daemon-reload"])?;
+    }
+
+    if !purge {
+        return Ok(done);
+    }
+
+    for folder in ["/etc/provisiond", "/var/lib/provisiond", "/var/log/provisiond"] {
+        let path = layout.path(folder);
+        match fs::remove_dir_all(&path) {
+            Ok(()) => done.push(format!("Removed {}", path.display())),
+            Err(e) if e.kind() == ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    let services = layout.path("/mnt/srv");
+    let services_left = match fs::read_dir(&services) {
+        Ok(mut entries) => entries.next().is_some(),
+        Err(e) if e.kind() == ErrorKind::NotFound => false,
+        Err(e) => return Err(e),
+    };
+    if services_left {
+        done.push(format!("Kept {} and user {}, services are left", services.display(), owner.user));
+        return Ok(done);
+    }
+    if fs::remove_dir(&services).is_ok() {
+        done.push(format!("Removed {}", services.display()));
+    }
+    if passwd.user_id(&owner.user)?.is_some() {
+        passwd.delete_account(&owner.user)?;
+        done.push(format!("Removed user {}", owner.user));
+    }
+
+    Ok(done)
+}
+
+/// Accounts of the host, or of the tree under `root`.
+pub fn passwd_for(layout: &Layout) -> EtcPasswd {
+    match layout.on_host() {
+        true => EtcPasswd::default(),
+        false => EtcPasswd::in_root(layout.root.clone()),
+    }
+}
+
+/// Writes `contents` to a temp file next to `path` and renames it over, so
+/// a running binary is swapped rather than written into.
+fn replace(path: &Path, contents: &[u8], mode: u32) -> io::Result<()> {
+    let mut temp_path = path.as_os_str().to_owned();
+    temp_path.push(".tmp");
+    let temp_path = PathBuf::from(temp_path);
+
+    let mut temp = OpenOptions::new().write(true).create(true).truncate(true).mode(mode).open(&temp_path)?;
+    temp.write_all(contents)?;
+    temp.sync_all()?;
+    fs::set_permissions(&temp_path, Permissions::from_mode(mode))?;
+    fs::rename(&temp_path, path)
+}
+
+fn systemctl(args: &[&str]) -> io::Result<()> {
+    let output = Command::new("systemctl").args(args).output()?;
+    match output.status.success() {
+        true => Ok(()),
+        false => Err(io::Error::other(format!(
+            "systemctl {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::permissions::FakePasswd;
+
+    #[test]
+    pub fn test_install_idempotent_and_uninstalled() {
+        let root = PathBuf::from("/tmp/provisiond_tests/test_install_idempotent_and_uninstalled");
+        let _ = fs::remove_dir_all(&root);
+        let layout = Layout::new(root.clone(), PathBuf::from("/opt/provisiond"));
+        let passwd = FakePasswd::new(&[]);
+        let owner = ServiceOwner::default();
+
+        let done = install(&layout, b"binary", &passwd, &owner).unwrap();
+        assert!(done.contains(&format!("Created user {}", owner.user)));
+        assert!(passwd.exists(&owner.user));
+        let unit = fs::read_to_string(root.join("etc/systemd/system/provisiond.service")).unwrap();
+        assert!(unit.contains("ExecStart=/opt/provisiond/bin/provisiond\n"), "{unit}");
+        assert_eq!(fs::read(root.join("opt/provisiond/bin/provisiond")).unwrap(), b"binary");
+        let policy = fs::metadata(root.join("etc/provisiond/policy.json")).unwrap();
+        assert_eq!(policy.permissions().mode() & 0o777, 0o600);
+        assert_eq!(fs::metadata(root.join("var/lib/provisiond")).unwrap().permissions().mode() & 0o777, 0o700);
+
+        // Local config survives, and nothing else is touched a second time.
+        fs::write(root.join("etc/provisiond/schedules.json"), "[ ]").unwrap();
+        assert_eq!(install(&layout, b"binary", &passwd, &owner).unwrap(), Vec::<String>::new());
+        assert_eq!(fs::read_to_string(root.join("etc/provisiond/schedules.json")).unwrap(), "[ ]");
+        assert_eq!(passwd.user_groups(&owner.user).unwrap(), [DOCKER_GROUP]);
+
+        // A user left by an older install is put in the docker group.
+        let existing = FakePasswd::new(&[(&owner.user, 999)]);
+        let done = install(&layout, b"binary", &existing, &owner).unwrap();
+        assert_eq!(done, [format!("Added user {} to group docker", owner.user)]);
+        assert_eq!(existing.user_groups(&owner.user).unwrap(), [DOCKER_GROUP]);
+
+        fs::create_dir(root.join("mnt/srv/db1")).unwrap();
+        uninstall(&layout, true, &passwd, &owner).unwrap();
+        assert!(!root.join("opt/provisiond/bin/provisiond").exists());
+        assert!(!root.join("etc/provisiond").exists());
+        assert!(root.join("mnt/srv/db1").exists(), "Services should never be removed");
+        assert!(passwd.exists(&owner.user), "The user should stay while services use it");
+
+        fs::remove_dir(root.join("mnt/srv/db1")).unwrap();
+        uninstall(&layout, true, &passwd, &owner).unwrap();
+        assert!(!passwd.exists(&owner.user));
+        assert!(!root.join("mnt/srv").exists());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
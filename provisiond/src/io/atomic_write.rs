@@ -0,0 +1,273 @@
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Versions kept in `.history` when no count is given.
+pub const DEFAULT_HISTORY: usize = 10;
+
+/// Folder next to a replaced file that holds its older versions.
+pub const HISTORY_DIR: &str = ".history";
+
+/// Keeps temp file names unique between concurrent writes in this process.
+static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// What is kept of a file when it is replaced.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Backup {
+    /// The previous version as `<file>.bak`.
+    #[default]
+    Previous,
+    /// `<file>.bak`, plus the last n versions under `.history`.
+    History(usize),
+}
+
+impl FromStr for Backup {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "bak" => Ok(Backup::Previous),
+            "history" => Ok(Backup::History(DEFAULT_HISTORY)),
+            _ => match value.strip_prefix("history:").map(str::parse) {
+                Some(Ok(keep)) if keep > 0 => Ok(Backup::History(keep)),
+                _ => Err(format!("invalid backup policy '{value}', expected bak, history or history:<count>")),
+            },
+        }
+    }
+}
+
+/// Path the previous version of `path` is kept at.
+pub fn backup_path(path: &Path) -> PathBuf {
+    with_suffix(path, ".bak")
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = OsString::from(path);
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+/// Replaces the file at `path` with `contents`. The contents go to a temp file
+/// in the same folder which is synced and renamed over the original, so after
+/// a crash the file holds either the old or the new contents in full.
+pub fn write_atomic(path: &Path, contents: &[u8], backup: Backup) -> io::Result<()> {
+    let (folder, file_name) = match (path.parent(), path.file_name()) {
+        (Some(folder), Some(file_name)) => (folder, file_name.to_string_lossy()),
+        _ => return Err(io::Error::new(ErrorKind::InvalidInput, "path does not name a file")),
+    };
+
+    let temp_path = folder.join(format!(
+        ".{file_name}.tmp-{pid}-{count}",
+        pid = std::process::id(),
+        count = TEMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    let result = write_temp(&temp_path, path, contents)
+        .and_then(|()| keep_previous(path, backup))
+        .and_then(|()| fs::rename(&temp_path, path));
+
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result?;
+
+    // The rename only survives a crash once the folder itself is synced.
+    File::open(folder)?.sync_all()
+}
+
+/// Puts the version `write_atomic` kept back in place, or removes the file
+/// if there was nothing before it.
+pub fn restore_backup(path: &Path) -> io::Result<()> {
+    let backup = backup_path(path);
+
+    let result = match backup.exists() {
+        true => fs::rename(&backup, path),
+        false => fs::remove_file(path),
+    };
+
+    match result {
+        Err(err) if err.kind() != ErrorKind::NotFound => Err(err),
+        _ => match path.parent() {
+            Some(folder) => File::open(folder)?.sync_all(),
+            None => Ok(()),
+        },
+    }
+}
+
+/// Keeps the current version of `path` as its `.bak`, or drops a stale
+/// `.bak` when there is no file.
+pub fn back_up(path: &Path) -> io::Result<()> {
+    keep_previous(path, Backup::Previous)?;
+
+    match path.parent() {
+        Some(folder) => File::open(folder)?.sync_all(),
+        None => Ok(()),
+    }
+}
+
+fn write_temp(temp_path: &Path, path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut file = OpenOptions::new().write(true).create_new(true).open(temp_path)?;
+
+    // A replaced file keeps its mode rather than falling back to the umask.
+    if let Ok(metadata) = fs::metadata(path) {
+        file.set_permissions(metadata.permissions())?;
+    }
+
+    file.write_all(contents)?;
+    file.sync_all()
+}
+
+fn keep_previous(path: &Path, backup: Backup) -> io::Result<()> {
+    let backup_file = backup_path(path);
+
+    match fs::copy(path, &backup_file) {
+        Ok(_) => File::open(&backup_file)?.sync_all()?,
+        // Nothing to keep, a backup left from an older version must not be
+        // restored in place of a file that did not exist.
+        Err(err) if err.kind() == ErrorKind::NotFound => {
+            return match fs::remove_file(&backup_file) {
+                Err(err) if err.kind() != ErrorKind::NotFound => Err(err),
+                _ => Ok(()),
+            };
+        }
+        Err(err) => return Err(err),
+    }
+
+    if let Backup::History(keep) = backup {
+        keep_history(path, keep)?;
+    }
+
+    Ok(())
+}
+
+/// Copies the current version into `.history` and drops all but the newest
+/// `keep` versions of the file.
+fn keep_history(path: &Path, keep: usize) -> io::Result<()> {
+    let (Some(folder), Some(file_name)) = (path.parent(), path.file_name()) else {
+        return Ok(());
+    };
+    let history = folder.join(HISTORY_DIR);
+    fs::create_dir_all(&history)?;
+
+    let stamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    let prefix = format!("{}.", file_name.to_string_lossy());
+    fs::copy(path, history.join(format!("{prefix}{stamp:020}")))?;
+
+    let mut versions: Vec<PathBuf> = fs::read_dir(&history)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|version| {
+            version
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_prefix(&prefix))
+                .is_some_and(|stamp| stamp.chars().all(|c| c.is_ascii_digit()))
+        })
+        .collect();
+    versions.sort();
+
+    let excess = versions.len().saturating_sub(keep);
+    for version in &versions[..excess] {
+        fs::remove_file(version)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_root_path(test_name: &str) -> PathBuf {
+        let path = PathBuf::from(format!("/tmp/provisiond_tests/{}", test_name));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).expect("Failed to create test path");
+        path
+    }
+
+    fn file_names(path: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(path)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    pub fn test_file_replaced_with_backup() {
+        let path = get_root_path("test_file_replaced_with_backup");
+        let file = path.join("docker-compose.yaml");
+
+        write_atomic(&file, b"first", Backup::Previous).expect("Failed to write file");
+        assert!(!backup_path(&file).exists(), "New file should have no backup");
+
+        write_atomic(&file, b"second", Backup::Previous).expect("Failed to replace file");
+
+        assert_eq!(fs::read_to_string(&file).unwrap(), "second");
+        assert_eq!(fs::read_to_string(backup_path(&file)).unwrap(), "first");
+        assert_eq!(file_names(&path), vec!["docker-compose.yaml", "docker-compose.yaml.bak"], "No temp files should be left");
+
+        restore_backup(&file).expect("Failed to restore backup");
+
+        assert_eq!(fs::read_to_string(&file).unwrap(), "first");
+
+        fs::remove_dir_all(&path)
+            .unwrap_or_else(|_| panic!("Failed to delete test path at {}", path.display()));
+    }
+
+    #[test]
+    pub fn test_restore_after_back_up_keeps_current() {
+        let path = get_root_path("test_restore_after_back_up_keeps_current");
+        let file = path.join("docker-compose.yaml");
+
+        write_atomic(&file, b"first", Backup::Previous).expect("Failed to write file");
+        write_atomic(&file, b"second", Backup::Previous).expect("Failed to replace file");
+
+        // A replace that never ran after the backup restores what is there.
+        back_up(&file).expect("Failed to back up file");
+        restore_backup(&file).expect("Failed to restore backup");
+
+        assert_eq!(fs::read_to_string(&file).unwrap(), "second");
+
+        fs::remove_dir_all(&path)
+            .unwrap_or_else(|_| panic!("Failed to delete test path at {}", path.display()));
+    }
+
+    #[test]
+    pub fn test_history_pruned() {
+        let path = get_root_path("test_history_pruned");
+        let file = path.join(".env");
+
+        for version in 0..5 {
+            write_atomic(&file, format!("V={version}").as_bytes(), Backup::History(2))
+                .expect("Failed to write file");
+        }
+
+        let history = file_names(&path.join(HISTORY_DIR));
+        let kept: Vec<String> = history
+            .iter()
+            .map(|name| fs::read_to_string(path.join(HISTORY_DIR).join(name)).unwrap())
+            .collect();
+
+        assert_eq!(kept, vec!["V=2", "V=3"], "Only the newest two old versions should be kept");
+
+        fs::remove_dir_all(&path)
+            .unwrap_or_else(|_| panic!("Failed to delete test path at {}", path.display()));
+    }
+
+    #[test]
+    pub fn test_backup_policy_parsed() {
+        assert_eq!("bak".parse(), Ok(Backup::Previous));
+        assert_eq!("history".parse(), Ok(Backup::History(DEFAULT_HISTORY)));
+        assert_eq!("history:3".parse(), Ok(Backup::History(3)));
+        assert!("history:0".parse::<Backup>().is_err());
+        assert!("none".parse::<Backup>().is_err());
+    }
+}
@@ -1,18 +1,28 @@
 use mockall::automock;
 use tonic::async_trait;
 use std::io;
-use std::path::PathBuf;
+use std::path::Path;
 
 #[automock]
 #[async_trait]
 pub(crate) trait FileManager {
-    fn new(provision_path: &PathBuf) -> io::Result<Self> where Self: Sized;
+    fn new(provision_path: &Path) -> io::Result<Self> where Self: Sized;
+
+    /// Folders that hold a service, other folders such as `lost+found` are
+    /// left out.
+    fn list_services(&self) -> io::Result<Vec<String>>;
+    /// Contents of the service's `provision.json`, `None` if it has none.
+    fn read_definition(&self, service_name: String) -> io::Result<Option<String>>;
 
     fn service_folder_exists(&self, service_name: String) -> bool;
     fn unit_file_exists(&self, service_name: String) -> bool;
     fn env_file_exists(&self, service_name: String) -> bool;
     fn compose_file_exists(&self, service_name: String) -> bool;
 
+    /// Atomically replaces a file in the service's folder, keeping the old
+    /// version according to the manager's backup policy.
+    fn write_file(&self, service_name: String, file_name: &str, contents: &str) -> io::Result<()>;
+
     fn create_service_folder(&self, service_name: String) -> io::Result<()>;
     fn create_unit_file(&self, service_name: String) -> io::Result<()>;
     fn create_env_file(&self, service_name: String) -> io::Result<()>;
@@ -1,5 +1,7 @@
+mod atomic_write;
 mod file_manager;
 mod real_file_manager;
  
+pub use atomic_write::*;
 pub use file_manager::*;
 pub(crate) use real_file_manager::*;
\ No newline at end of file
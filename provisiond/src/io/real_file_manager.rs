@@ -1,23 +1,65 @@
+use crate::io::atomic_write::{Backup, write_atomic};
 use crate::io::file_manager::FileManager;
-use std::io::Write;
-use std::path::PathBuf;
+use crate::services::ServiceDefinition;
+use std::path::{Path, PathBuf};
 use std::{fs, io};
-use std::fs::File;
 
 pub(crate) struct RealFileManager {
     root_path: PathBuf,
+    backup: Backup,
+}
+
+impl RealFileManager {
+    pub fn with_backup(mut self, backup: Backup) -> Self {
+        self.backup = backup;
+        self
+    }
 }
 
 impl FileManager for RealFileManager {
-    fn new(provision_path: &PathBuf) -> io::Result<Self> {
+    fn new(provision_path: &Path) -> io::Result<Self> {
         if !provision_path.exists() {
             fs::create_dir_all(provision_path)?;
         }
         Ok(RealFileManager {
-            root_path: provision_path.clone(),
+            root_path: provision_path.to_path_buf(),
+            backup: Backup::default(),
         })
     }
 
+    fn list_services(&self) -> io::Result<Vec<String>> {
+        if !self.root_path.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut services = Vec::new();
+        for entry in fs::read_dir(&self.root_path)? {
+            let entry = entry?;
+            if !entry.file_type()?.is_dir() {
+                continue;
+            }
+            // Only a name a service could have been created with, and a
+            // folder it has written its compose file to.
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if ServiceDefinition::legacy(name.clone()).validate().is_ok() && self.compose_file_exists(name.clone()) {
+                services.push(name);
+            }
+        }
+        services.sort();
+
+        Ok(services)
+    }
+
+    fn read_definition(&self, service_name: String) -> io::Result<Option<String>> {
+        let definition_file = self.root_path.join(service_name).join("provision.json");
+
+        match fs::read_to_string(definition_file) {
+            Ok(contents) => Ok(Some(contents)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
     fn service_folder_exists(&self, service_name: String) -> bool {
         let service_folder = self.root_path.clone().join(service_name);
         service_folder.exists()
@@ -46,6 +88,11 @@ impl FileManager for RealFileManager {
         compose_file.exists()
     }
 
+    fn write_file(&self, service_name: String, file_name: &str, contents: &str) -> io::Result<()> {
+        let file = self.root_path.join(service_name).join(file_name);
+        write_atomic(&file, contents.as_bytes(), self.backup)
+    }
+
     fn create_service_folder(&self, service_name: String) -> io::Result<()> {
         let service_folder = self.root_path.clone().join(service_name);
         fs::create_dir_all(&service_folder)?;
@@ -53,40 +100,23 @@ impl FileManager for RealFileManager {
     }
 
     fn create_unit_file(&self, service_name: String) -> io::Result<()> {
-        let unit_file = self
-            .root_path
-            .clone()
-            .join(&service_name)
-            .join(service_name + ".service");
-
-        let mut file = fs::File::create(&unit_file)?;
+        let file_name = format!("{service_name}.service");
 
         let contents = r#"
         [Servcie]
         Description=Hello world
         "#
-        .trim()
-        .as_bytes();
+        .trim();
 
-        file.write_all(contents)?;
-
-        Ok(())
+        self.write_file(service_name, &file_name, contents)
     }
 
     fn create_env_file(&self, service_name: String) -> io::Result<()> {
-        let env_file = self.root_path.clone().join(&service_name).join(".env");
-        let _ = fs::File::create(&env_file)?;
-        Ok(())
+        self.write_file(service_name, ".env", "")
     }
 
     fn create_compose_file(&self, service_name: String) -> io::Result<()> {
-        let compose_file = self
-            .root_path
-            .clone()
-            .join(&service_name)
-            .join("docker-compose.yaml");
-        let _ = fs::File::create(&compose_file)?;
-        Ok(())
+        self.write_file(service_name, "docker-compose.yaml", "")
     }
 }
 
@@ -94,6 +124,7 @@ impl Default for RealFileManager {
     fn default() -> Self {
         Self {
             root_path: PathBuf::from("/mnt/srv/"),
+            backup: Backup::default(),
         }
     }
 }
@@ -105,8 +136,7 @@ mod tests {
     use std::path::PathBuf;
 
     fn get_root_path(test_name: &str) -> PathBuf {
-        let path = PathBuf::from(format!("/tmp/provisiond_tests/{}", test_name));
-        path
+        PathBuf::from(format!("/tmp/provisiond_tests/{}", test_name))
     }
 
     #[test]
@@ -127,7 +157,7 @@ mod tests {
         assert!(path.exists(), "Path at {} Does not exist", path.display());
 
         fs::remove_dir(&path)
-            .expect(format!("Failed to delete test path at {}", path.display()).as_str());
+            .unwrap_or_else(|_| panic!("Failed to delete test path at {}", path.display()));
     }
 
     #[test]
@@ -150,7 +180,86 @@ mod tests {
         assert!(exists, "Service folder should now exist");
 
         fs::remove_dir_all(&path)
-            .expect(format!("Failed to delete test path at {}", path.display()).as_str());
+            .unwrap_or_else(|_| panic!("Failed to delete test path at {}", path.display()));
+    }
+
+    #[test]
+    pub fn test_services_listed() {
+        let path = get_root_path("test_services_listed");
+
+        let fm = RealFileManager::new(&path).expect("Failed to create file manager");
+
+        assert!(fm.list_services().expect("Failed to list services").is_empty());
+
+        for service_name in ["b_service", "a_service"] {
+            fm.create_service_folder(service_name.to_owned())
+                .expect("Failed to create service folder");
+            fm.create_compose_file(service_name.to_owned())
+                .expect("Failed to create compose file");
+        }
+        fs::write(path.join("stray_file"), "").expect("Failed to create stray file");
+        fs::create_dir(path.join("lost+found")).expect("Failed to create lost+found");
+        fs::write(path.join("lost+found").join("docker-compose.yaml"), "").expect("Failed to write to lost+found");
+        fs::create_dir(path.join("stray_folder")).expect("Failed to create stray folder");
+
+        let services = fm.list_services().expect("Failed to list services");
+
+        assert_eq!(services, vec!["a_service", "b_service"], "Only service folders should be listed, in order");
+
+        fs::remove_dir_all(&path)
+            .unwrap_or_else(|_| panic!("Failed to delete test path at {}", path.display()));
+    }
+
+    #[test]
+    pub fn test_definition_read() {
+        let path = get_root_path("test_definition_read");
+        let service_name = "test_service".to_owned();
+
+        let fm = RealFileManager::new(&path).expect("Failed to create file manager");
+        fm.create_service_folder(service_name.clone())
+            .expect("Failed to create service folder");
+
+        let definition = fm.read_definition(service_name.clone()).expect("Failed to read definition");
+
+        assert_eq!(definition, None, "Service without a definition should read as None");
+
+        fs::write(path.join(&service_name).join("provision.json"), "{}").expect("Failed to write definition");
+
+        let definition = fm.read_definition(service_name.clone()).expect("Failed to read definition");
+
+        assert_eq!(definition.as_deref(), Some("{}"));
+
+        fs::remove_dir_all(&path)
+            .unwrap_or_else(|_| panic!("Failed to delete test path at {}", path.display()));
+    }
+
+    #[test]
+    pub fn test_file_replaced_with_history() {
+        let path = get_root_path("test_file_replaced_with_history");
+        let service_name = "test_service".to_owned();
+
+        let fm = RealFileManager::new(&path)
+            .expect("Failed to create file manager")
+            .with_backup(Backup::History(1));
+        fm.create_service_folder(service_name.clone())
+            .expect("Failed to create service folder");
+
+        for contents in ["first", "second", "third"] {
+            fm.write_file(service_name.clone(), ".env", contents)
+                .expect("Failed to write env file");
+        }
+
+        let service_path = path.join(&service_name);
+        let history: Vec<_> = fs::read_dir(service_path.join(".history"))
+            .expect("History folder should exist")
+            .collect();
+
+        assert_eq!(fs::read_to_string(service_path.join(".env")).unwrap(), "third");
+        assert_eq!(fs::read_to_string(service_path.join(".env.bak")).unwrap(), "second");
+        assert_eq!(history.len(), 1, "Only one old version should be kept");
+
+        fs::remove_dir_all(&path)
+            .unwrap_or_else(|_| panic!("Failed to delete test path at {}", path.display()));
     }
 
     #[test]
@@ -174,7 +283,7 @@ mod tests {
         assert!(exists, "Unit file should exist");
 
         fs::remove_dir_all(&path)
-            .expect(format!("Failed to delete test path at {}", path.display()).as_str());
+            .unwrap_or_else(|_| panic!("Failed to delete test path at {}", path.display()));
     }
 
     #[test]
@@ -198,7 +307,7 @@ mod tests {
         assert!(exists, "Env file should now exist");
 
         fs::remove_dir_all(&path)
-            .expect(format!("Failed to delete test path at {}", path.display()).as_str());
+            .unwrap_or_else(|_| panic!("Failed to delete test path at {}", path.display()));
     }
 
     #[test]
@@ -222,6 +331,6 @@ mod tests {
         assert!(exists, "Compose file should exist");
 
         fs::remove_dir_all(&path)
-            .expect(format!("Failed to delete test path at {}", path.display()).as_str());
+            .unwrap_or_else(|_| panic!("Failed to delete test path at {}", path.display()));
     }
 }
@@ -1,43 +1,213 @@
+// tonic::Status is large, but it is what every handler has to return anyway.
+#![allow(clippy::result_large_err)]
+
+mod access;
+mod backups;
+mod config;
 mod provisioner_server;
 mod operations;
 mod executors;
+mod health;
+mod install;
+// Only used for reading service state, the executors do not use it yet.
+#[allow(dead_code)]
 mod io;
+mod metrics;
+mod permissions;
+mod ports;
+mod scheduler;
+mod schedules;
+mod services;
+mod shutdown;
+mod state;
+mod systemd;
+mod telemetry;
 
+use crate::access::{AuditLog, Policy};
+use crate::backups::BackupStore;
+use crate::config::DaemonConfig;
+use crate::install::{InstallArgs, Layout, UninstallArgs};
+use crate::metrics::{Metrics, RpcMetricsLayer};
+use crate::permissions::ServiceOwner;
+use crate::ports::PortPool;
 use crate::provisioner_server::ProvisionerImpl;
-use libprovision::hello_world::{
-    Greeter, GreeterServer, HelloReply, HelloRequest, ProvisionerServer,
-};
-use log::info;
+use crate::schedules::{ScheduleStore, load_global};
+use crate::state::OperationJournal;
+use crate::telemetry::RpcSpanLayer;
+use libprovision::hello_world::ProvisionerServer;
+use clap::{Parser, Subcommand};
+use tracing::{info, warn};
+use std::fmt::Display;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
 use tonic::transport::Server;
-use tonic::{Request, Response, Status};
-
-#[derive(Debug, Default)]
-struct GreeterServerImpl;
-
-#[tonic::async_trait]
-impl Greeter for GreeterServerImpl {
-    async fn say_hello(
-        &self,
-        request: Request<HelloRequest>,
-    ) -> Result<Response<HelloReply>, Status> {
-        info!("Got a request from {:?}", request.remote_addr());
-        Ok(Response::new(HelloReply {
-            message: "Hello world".into(),
-        }))
-    }
+use tonic::transport::server::TcpIncoming;
+
+/// Where gRPC is served unless systemd passes a socket.
+const ADDR: &str = "[::1]:50051";
+
+/// Runs the daemon unless a subcommand is given.
+#[derive(Parser, Debug)]
+#[command(version, about = "Provisions docker compose services as systemd units", long_about = None)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Set up the binary, units, folders, config and service user on a host
+    Install(InstallArgs),
+    /// Remove what install set up
+    Uninstall(UninstallArgs),
 }
 
-#[tokio::main(flavor = "current_thread")]
+/// Prints a startup error the operator has to fix and exits, rather than
+/// panicking.
+fn or_exit<T>(result: Result<T, impl Display>) -> T {
+    result.unwrap_or_else(|e| {
+        eprintln!("error: {e}");
+        std::process::exit(1);
+    })
+}
+
+// Multi-threaded so commands run with `run_blocking` do not stall the rest,
+// the systemd watchdog ping included.
+#[tokio::main]
 async fn main() -> () {
-    env_logger::init();
+    if let Some(command) = Cli::parse().command {
+        let owner = ServiceOwner::default();
+        let result = match command {
+            Command::Install(args) => {
+                let layout = Layout::new(args.root, args.prefix);
+                std::env::current_exe()
+                    .and_then(std::fs::read)
+                    .and_then(|binary| install::install(&layout, &binary, &install::passwd_for(&layout), &owner))
+            }
+            Command::Uninstall(args) => {
+                let layout = Layout::new(args.location.root, args.location.prefix);
+                install::uninstall(&layout, args.purge, &install::passwd_for(&layout), &owner)
+            }
+        };
+        match result {
+            Ok(done) if done.is_empty() => println!("Nothing to do"),
+            Ok(done) => done.iter().for_each(|line| println!("{line}")),
+            Err(e) => {
+                eprintln!("error: {e}");
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
 
-    let g = GreeterServerImpl::default();
-    let provisioner_server = ProvisionerImpl::default();
+    // Settings come from the config file, with PROVISIOND_* variables and
+    // RUST_LOG overriding single ones.
+    let config = std::env::var("PROVISIOND_CONFIG").unwrap_or_else(|_| config::DEFAULT_PATH.to_owned());
+    let config = or_exit(
+        DaemonConfig::load(&PathBuf::from(config))
+            .and_then(|config| config.with_env(|name| std::env::var(name).ok()))
+            .and_then(DaemonConfig::validate),
+    );
 
-    Server::builder()
-        .add_service(GreeterServer::new(g))
-        .add_service(ProvisionerServer::new(provisioner_server))
-        .serve("[::1]:50051".parse().unwrap())
-        .await
-        .expect("TODO: panic message");
+    // Lines are text or json, and spans also go to an OTLP collector if one
+    // is set.
+    let tracer_provider = or_exit(telemetry::init(&config.log_filter, config.log_format, config.otlp_endpoint()));
+
+    let backup_store = BackupStore::new(config.backup_dir.clone(), config.backup_keep);
+    let global_schedules = or_exit(load_global(&config.schedules));
+    let schedule_store =
+        ScheduleStore::load(config.schedule_state.clone(), global_schedules).expect("Failed to read the schedule state");
+    let policy = Arc::new(or_exit(Policy::load(&config.policy)));
+    let port_pool = PortPool::load(config.port_pool, config.port_state.clone())
+        .expect("Failed to read the port assignments")
+        .with_address(config.port_address);
+    let journal = OperationJournal::open(config.journal.clone()).expect("Failed to read the operation journal");
+    let metrics = Arc::new(Metrics::default());
+    let provisioner_server = ProvisionerImpl::with_backup(config.backup)
+        .with_service_owner(config.service_owner())
+        .with_backup_store(backup_store)
+        .with_schedule_store(schedule_store)
+        .with_restart_unhealthy(config.restart_unhealthy)
+        .with_quota(config.quota.clone())
+        .with_port_pool(port_pool)
+        .with_policy(policy.clone())
+        .with_audit_log(AuditLog::new(config.audit_log.clone()))
+        .with_metrics(metrics.clone())
+        .with_journal(journal);
+    for operation in provisioner_server.running_operations() {
+        provisioner_server.recover(&operation).await;
+    }
+    if let Err(status) = provisioner_server.release_unused_ports() {
+        warn!("{}", status.message());
+    }
+    provisioner_server.warn_on_permissions();
+
+    let provisioner_server = Arc::new(provisioner_server);
+    tokio::spawn(scheduler::run(provisioner_server.clone()));
+    if config.health_interval > 0 {
+        tokio::spawn(scheduler::monitor(provisioner_server.clone(), Duration::from_secs(config.health_interval)));
+    }
+    if let Some(addr) = config.metrics_addr {
+        let provisioner_server = provisioner_server.clone();
+        tokio::spawn(async move {
+            if let Err(e) = metrics::serve(addr, provisioner_server).await {
+                warn!("Failed to serve metrics on {addr}: {e}");
+            }
+        });
+    }
+
+    // grpc.health.v1 reports docker, systemd and the provisioning root by
+    // name, and "" for the daemon as a whole.
+    let (health_reporter, health_server) = tonic_health::server::health_reporter();
+    tokio::spawn(scheduler::report_dependencies(provisioner_server.clone(), health_reporter));
+    // grpc.reflection.v1 lets tools like grpcurl discover what is served.
+    let reflection_server = tonic_reflection::server::Builder::configure()
+        .register_encoded_file_descriptor_set(libprovision::FILE_DESCRIPTOR_SET)
+        .register_encoded_file_descriptor_set(tonic_health::pb::FILE_DESCRIPTOR_SET)
+        .build_v1()
+        .expect("Failed to read the served proto descriptors");
+
+    // Under socket activation systemd holds the socket, so requests that come
+    // in while the daemon restarts wait rather than fail.
+    let incoming = match systemd::activated_listener().expect("Failed to take the socket passed by systemd") {
+        Some(listener) => TcpIncoming::from(tokio::net::TcpListener::from_std(listener).expect("Failed to listen on the passed socket")),
+        None => TcpIncoming::bind(ADDR.parse().unwrap()).expect("Failed to bind the gRPC address"),
+    };
+
+    let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+    let server = Server::builder()
+        .layer(RpcSpanLayer::new(policy))
+        .layer(RpcMetricsLayer::new(metrics))
+        .add_service(health_server)
+        .add_service(reflection_server)
+        .add_service(ProvisionerServer::from_arc(provisioner_server.clone()))
+        .serve_with_incoming_shutdown(incoming.with_nodelay(Some(true)), async {
+            let _ = stopped.await;
+        });
+    let mut server = std::pin::pin!(server);
+
+    // Interrupted operations are recovered and the socket is bound by now.
+    systemd::notify("READY=1");
+    if let Some(interval) = systemd::watchdog_interval() {
+        tokio::spawn(systemd::keep_alive(interval));
+    }
+
+    tokio::select! {
+        result = &mut server => result.expect("Failed to serve gRPC"),
+        () = shutdown::requested() => {
+            systemd::notify("STOPPING=1");
+            // New connections and requests are turned away from here on.
+            let _ = stop.send(());
+            // Operations are journaled, the ones that do not finish in time
+            // are recovered on the next start.
+            shutdown::drain(&provisioner_server, server, config.shutdown_timeout()).await;
+        }
+    }
+    info!("Stopped");
+    if let Some(provider) = tracer_provider
+        && let Err(e) = provider.shutdown()
+    {
+        eprintln!("Failed to send the last spans: {e}");
+    }
 }
@@ -0,0 +1,23 @@
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use axum::Router;
+use axum::http::header::CONTENT_TYPE;
+use axum::routing::get;
+use tokio::net::TcpListener;
+use tracing::info;
+
+use crate::provisioner_server::ProvisionerImpl;
+
+/// Serves `GET /metrics` for Prometheus to scrape.
+pub async fn serve(address: SocketAddr, provisioner: Arc<ProvisionerImpl>) -> io::Result<()> {
+    let app = Router::new().route(
+        "/metrics",
+        get(move || async move { ([(CONTENT_TYPE, "text/plain; version=0.0.4")], provisioner.render_metrics()) }),
+    );
+
+    let listener = TcpListener::bind(address).await?;
+    info!("Serving metrics on http://{address}/metrics");
+    axum::serve(listener, app).await
+}
@@ -0,0 +1,42 @@
+use std::time::Duration;
+
+use crate::metrics::TextFormat;
+
+/// Upper bounds in seconds. Steps like pulling an image take minutes, so the
+/// buckets go further than RPC latencies alone would need.
+const BUCKETS: [f64; 14] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 300.0];
+
+#[derive(Clone, Debug, Default)]
+pub struct Histogram {
+    /// Observations per bucket, not yet summed up. The last one counts what
+    /// is above every bound.
+    counts: [u64; BUCKETS.len() + 1],
+    sum: f64,
+}
+
+impl Histogram {
+    pub fn observe(&mut self, elapsed: Duration) {
+        let seconds = elapsed.as_secs_f64();
+        let bucket = BUCKETS.iter().position(|bound| seconds <= *bound).unwrap_or(BUCKETS.len());
+        self.counts[bucket] += 1;
+        self.sum += seconds;
+    }
+
+    /// Writes the `_bucket`, `_sum` and `_count` samples of `name`.
+    pub fn write(&self, format: &mut TextFormat, name: &str, labels: &[(&str, &str)]) {
+        let bucket_name = format!("{name}_bucket");
+        let mut cumulative = 0;
+        for (bound, count) in BUCKETS.iter().chain([&f64::INFINITY]).zip(self.counts) {
+            cumulative += count;
+            let le = match *bound {
+                f64::INFINITY => "+Inf".to_owned(),
+                bound => bound.to_string(),
+            };
+            let mut bucket_labels = labels.to_vec();
+            bucket_labels.push(("le", &le));
+            format.sample(&bucket_name, &bucket_labels, cumulative as f64);
+        }
+        format.sample(&format!("{name}_sum"), labels, self.sum);
+        format.sample(&format!("{name}_count"), labels, cumulative as f64);
+    }
+}
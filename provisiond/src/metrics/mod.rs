@@ -0,0 +1,13 @@
+mod endpoint;
+mod histogram;
+mod registry;
+mod rpc_layer;
+mod text_format;
+
+pub use endpoint::serve;
+pub use registry::Metrics;
+pub use rpc_layer::RpcMetricsLayer;
+pub use text_format::TextFormat;
+
+/// Where `/metrics` is served unless `PROVISIOND_METRICS_ADDR` says otherwise.
+pub const DEFAULT_ADDR: &str = "[::1]:9184";
@@ -0,0 +1,125 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use tonic::Code;
+
+use crate::metrics::TextFormat;
+use crate::metrics::histogram::Histogram;
+
+#[derive(Default)]
+struct Inner {
+    /// Keyed by gRPC service, method and status code.
+    rpcs: BTreeMap<(String, String, String), u64>,
+    rpc_durations: BTreeMap<(String, String), Histogram>,
+    step_durations: BTreeMap<String, Histogram>,
+    step_failures: BTreeMap<String, u64>,
+    rollbacks: u64,
+    /// Unix time of the last successful pull per service.
+    pulls: BTreeMap<String, u64>,
+}
+
+/// Counters the daemon keeps while running. Per-service gauges that can be
+/// read from the host are added when `/metrics` is rendered instead.
+#[derive(Default)]
+pub struct Metrics {
+    inner: Mutex<Inner>,
+}
+
+impl Metrics {
+    pub fn rpc_finished(&self, service: &str, method: &str, code: Code, elapsed: Duration) {
+        let mut inner = self.inner.lock().unwrap();
+        *inner
+            .rpcs
+            .entry((service.to_owned(), method.to_owned(), format!("{code:?}")))
+            .or_default() += 1;
+        inner
+            .rpc_durations
+            .entry((service.to_owned(), method.to_owned()))
+            .or_default()
+            .observe(elapsed);
+    }
+
+    pub fn step_finished(&self, step_name: &str, elapsed: Duration, failed: bool) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.step_durations.entry(step_name.to_owned()).or_default().observe(elapsed);
+        let failures = inner.step_failures.entry(step_name.to_owned()).or_default();
+        if failed {
+            *failures += 1;
+        }
+    }
+
+    pub fn rolled_back(&self) {
+        self.inner.lock().unwrap().rollbacks += 1;
+    }
+
+    pub fn pulled(&self, service_name: &str, at: u64) {
+        self.inner.lock().unwrap().pulls.insert(service_name.to_owned(), at);
+    }
+
+    pub fn last_pull(&self, service_name: &str) -> Option<u64> {
+        self.inner.lock().unwrap().pulls.get(service_name).copied()
+    }
+
+    pub fn write(&self, format: &mut TextFormat) {
+        let inner = self.inner.lock().unwrap();
+
+        format.family("provisiond_rpc_requests_total", "counter", "gRPC calls handled, by method and status code.");
+        for ((service, method, code), count) in &inner.rpcs {
+            let labels = [("grpc_service", service.as_str()), ("grpc_method", method.as_str()), ("grpc_code", code.as_str())];
+            format.sample("provisiond_rpc_requests_total", &labels, *count as f64);
+        }
+
+        format.family("provisiond_rpc_duration_seconds", "histogram", "Time until a gRPC call answered.");
+        for ((service, method), histogram) in &inner.rpc_durations {
+            let labels = [("grpc_service", service.as_str()), ("grpc_method", method.as_str())];
+            histogram.write(format, "provisiond_rpc_duration_seconds", &labels);
+        }
+
+        format.family("provisiond_step_duration_seconds", "histogram", "Time operation steps took, by step.");
+        for (step, histogram) in &inner.step_durations {
+            histogram.write(format, "provisiond_step_duration_seconds", &[("step", step)]);
+        }
+
+        format.family("provisiond_step_failures_total", "counter", "Operation steps that failed, by step.");
+        for (step, failures) in &inner.step_failures {
+            format.sample("provisiond_step_failures_total", &[("step", step)], *failures as f64);
+        }
+
+        format.family("provisiond_rollbacks_total", "counter", "Operations whose steps were rolled back.");
+        format.sample("provisiond_rollbacks_total", &[], inner.rollbacks as f64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_rpcs_and_steps_counted() {
+        let metrics = Metrics::default();
+        metrics.rpc_finished("provision.Provisioner", "Create", Code::Ok, Duration::from_millis(20));
+        metrics.rpc_finished("provision.Provisioner", "Create", Code::NotFound, Duration::from_millis(2));
+        metrics.step_finished("Create Folder", Duration::from_millis(3), false);
+        metrics.step_finished("Create Folder", Duration::from_secs(400), true);
+        metrics.rolled_back();
+
+        let mut format = TextFormat::default();
+        metrics.write(&mut format);
+        let text = format.finish();
+
+        let expected = [
+            r#"provisiond_rpc_requests_total{grpc_service="provision.Provisioner",grpc_method="Create",grpc_code="NotFound"} 1"#,
+            r#"provisiond_rpc_duration_seconds_bucket{grpc_service="provision.Provisioner",grpc_method="Create",le="0.005"} 1"#,
+            r#"provisiond_rpc_duration_seconds_bucket{grpc_service="provision.Provisioner",grpc_method="Create",le="0.025"} 2"#,
+            r#"provisiond_step_duration_seconds_bucket{step="Create Folder",le="300"} 1"#,
+            r#"provisiond_step_duration_seconds_bucket{step="Create Folder",le="+Inf"} 2"#,
+            r#"provisiond_step_duration_seconds_count{step="Create Folder"} 2"#,
+            r#"provisiond_step_failures_total{step="Create Folder"} 1"#,
+            "provisiond_rollbacks_total 1",
+        ];
+        for line in expected {
+            assert!(text.lines().any(|l| l == line), "Missing {line} in\n{text}");
+        }
+    }
+}
@@ -0,0 +1,59 @@
+use std::fmt::Write;
+
+/// Writes metrics in the Prometheus text exposition format.
+#[derive(Default)]
+pub struct TextFormat {
+    out: String,
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+impl TextFormat {
+    /// Starts a metric family, its samples have to follow before the next one.
+    pub fn family(&mut self, name: &str, kind: &str, help: &str) {
+        let _ = writeln!(self.out, "# HELP {name} {help}");
+        let _ = writeln!(self.out, "# TYPE {name} {kind}");
+    }
+
+    pub fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: f64) {
+        self.out.push_str(name);
+        if !labels.is_empty() {
+            let labels: Vec<String> = labels
+                .iter()
+                .map(|(key, value)| format!("{key}=\"{value}\"", value = escape(value)))
+                .collect();
+            let _ = write!(self.out, "{{{labels}}}", labels = labels.join(","));
+        }
+        let _ = match value {
+            f64::INFINITY => writeln!(self.out, " +Inf"),
+            value => writeln!(self.out, " {value}"),
+        };
+    }
+
+    pub fn finish(self) -> String {
+        self.out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_samples_written() {
+        let mut format = TextFormat::default();
+        format.family("provisiond_rollbacks_total", "counter", "Operations that were rolled back.");
+        format.sample("provisiond_rollbacks_total", &[], 2.0);
+        format.sample("provisiond_step_seconds_sum", &[("step", "Say \"hi\"\n")], 0.25);
+
+        assert_eq!(
+            format.finish(),
+            "# HELP provisiond_rollbacks_total Operations that were rolled back.\n\
+             # TYPE provisiond_rollbacks_total counter\n\
+             provisiond_rollbacks_total 2\n\
+             provisiond_step_seconds_sum{step=\"Say \\\"hi\\\"\\n\"} 0.25\n"
+        );
+    }
+}
@@ -0,0 +1,86 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, ErrorKind};
+use std::sync::Mutex;
+
+use crate::permissions::Passwd;
+
+/// Keeps accounts in memory, for tests that create and delete them.
+pub struct FakePasswd {
+    /// Name to id, users and their groups share both.
+    accounts: Mutex<BTreeMap<String, u32>>,
+    /// User to the groups it is in besides its own.
+    groups: Mutex<BTreeMap<String, BTreeSet<String>>>,
+}
+
+impl FakePasswd {
+    pub fn new(accounts: &[(&str, u32)]) -> Self {
+        Self {
+            accounts: Mutex::new(accounts.iter().map(|(name, id)| (name.to_string(), *id)).collect()),
+            groups: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn exists(&self, name: &str) -> bool {
+        self.accounts.lock().unwrap().contains_key(name)
+    }
+
+    fn name(&self, id: u32) -> Option<String> {
+        self.accounts
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|(_, account_id)| **account_id == id)
+            .map(|(name, _)| name.clone())
+    }
+}
+
+impl Passwd for FakePasswd {
+    fn user_id(&self, name: &str) -> io::Result<Option<u32>> {
+        Ok(self.accounts.lock().unwrap().get(name).copied())
+    }
+
+    fn group_id(&self, name: &str) -> io::Result<Option<u32>> {
+        self.user_id(name)
+    }
+
+    fn user_name(&self, uid: u32) -> io::Result<Option<String>> {
+        Ok(self.name(uid))
+    }
+
+    fn group_name(&self, gid: u32) -> io::Result<Option<String>> {
+        Ok(self.name(gid))
+    }
+
+    fn user_groups(&self, name: &str) -> io::Result<Vec<String>> {
+        Ok(self.groups.lock().unwrap().get(name).map(|groups| groups.iter().cloned().collect()).unwrap_or_default())
+    }
+
+    fn create_account(&self, name: &str, groups: &[&'static str]) -> io::Result<()> {
+        let mut accounts = self.accounts.lock().unwrap();
+        if accounts.contains_key(name) {
+            return Err(io::Error::new(ErrorKind::AlreadyExists, format!("account '{name}' already exists")));
+        }
+
+        let id = accounts.values().max().map_or(1000, |id| id + 1);
+        accounts.insert(name.to_owned(), id);
+        let groups = groups.iter().map(|group| group.to_string()).collect();
+        self.groups.lock().unwrap().insert(name.to_owned(), groups);
+        Ok(())
+    }
+
+    fn add_to_group(&self, name: &str, group: &'static str) -> io::Result<()> {
+        if !self.exists(name) {
+            return Err(io::Error::new(ErrorKind::NotFound, format!("account '{name}' does not exist")));
+        }
+        self.groups.lock().unwrap().entry(name.to_owned()).or_default().insert(group.to_owned());
+        Ok(())
+    }
+
+    fn delete_account(&self, name: &str) -> io::Result<()> {
+        self.groups.lock().unwrap().remove(name);
+        match self.accounts.lock().unwrap().remove(name) {
+            Some(_) => Ok(()),
+            None => Err(io::Error::new(ErrorKind::NotFound, format!("account '{name}' does not exist"))),
+        }
+    }
+}
@@ -0,0 +1,9 @@
+#[cfg(test)]
+mod fake_passwd;
+mod passwd;
+mod permission_plan;
+
+#[cfg(test)]
+pub use fake_passwd::FakePasswd;
+pub use passwd::{EtcPasswd, Passwd};
+pub use permission_plan::{DOCKER_GROUP, FOLDER_MODE, FileCheck, Owner, ServiceOwner, apply, check};
@@ -0,0 +1,214 @@
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::PathBuf;
+use std::process::Command;
+
+use mockall::automock;
+use tracing::info;
+
+use crate::executors::run_blocking;
+
+/// Looks up and manages users and groups, so tests do not depend on or
+/// change the host's accounts.
+#[automock]
+pub trait Passwd {
+    fn user_id(&self, name: &str) -> io::Result<Option<u32>>;
+    fn group_id(&self, name: &str) -> io::Result<Option<u32>>;
+    fn user_name(&self, uid: u32) -> io::Result<Option<String>>;
+    fn group_name(&self, gid: u32) -> io::Result<Option<String>>;
+    /// Groups the user is in besides its primary one.
+    fn user_groups(&self, name: &str) -> io::Result<Vec<String>>;
+
+    /// Adds a system user without a login shell or home, with a group of the
+    /// same name and in `groups` as well. Fails with `AlreadyExists` rather
+    /// than reusing an account.
+    fn create_account(&self, name: &str, groups: &[&'static str]) -> io::Result<()>;
+    /// Adds an existing user to a group.
+    fn add_to_group(&self, name: &str, group: &'static str) -> io::Result<()>;
+    /// Removes the user and its group, `NotFound` if there is no such user.
+    fn delete_account(&self, name: &str) -> io::Result<()>;
+}
+
+/// Reads `/etc/passwd` and `/etc/group` directly. Accounts only known to
+/// other NSS sources such as LDAP are not found.
+pub struct EtcPasswd {
+    passwd_path: PathBuf,
+    group_path: PathBuf,
+    /// Accounts are changed in the tree under this folder rather than on
+    /// the host, with the commands' `--root`.
+    root: Option<PathBuf>,
+}
+
+impl EtcPasswd {
+    pub fn new(passwd_path: PathBuf, group_path: PathBuf) -> Self {
+        Self {
+            passwd_path,
+            group_path,
+            root: None,
+        }
+    }
+
+    /// The accounts of the system installed under `root`.
+    pub fn in_root(root: PathBuf) -> Self {
+        Self {
+            passwd_path: root.join("etc/passwd"),
+            group_path: root.join("etc/group"),
+            root: Some(root),
+        }
+    }
+
+    /// Runs a shadow-utils command against the host or the tree under `root`.
+    fn run(&self, program: &str, args: &[&str]) -> io::Result<()> {
+        match &self.root {
+            Some(root) => run(program, &[&["--root", &root.to_string_lossy()], args].concat()),
+            None => run(program, args),
+        }
+    }
+
+    /// Name and id of every entry, both files keep them in the first and
+    /// third field.
+    fn entries(path: &PathBuf) -> io::Result<Vec<(String, u32)>> {
+        let contents = fs::read_to_string(path)?;
+
+        Ok(contents
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .filter_map(|line| {
+                let mut fields = line.split(':');
+                let name = fields.next()?;
+                let id = fields.nth(1)?.parse().ok()?;
+                Some((name.to_owned(), id))
+            })
+            .collect())
+    }
+}
+
+/// Runs a shadow-utils command, exit codes are the ones useradd, userdel and
+/// groupdel share.
+fn run(program: &str, args: &[&str]) -> io::Result<()> {
+    info!("Running {program} {}", args.join(" "));
+
+    let output = run_blocking(Command::new(program).args(args))?;
+    let kind = match output.status.code() {
+        Some(0) => return Ok(()),
+        Some(1) => ErrorKind::PermissionDenied,
+        Some(6) => ErrorKind::NotFound,
+        Some(9) => ErrorKind::AlreadyExists,
+        _ => ErrorKind::Other,
+    };
+
+    Err(io::Error::new(
+        kind,
+        format!("{program} failed: {}", String::from_utf8_lossy(&output.stderr).trim()),
+    ))
+}
+
+impl Default for EtcPasswd {
+    fn default() -> Self {
+        Self::new(PathBuf::from("/etc/passwd"), PathBuf::from("/etc/group"))
+    }
+}
+
+impl Passwd for EtcPasswd {
+    fn user_id(&self, name: &str) -> io::Result<Option<u32>> {
+        Ok(Self::entries(&self.passwd_path)?
+            .into_iter()
+            .find(|(user, _)| user == name)
+            .map(|(_, uid)| uid))
+    }
+
+    fn group_id(&self, name: &str) -> io::Result<Option<u32>> {
+        Ok(Self::entries(&self.group_path)?
+            .into_iter()
+            .find(|(group, _)| group == name)
+            .map(|(_, gid)| gid))
+    }
+
+    fn user_name(&self, uid: u32) -> io::Result<Option<String>> {
+        Ok(Self::entries(&self.passwd_path)?
+            .into_iter()
+            .find(|(_, id)| *id == uid)
+            .map(|(user, _)| user))
+    }
+
+    fn group_name(&self, gid: u32) -> io::Result<Option<String>> {
+        Ok(Self::entries(&self.group_path)?
+            .into_iter()
+            .find(|(_, id)| *id == gid)
+            .map(|(group, _)| group))
+    }
+
+    fn user_groups(&self, name: &str) -> io::Result<Vec<String>> {
+        let contents = fs::read_to_string(&self.group_path)?;
+
+        // Members are the fourth field, separated by commas.
+        Ok(contents
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .filter_map(|line| {
+                let mut fields = line.split(':');
+                let group = fields.next()?;
+                let members = fields.nth(2)?;
+                members.split(',').any(|member| member == name).then(|| group.to_owned())
+            })
+            .collect())
+    }
+
+    fn create_account(&self, name: &str, groups: &[&'static str]) -> io::Result<()> {
+        if self.user_id(name)?.is_some() || self.group_id(name)?.is_some() {
+            return Err(io::Error::new(ErrorKind::AlreadyExists, format!("account '{name}' already exists")));
+        }
+
+        let groups = groups.join(",");
+        let mut args = vec!["--system", "--user-group", "--no-create-home", "--home-dir", "/nonexistent", "--shell", "/usr/sbin/nologin"];
+        if !groups.is_empty() {
+            args.extend(["--groups", &groups]);
+        }
+        args.push(name);
+        self.run("useradd", &args)
+    }
+
+    fn add_to_group(&self, name: &str, group: &'static str) -> io::Result<()> {
+        self.run("usermod", &["--append", "--groups", group, name])
+    }
+
+    fn delete_account(&self, name: &str) -> io::Result<()> {
+        self.run("userdel", &[name])?;
+
+        // userdel leaves the group behind when USERGROUPS_ENAB is off.
+        match self.run("groupdel", &[name]) {
+            Err(err) if err.kind() != ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_accounts_looked_up() {
+        let path = PathBuf::from("/tmp/provisiond_tests/test_accounts_looked_up");
+        fs::create_dir_all(&path).expect("Failed to create test path");
+        fs::write(
+            path.join("passwd"),
+            "root:x:0:0:root:/root:/bin/sh\nserver-daemon:x:998:997::/nonexistent:/usr/sbin/nologin\n",
+        )
+        .expect("Failed to write passwd");
+        fs::write(path.join("group"), "# comment\nroot:x:0:\nserver-daemon:x:997:\ndocker:x:996:root,server-daemon\n")
+            .expect("Failed to write group");
+
+        let passwd = EtcPasswd::new(path.join("passwd"), path.join("group"));
+
+        assert_eq!(passwd.user_id("server-daemon").unwrap(), Some(998));
+        assert_eq!(passwd.group_id("server-daemon").unwrap(), Some(997));
+        assert_eq!(passwd.user_name(0).unwrap().as_deref(), Some("root"));
+        assert_eq!(passwd.group_name(997).unwrap().as_deref(), Some("server-daemon"));
+        assert_eq!(passwd.user_id("nobody").unwrap(), None);
+        assert_eq!(passwd.user_groups("server-daemon").unwrap(), ["docker"]);
+
+        fs::remove_dir_all(&path)
+            .unwrap_or_else(|_| panic!("Failed to delete test path at {}", path.display()));
+    }
+}
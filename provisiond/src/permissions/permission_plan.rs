@@ -0,0 +1,182 @@
+use std::fs::{self, Permissions};
+use std::io::{self, ErrorKind};
+use std::iter::once;
+use std::os::unix::fs::{MetadataExt, PermissionsExt, lchown};
+use std::path::{Path, PathBuf};
+
+use crate::executors::ServiceFile;
+
+/// Only the service user needs into the folder, it holds the database password.
+pub const FOLDER_MODE: u32 = 0o700;
+
+/// Owns the docker socket. Units run `docker compose` as the service's user,
+/// which has to be in it.
+pub const DOCKER_GROUP: &str = "docker";
+
+/// Names of the user and group services run as.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ServiceOwner {
+    pub user: String,
+    pub group: String,
+}
+
+impl Default for ServiceOwner {
+    fn default() -> Self {
+        Self {
+            user: "server-daemon".to_owned(),
+            group: "server-daemon".to_owned(),
+        }
+    }
+}
+
+impl ServiceOwner {
+    /// The account a service gets when it asks for a user of its own.
+    pub fn dedicated(service_name: &str) -> Self {
+        let name = format!("svc-{service_name}");
+        Self {
+            user: name.clone(),
+            group: name,
+        }
+    }
+}
+
+/// A `ServiceOwner` resolved to ids.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Owner {
+    pub uid: u32,
+    pub gid: u32,
+}
+
+/// How one path of a service compares to the plan.
+#[derive(Debug, PartialEq)]
+pub struct FileCheck {
+    pub path: PathBuf,
+    pub expected_mode: u32,
+    /// `None` if the path is missing.
+    pub mode: Option<u32>,
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+}
+
+impl FileCheck {
+    /// `owner` is `None` when the configured user or group does not exist,
+    /// nothing can match then.
+    pub fn ok(&self, owner: Option<Owner>) -> bool {
+        self.mode == Some(self.expected_mode)
+            && owner.is_some_and(|owner| self.uid == Some(owner.uid) && self.gid == Some(owner.gid))
+    }
+}
+
+fn mode(file: ServiceFile) -> u32 {
+    match file {
+        ServiceFile::Env | ServiceFile::Definition => 0o600,
+        ServiceFile::Compose | ServiceFile::Unit => 0o644,
+    }
+}
+
+/// Every path of the service in `folder` with the mode it should have.
+fn plan(folder: &Path, service_name: &str) -> Vec<(PathBuf, u32)> {
+    once((folder.to_path_buf(), FOLDER_MODE))
+        .chain(
+            ServiceFile::ALL
+                .iter()
+                .map(|file| (folder.join(file.file_name(service_name)), mode(*file))),
+        )
+        .collect()
+}
+
+/// Gives the service's folder and files to `owner` with the planned modes.
+/// Symlinks are refused rather than followed, the folder belongs to the
+/// service user who could otherwise point them anywhere.
+pub fn apply(folder: &Path, service_name: &str, owner: Owner) -> io::Result<()> {
+    for (path, mode) in plan(folder, service_name) {
+        if fs::symlink_metadata(&path)?.file_type().is_symlink() {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!("refusing to change permissions through the symlink at {}", path.display()),
+            ));
+        }
+
+        lchown(&path, Some(owner.uid), Some(owner.gid))?;
+        fs::set_permissions(&path, Permissions::from_mode(mode))?;
+    }
+
+    Ok(())
+}
+
+pub fn check(folder: &Path, service_name: &str) -> Vec<FileCheck> {
+    plan(folder, service_name)
+        .into_iter()
+        .map(|(path, expected_mode)| {
+            let metadata = fs::symlink_metadata(&path).ok();
+            FileCheck {
+                mode: metadata.as_ref().map(|m| m.mode() & 0o7777),
+                uid: metadata.as_ref().map(MetadataExt::uid),
+                gid: metadata.as_ref().map(MetadataExt::gid),
+                path,
+                expected_mode,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_root_path(test_name: &str) -> PathBuf {
+        let path = PathBuf::from(format!("/tmp/provisiond_tests/{}", test_name));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).expect("Failed to create test path");
+        path
+    }
+
+    fn current_owner(path: &Path) -> Owner {
+        let metadata = fs::metadata(path).unwrap();
+        Owner {
+            uid: metadata.uid(),
+            gid: metadata.gid(),
+        }
+    }
+
+    #[test]
+    pub fn test_plan_applied_and_checked() {
+        let path = get_root_path("test_plan_applied_and_checked");
+        for file in ServiceFile::ALL {
+            fs::write(path.join(file.file_name("test_service")), "").expect("Failed to write file");
+        }
+        fs::set_permissions(path.join(".env"), Permissions::from_mode(0o644)).unwrap();
+
+        let owner = current_owner(&path);
+        let env_check = |checks: Vec<FileCheck>| checks.into_iter().find(|c| c.path.ends_with(".env")).unwrap();
+
+        assert!(!env_check(check(&path, "test_service")).ok(Some(owner)), "World readable env should be reported");
+
+        apply(&path, "test_service", owner).expect("Failed to apply permissions");
+
+        let checks = check(&path, "test_service");
+        assert!(checks.iter().all(|c| c.ok(Some(owner))), "Every path should match the plan: {checks:?}");
+        assert_eq!(env_check(checks).mode, Some(0o600));
+        assert!(!check(&path, "test_service")[0].ok(None), "Nothing matches an owner that does not exist");
+
+        fs::remove_dir_all(&path)
+            .unwrap_or_else(|_| panic!("Failed to delete test path at {}", path.display()));
+    }
+
+    #[test]
+    pub fn test_symlinks_refused() {
+        let path = get_root_path("test_symlinks_refused");
+        for file in ServiceFile::ALL {
+            fs::write(path.join(file.file_name("test_service")), "").expect("Failed to write file");
+        }
+        fs::remove_file(path.join(".env")).unwrap();
+        std::os::unix::fs::symlink("/etc/hostname", path.join(".env")).unwrap();
+
+        let err = apply(&path, "test_service", current_owner(&path)).expect_err("Symlink should be refused");
+
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+        fs::remove_dir_all(&path)
+            .unwrap_or_else(|_| panic!("Failed to delete test path at {}", path.display()));
+    }
+}
@@ -0,0 +1,3 @@
+mod port_pool;
+
+pub use port_pool::{DEFAULT_ADDRESS, DEFAULT_RANGE, PortPool, PortRange};
@@ -0,0 +1,204 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, TcpListener};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Mutex;
+
+use crate::io::{Backup, write_atomic};
+
+/// Host ports handed out unless `PROVISIOND_PORT_POOL` says otherwise.
+pub const DEFAULT_RANGE: PortRange = PortRange { first: 20000, last: 20999 };
+
+/// Where ports are published unless `PROVISIOND_PORT_ADDRESS` says
+/// otherwise, so services are only reachable from the host itself.
+pub const DEFAULT_ADDRESS: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+/// An inclusive range of host ports, written as `20000-20999`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PortRange {
+    pub first: u16,
+    pub last: u16,
+}
+
+impl FromStr for PortRange {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid port range '{value}', expected <first>-<last> such as 20000-20999");
+        let (first, last) = value.split_once('-').ok_or_else(invalid)?;
+        let (first, last) = (first.parse().map_err(|_| invalid())?, last.parse().map_err(|_| invalid())?);
+        match 0 < first && first <= last {
+            true => Ok(Self { first, last }),
+            false => Err(invalid()),
+        }
+    }
+}
+
+impl fmt::Display for PortRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.first, self.last)
+    }
+}
+
+/// Service name to the host port of each of its blueprint's ports.
+type Assignments = BTreeMap<String, BTreeMap<String, u16>>;
+
+/// Hands out host ports to services from a range, so no two services bind
+/// the same one. Assignments are written to `state_path` before they are
+/// used, a port is only given out again once its service is deleted.
+pub struct PortPool {
+    range: PortRange,
+    /// The host address the ports are published on.
+    address: IpAddr,
+    state_path: Option<PathBuf>,
+    assignments: Mutex<Assignments>,
+    /// Whether something outside the pool already listens on a port.
+    in_use: fn(u16) -> bool,
+}
+
+impl Default for PortPool {
+    fn default() -> Self {
+        Self {
+            range: DEFAULT_RANGE,
+            address: DEFAULT_ADDRESS,
+            state_path: None,
+            assignments: Mutex::new(BTreeMap::new()),
+            in_use: bound_on_host,
+        }
+    }
+}
+
+impl PortPool {
+    pub fn load(range: PortRange, state_path: PathBuf) -> io::Result<Self> {
+        let assignments = match fs::read_to_string(&state_path) {
+            Ok(json) => serde_json::from_str(&json).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?,
+            Err(e) if e.kind() == ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e),
+        };
+
+        Ok(Self {
+            range,
+            address: DEFAULT_ADDRESS,
+            state_path: Some(state_path),
+            assignments: Mutex::new(assignments),
+            in_use: bound_on_host,
+        })
+    }
+
+    pub fn with_address(mut self, address: IpAddr) -> Self {
+        self.address = address;
+        self
+    }
+
+    pub fn address(&self) -> IpAddr {
+        self.address
+    }
+
+    /// The ports a service holds.
+    pub fn ports(&self, service_name: &str) -> BTreeMap<String, u16> {
+        self.assignments.lock().unwrap().get(service_name).cloned().unwrap_or_default()
+    }
+
+    /// A host port for each of `names`, keeping the ones the service already
+    /// holds. New ports are the lowest in the range no service holds and
+    /// nothing on the host listens on, `AddrNotAvailable` if there is none.
+    pub fn allocate(&self, service_name: &str, names: &[&str]) -> io::Result<BTreeMap<String, u16>> {
+        let mut assignments = self.assignments.lock().unwrap();
+        let held = assignments.get(service_name).cloned().unwrap_or_default();
+        if names.iter().all(|name| held.contains_key(*name)) {
+            return Ok(held);
+        }
+
+        let mut ports = held;
+        let mut candidates = self.range.first..=self.range.last;
+        let missing: Vec<&str> = names.iter().copied().filter(|name| !ports.contains_key(*name)).collect();
+        for name in missing {
+            let taken = |port: u16| assignments.values().chain([&ports]).any(|held| held.values().any(|p| *p == port));
+            let port = candidates
+                .find(|port| !taken(*port) && !(self.in_use)(*port))
+                .ok_or_else(|| {
+                    io::Error::new(ErrorKind::AddrNotAvailable, format!("no free host port left in {range}", range = self.range))
+                })?;
+            ports.insert(name.to_string(), port);
+        }
+
+        let mut updated = assignments.clone();
+        updated.insert(service_name.to_owned(), ports.clone());
+        self.persist(&updated)?;
+        *assignments = updated;
+        Ok(ports)
+    }
+
+    /// Gives a service's ports back to the pool.
+    pub fn release(&self, service_name: &str) -> io::Result<()> {
+        let mut assignments = self.assignments.lock().unwrap();
+        if !assignments.contains_key(service_name) {
+            return Ok(());
+        }
+
+        let mut updated = assignments.clone();
+        updated.remove(service_name);
+        self.persist(&updated)?;
+        *assignments = updated;
+        Ok(())
+    }
+
+    /// Services holding ports, for releasing the ones left by services that
+    /// are gone.
+    pub fn service_names(&self) -> Vec<String> {
+        self.assignments.lock().unwrap().keys().cloned().collect()
+    }
+
+    fn persist(&self, assignments: &Assignments) -> io::Result<()> {
+        let Some(path) = &self.state_path else {
+            return Ok(());
+        };
+        let json = serde_json::to_string_pretty(assignments).expect("port assignments are always serializable");
+        write_atomic(path, json.as_bytes(), Backup::Previous)
+    }
+}
+
+/// Whether the port is taken on the host, by trying to bind it on every
+/// address as docker would. A host without IPv6 only checks IPv4.
+fn bound_on_host(port: u16) -> bool {
+    let taken = |address: IpAddr| matches!(TcpListener::bind((address, port)), Err(e) if e.kind() == ErrorKind::AddrInUse);
+    taken(Ipv4Addr::UNSPECIFIED.into()) || taken(Ipv6Addr::UNSPECIFIED.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_ports_allocated_persisted_and_released() {
+        let path = PathBuf::from("/tmp/provisiond_tests/test_ports_allocated_persisted_and_released");
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        let range: PortRange = "20000-20002".parse().unwrap();
+
+        let pool = PortPool {
+            in_use: |port| port == 20000,
+            ..PortPool::load(range, path.join("ports.json")).unwrap()
+        };
+        let allocate = |pool: &PortPool, service_name| pool.allocate(service_name, &["postgres"]).map_err(|e| e.to_string());
+        let postgres = |port| Ok(BTreeMap::from([("postgres".to_owned(), port)]));
+        assert_eq!(allocate(&pool, "first"), postgres(20001));
+        assert_eq!(allocate(&pool, "first"), postgres(20001));
+        assert_eq!(allocate(&pool, "second"), postgres(20002));
+        assert_eq!(allocate(&pool, "third"), Err("no free host port left in 20000-20002".to_owned()));
+
+        let pool = PortPool {
+            in_use: |_| false,
+            ..PortPool::load(range, path.join("ports.json")).unwrap()
+        };
+        assert_eq!(pool.ports("second").get("postgres"), Some(&20002), "Assignments should survive a restart");
+        pool.release("first").unwrap();
+        assert_eq!(allocate(&pool, "third"), postgres(20000));
+        assert_eq!(allocate(&pool, "fourth"), postgres(20001));
+
+        fs::remove_dir_all(&path).unwrap();
+    }
+}
@@ -1,49 +1,146 @@
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, VecDeque};
+use std::pin::Pin;
 use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use log::info;
-use tonic::{Code, Request, Response, Status};
+use futures_util::{Stream, StreamExt, TryStreamExt, stream};
+use tracing::field::Empty;
+use tracing::{info, info_span, instrument, warn};
+use tokio::io::AsyncWriteExt;
+use tonic::{Code, Request, Response, Status, Streaming};
+use tonic::codegen::Bytes;
+use tonic::metadata::MetadataMap;
 
 use libprovision::hello_world::{
-    CreateRequest, CreateResponse, DeleteRequest, DeleteResponse, Provisioner, PullRequest,
-    PullResponse, RestartRequest, RestartResponse,
+    ApplyRequest, ApplyResponse, BackupRequest, BackupResponse, CreateRequest, CreateResponse, DeleteRequest, DeleteResponse,
+    DescribeRequest, ExecInput, ExecOutput, ExecStart, FilePermission, GetHealthRequest, GetHealthResponse, GetOperationRequest, HealthCheckResult, HealthStatus, ListBackupsRequest, ListBackupsResponse, ListRequest, ListResponse,
+    ListSchedulesRequest, ListSchedulesResponse, LogLine, LogsRequest, Operation, OperationStatus, Provisioner, PullRequest,
+    PublishedPort, PullResponse, ResourceLimits, RestartRequest, RestartResponse, RestoreRequest, RestoreResponse, Schedule, ScheduledJob,
+    ServiceChange, ServiceDescription, ServiceSummary,
+    UpdateServiceRequest, UpdateServiceResponse, exec_input, exec_output,
 };
+use libprovision::dependencies;
 
-use crate::executors::{CreateExecutorError, DeleteExecutorError, RealCreateExecutor};
-use crate::executors::RealDeleteExecutor;
-use crate::executors::{CreateExecutor, DeleteExecutor};
+use crate::access::{AuditEntry, AuditLog, Policy};
+use crate::backups::{BackupMetadata, BackupStore};
+use crate::executors::{BackupErrorType, BackupExecutor, BackupExecutorError, RealBackupExecutor};
+use crate::executors::{CreateErrorType, CreateExecutorError, DeleteErrorType, DeleteExecutorError, RealCreateExecutor};
+use crate::executors::{RealDeleteExecutor, RealUnitExecutor, ServiceFile};
+use crate::executors::{CreateExecutor, DeleteExecutor, ErrorReason, ImageExecutor, RealImageExecutor, UnitExecutor};
+use crate::executors::{HealthExecutor, HealthExecutorError, RealHealthExecutor};
+use crate::executors::{ExecExecutor, ExecExecutorError, ExecSession, RealExecExecutor};
+use crate::executors::{LogExecutor, LogExecutorError, LogOptions, RealLogExecutor};
+use crate::executors::{ImageExecutorError, UnitErrorType, UnitExecutorError};
+use crate::executors::{RealVolumeExecutor, VolumeExecutor, VolumeExecutorError};
+use crate::health::HealthHistory;
+use crate::io::{Backup, FileManager, RealFileManager};
+use crate::metrics::{Metrics, TextFormat};
+use crate::permissions::{self, DOCKER_GROUP, EtcPasswd, FileCheck, Owner, Passwd, ServiceOwner};
+use crate::ports::PortPool;
+use crate::schedules::{Job, JobKind, LastRun, ScheduleStore};
+use crate::services::{self, Action, Blueprint, DumpCommands, PlannedChange, ServiceDefinition, changed_fields};
+use crate::state::{BeginOutcome, InterruptedOperation, OperationJournal, OperationStore};
+use crate::telemetry;
+
+type UndoFn = Box<dyn FnOnce(String) + Send>;
+type UndoStack = VecDeque<UndoFn>;
+type StepFn<'a> = &'a dyn Fn(String) -> Result<(), CreateExecutorError>;
+type DeleteStepFn = fn(&(dyn DeleteExecutor + Send + Sync), String) -> Result<(), DeleteExecutorError>;
+type UnitStepFn = fn(&(dyn UnitExecutor + Send + Sync), String) -> Result<(), UnitExecutorError>;
+
+/// How a restarted unit is watched before an update is kept.
+pub struct HealthCheck {
+    pub interval: Duration,
+    /// Consecutive active checks needed for the unit to count as healthy.
+    pub required_checks: u32,
+    pub timeout: Duration,
+}
+
+impl Default for HealthCheck {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(1),
+            required_checks: 3,
+            timeout: Duration::from_secs(30),
+        }
+    }
+}
 
 pub struct ProvisionerImpl {
     create_executor: Arc<dyn CreateExecutor + Send + Sync>,
     delete_executor: Arc<dyn DeleteExecutor + Send + Sync>,
+    unit_executor: Arc<dyn UnitExecutor + Send + Sync>,
+    backup_executor: Arc<dyn BackupExecutor + Send + Sync>,
+    image_executor: Arc<dyn ImageExecutor + Send + Sync>,
+    health_executor: Arc<dyn HealthExecutor + Send + Sync>,
+    log_executor: Arc<dyn LogExecutor + Send + Sync>,
+    exec_executor: Arc<dyn ExecExecutor + Send + Sync>,
+    volume_executor: Arc<dyn VolumeExecutor + Send + Sync>,
+    file_manager: Arc<dyn FileManager + Send + Sync>,
+    passwd: Arc<dyn Passwd + Send + Sync>,
+    operations: Arc<OperationStore>,
+    health_check: HealthCheck,
+    service_owner: ServiceOwner,
+    backups: BackupStore,
+    schedules: ScheduleStore,
+    health: HealthHistory,
+    /// Unhealthy checks in a row before a monitored service is restarted,
+    /// 0 never restarts.
+    restart_unhealthy: u32,
+    /// Who may exec into which services.
+    policy: Arc<Policy>,
+    audit: Arc<AuditLog>,
+    metrics: Arc<Metrics>,
+    ports: Arc<PortPool>,
+    /// Limits every service's limits together have to stay within.
+    quota: services::ResourceLimits,
+}
+
+/// Checks the definition against its blueprint, returning the blueprint so
+/// callers can render from it.
+fn validate(definition: &ServiceDefinition) -> Result<&'static Blueprint, Status> {
+    Blueprint::find(&definition.blueprint)
+        .and_then(|blueprint| blueprint.validate(definition).map(|_| blueprint))
+        .map_err(|e| Status::new(Code::InvalidArgument, e))
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+/// The files a definition produces, in the order the steps write them.
+fn render(blueprint: &Blueprint, definition: &ServiceDefinition, owner: &ServiceOwner, ports: &PortPool) -> [String; 4] {
+    let published = ports.ports(&definition.name);
+    [
+        blueprint.render_compose(definition, &published, ports.address()),
+        blueprint.render_env(definition, &published),
+        blueprint.render_unit(definition, owner),
+        definition.to_json(),
+    ]
 }
 
 impl ProvisionerImpl {
-    pub(crate) fn unwind(
-        &self,
-        service_name: String,
-        queue: VecDeque<Box<dyn FnOnce(String) -> () + Send>>,
-    ) -> Result<(), Status> {
-        
+    pub(crate) fn unwind(&self, service_name: String, queue: UndoStack) {
         info!("Unwinding {service_name} total steps {step_count}", service_name = service_name, step_count = queue.len());
-        
+        self.metrics.rolled_back();
+
         for f in queue.into_iter().rev() {
             f(service_name.clone());
         }
-
-        Ok(())
     }
 
     fn run_step(
         &self,
+        operation_id: &str,
         service_name: &String,
         step_name: &'static str,
-        undo_stack: &mut VecDeque<Box<dyn FnOnce(String) + Send>>,
-        step_fn: &dyn Fn(String) -> Result<(), CreateExecutorError>,
-        inverse_fn: &dyn Fn(String) -> Result<(), DeleteExecutorError>,
-    ) -> Option<Result<Response<CreateResponse>, Status>> {
+        undo_stack: &mut UndoStack,
+        step_fn: StepFn,
+        undo_fn: UndoFn,
+    ) -> Result<(), Status> {
 
         info!("Running step '{step_name}' for: {service_name}", step_name = step_name, service_name = service_name);
+        self.operations.step_started(operation_id, service_name, step_name)?;
 
         let name = service_name.clone();
         let step_res = step_fn(name);
@@ -53,121 +150,2975 @@ impl ProvisionerImpl {
                 "Step '{step_name}' failed unwinding stack for {service_name} got error {err}",
                 step_name=  step_name,
                 service_name = service_name,
-                err = e.to_string()
+                err = e
             );
-            return Some(Err(Status::new(
-                Code::Internal,
-                format!("Error Running step '{step_name}' for {service_name} got error {err}", step_name = step_name, service_name = service_name,err = e.to_string()),
-
-            )));
+            self.operations.step_finished(operation_id, service_name, step_name, Some(e.to_string()));
+            return Err(e.to_status(service_name, step_name));
         }
 
-        info!("Appending undo folder for: {}", service_name);
+        self.operations.step_finished(operation_id, service_name, step_name, None);
+
+        info!("Appending undo for step '{step_name}' for: {service_name}", step_name = step_name, service_name = service_name);
+        undo_stack.push_back(undo_fn);
 
+        Ok(())
+    }
+
+    /// Undoes a create step by deleting what it made.
+    fn undo_delete(&self, step_name: &'static str, inverse_fn: DeleteStepFn) -> UndoFn {
         let delete_executor = self.delete_executor.clone();
-        undo_stack.push_back(Box::new(move |name| {
-            info!("Deleting folder for: {}", name);
-            delete_executor.delete_folder(name).unwrap();
-        }));
+        Box::new(move |name| {
+            let _span = info_span!("compensation", step = step_name, service_name = %name).entered();
+            info!("Undoing step '{step_name}' for: {name}", step_name = step_name, name = name);
+            if let Err(e) = inverse_fn(delete_executor.as_ref(), name) {
+                info!("Undoing step '{step_name}' failed with error {err}", step_name = step_name, err = e);
+            }
+        })
+    }
 
-        None
+    /// Undoes the user step by removing the account again.
+    fn undo_account(&self, step_name: &'static str) -> UndoFn {
+        let passwd = self.passwd.clone();
+        Box::new(move |name| {
+            let _span = info_span!("compensation", step = step_name, service_name = %name).entered();
+            info!("Undoing step '{step_name}' for: {name}", step_name = step_name, name = name);
+            if let Err(e) = passwd.delete_account(&ServiceOwner::dedicated(&name).user) {
+                info!("Undoing step '{step_name}' failed with error {err}", step_name = step_name, err = e);
+            }
+        })
     }
-}
 
-impl Default for ProvisionerImpl {
-    fn default() -> Self {
-        Self {
-            create_executor: Arc::new(RealCreateExecutor::default()),
-            delete_executor: Arc::new(RealDeleteExecutor::default()),
+    /// Undoes the link step so systemd forgets the unit again.
+    fn undo_link(&self, step_name: &'static str) -> UndoFn {
+        let unit_executor = self.unit_executor.clone();
+        Box::new(move |name| {
+            let _span = info_span!("compensation", step = step_name, service_name = %name).entered();
+            info!("Undoing step '{step_name}' for: {name}", step_name = step_name, name = name);
+            if let Err(e) = unit_executor.unlink_unit(name) {
+                info!("Undoing step '{step_name}' failed with error {err}", step_name = step_name, err = e);
+            }
+        })
+    }
+
+    /// Undoes a port allocation by giving the service's ports back.
+    fn undo_ports(&self, step_name: &'static str) -> UndoFn {
+        let ports = self.ports.clone();
+        Box::new(move |name| {
+            let _span = info_span!("compensation", step = step_name, service_name = %name).entered();
+            info!("Undoing step '{step_name}' for: {name}", step_name = step_name, name = name);
+            if let Err(e) = ports.release(&name) {
+                info!("Undoing step '{step_name}' failed with error {err}", step_name = step_name, err = e);
+            }
+        })
+    }
+
+    /// Host ports for the service, keeping any it already holds.
+    fn allocate_ports(&self, blueprint: &Blueprint, service_name: &str) -> Result<BTreeMap<String, u16>, CreateExecutorError> {
+        self.ports.allocate(service_name, &blueprint.port_names()).map_err(|e| {
+            let kind = match e.kind() {
+                std::io::ErrorKind::AddrNotAvailable => CreateErrorType::PortsExhausted,
+                _ => CreateErrorType::OtherIO,
+            };
+            CreateExecutorError::new(kind, e.to_string())
+        })
+    }
+
+    /// Undoes a replace step by putting the backed up version back.
+    fn undo_replace(&self, step_name: &'static str, file: ServiceFile) -> UndoFn {
+        let create_executor = self.create_executor.clone();
+        Box::new(move |name| {
+            let _span = info_span!("compensation", step = step_name, service_name = %name).entered();
+            info!("Undoing step '{step_name}' for: {name}", step_name = step_name, name = name);
+            if let Err(e) = create_executor.restore_file(name, file) {
+                info!("Undoing step '{step_name}' failed with error {err}", step_name = step_name, err = e);
+            }
+        })
+    }
+
+    fn create_service(&self, operation_id: &str, definition: &ServiceDefinition) -> Result<(), Status> {
+        let service_name = &definition.name;
+        let service_owner = self.owner_for(definition);
+        let blueprint = validate(definition)?;
+        let definition = &blueprint.with_secrets(definition, None);
+
+        // Allocating hands back the ports an existing service holds, undoing
+        // it when Create Folder then fails would take them from that service.
+        if self.file_manager.service_folder_exists(service_name.clone()) {
+            let error = CreateExecutorError::new(
+                CreateErrorType::FolderExists,
+                format!("service '{service_name}' already exists"),
+            );
+            return Err(error.to_status(service_name, "Create Folder"));
+        }
+
+        info!("Creating undo queue for: {}", service_name);
+        let mut undo_stack: UndoStack = VecDeque::new();
+
+        let step_fn: StepFn = &|n| self.allocate_ports(blueprint, &n).map(|_| ());
+        let undo_fn = self.undo_ports("Allocate Ports");
+        self.run_step(operation_id, service_name, "Allocate Ports", &mut undo_stack, step_fn, undo_fn)?;
+        let [compose, env, unit, json] = render(blueprint, definition, &service_owner, &self.ports);
+
+        if definition.dedicated_user {
+            let step_fn: StepFn = &|_| {
+                self.passwd.create_account(&service_owner.user, &[DOCKER_GROUP]).map_err(|e| {
+                    let kind = match e.kind() {
+                        std::io::ErrorKind::AlreadyExists => CreateErrorType::UserExists,
+                        _ => CreateErrorType::UserCreateFailed,
+                    };
+                    CreateExecutorError::new(kind, e.to_string())
+                })
+            };
+            let undo_fn = self.undo_account("Create Service User");
+            if let Err(status) = self.run_step(operation_id, service_name, "Create Service User", &mut undo_stack, step_fn, undo_fn) {
+                self.unwind(service_name.clone(), undo_stack);
+                return Err(status);
+            }
         }
+
+        let owner = match self.resolve_owner(&service_owner) {
+            Ok(owner) => owner,
+            Err(status) => {
+                self.unwind(service_name.clone(), undo_stack);
+                return Err(status);
+            }
+        };
+
+        let steps: [(&'static str, StepFn, DeleteStepFn); 6] = [
+            ("Create Folder", &|n| self.create_executor.create_folder(n), |d, n| d.delete_folder(n)),
+            ("Create Compose File", &|n| self.create_executor.create_compose_file(n, &compose), |d, n| d.delete_compose_file(n)),
+            ("Create Env File", &|n| self.create_executor.create_env_file(n, &env), |d, n| d.delete_env_file(n)),
+            ("Create Unit File", &|n| self.create_executor.create_systemd_unit(n, &unit), |d, n| d.delete_systemd_unit(n)),
+            ("Create Definition File", &|n| self.create_executor.create_definition_file(n, &json), |d, n| d.delete_definition_file(n)),
+            // Nothing to undo, the folder is removed by the first step's undo.
+            ("Set Permissions", &|n| self.create_executor.set_permissions(n, owner), |_, _| Ok(())),
+        ];
+
+        for (step_name, step_fn, inverse_fn) in steps {
+            let undo_fn = self.undo_delete(step_name, inverse_fn);
+            if let Err(status) = self.run_step(operation_id, service_name, step_name, &mut undo_stack, step_fn, undo_fn) {
+                self.unwind(service_name.clone(), undo_stack);
+                return Err(status);
+            }
+
+            // systemd only finds the unit once it is linked, restarts and
+            // updates rely on that.
+            if step_name == "Create Unit File" {
+                if let Err(status) = self.unit_step(operation_id, service_name, "Link Unit", |u, n| u.link_unit(n)) {
+                    self.unwind(service_name.clone(), undo_stack);
+                    return Err(status);
+                }
+                undo_stack.push_back(self.undo_link("Link Unit"));
+            }
+        }
+
+        info!("Created service folder for: {}", service_name);
+        Ok(())
     }
-}
 
-#[tonic::async_trait]
-impl Provisioner for ProvisionerImpl {
-    async fn create(
+    /// Replaces the generated files of an existing service, returning the
+    /// undo stack that puts the previous versions back.
+    fn write_service_files(&self, operation_id: &str, definition: &ServiceDefinition) -> Result<UndoStack, Status> {
+        let service_name = &definition.name;
+        let service_owner = self.owner_for(definition);
+        let blueprint = validate(definition)?;
+        let definition = &blueprint.with_secrets(definition, Some(&self.read_definition(service_name)?));
+        let owner = self.resolve_owner(&service_owner)?;
+
+        let mut undo_stack: UndoStack = VecDeque::new();
+
+        // Services from before the pool get their ports on the next update.
+        // The undo gives back every port, so a service that already holds
+        // some never runs the step.
+        if self.ports.ports(service_name).is_empty() && !blueprint.port_names().is_empty() {
+            let step_fn: StepFn = &|n| self.allocate_ports(blueprint, &n).map(|_| ());
+            let undo_fn = self.undo_ports("Allocate Ports");
+            self.run_step(operation_id, service_name, "Allocate Ports", &mut undo_stack, step_fn, undo_fn)?;
+        }
+        let rendered = render(blueprint, definition, &service_owner, &self.ports);
+
+        let steps: [(&'static str, ServiceFile); 4] = [
+            ("Write Compose File", ServiceFile::Compose),
+            ("Write Env File", ServiceFile::Env),
+            ("Write Unit File", ServiceFile::Unit),
+            ("Write Definition File", ServiceFile::Definition),
+        ];
+
+        for ((step_name, file), contents) in steps.into_iter().zip(rendered) {
+            // Taken before the step is journaled, so recovering a step the
+            // daemon died in never restores a backup older than this update.
+            if let Err(e) = self.create_executor.back_up_file(service_name.clone(), file) {
+                self.unwind(service_name.clone(), undo_stack);
+                return Err(e.to_status(service_name, step_name));
+            }
+            let step_fn: StepFn = &|n| self.create_executor.replace_file(n, file, &contents);
+            let undo_fn = self.undo_replace(step_name, file);
+            if let Err(status) = self.run_step(operation_id, service_name, step_name, &mut undo_stack, step_fn, undo_fn) {
+                self.unwind(service_name.clone(), undo_stack);
+                return Err(status);
+            }
+        }
+
+        // Replaced files are owned by the daemon until this runs.
+        let step_fn: StepFn = &|n| self.create_executor.set_permissions(n, owner);
+        if let Err(status) = self.run_step(operation_id, service_name, "Set Permissions", &mut undo_stack, step_fn, Box::new(|_| ())) {
+            self.unwind(service_name.clone(), undo_stack);
+            return Err(status);
+        }
+
+        Ok(undo_stack)
+    }
+
+    /// Rewrites the service's files and restarts it. If the unit does not come
+    /// back healthy the previous files are restored and it is restarted again.
+    async fn update_service(
         &self,
-        request: Request<CreateRequest>,
-    ) -> Result<Response<CreateResponse>, Status> {
-        let service_name = request.get_ref().clone().service_name;
-        info!(
-            "Got create request to make service with name: {}",
-            service_name
+        operation_id: &str,
+        definition: &ServiceDefinition,
+        restart: bool,
+    ) -> Result<(), Status> {
+        let service_name = &definition.name;
+        let undo_stack = self.write_service_files(operation_id, definition)?;
+
+        if !restart {
+            info!("Updated service files for {service_name} without restarting", service_name = service_name);
+            return Ok(());
+        }
+
+        let Err(status) = self.restart_unit(operation_id, service_name).await else {
+            info!("Updated service: {}", service_name);
+            return Ok(());
+        };
+
+        info!("Rolling back update of {service_name}: {err}", service_name = service_name, err = status.message());
+        self.unwind(service_name.clone(), undo_stack);
+
+        if let Err(e) = self.restart_unit(operation_id, service_name).await {
+            info!("Restarting {service_name} after rollback failed: {err}", service_name = service_name, err = e.message());
+        }
+
+        Err(Status::with_details(
+            status.code(),
+            format!("{message}, the previous configuration was restored", message = status.message()),
+            Bytes::copy_from_slice(status.details()),
+        ))
+    }
+
+    fn unit_step(
+        &self,
+        operation_id: &str,
+        service_name: &String,
+        step_name: &'static str,
+        step_fn: UnitStepFn,
+    ) -> Result<(), Status> {
+        info!("Running step '{step_name}' for: {service_name}", step_name = step_name, service_name = service_name);
+        self.operations.step_started(operation_id, service_name, step_name)?;
+
+        let result = step_fn(self.unit_executor.as_ref(), service_name.clone());
+
+        self.operations.step_finished(operation_id, service_name, step_name, result.as_ref().err().map(|e| e.to_string()));
+        result.map_err(|e| e.to_status(service_name, step_name))
+    }
+
+    async fn restart_unit(&self, operation_id: &str, service_name: &String) -> Result<(), Status> {
+        self.unit_step(operation_id, service_name, "Reload Units", |u, _| u.reload_units())?;
+        self.unit_step(operation_id, service_name, "Restart Unit", |u, n| u.restart_unit(n))?;
+
+        let step_name = "Wait For Healthy";
+        self.operations.step_started(operation_id, service_name, step_name)?;
+
+        let result = self.wait_healthy(service_name).await;
+
+        self.operations.step_finished(operation_id, service_name, step_name, result.as_ref().err().map(|e| e.to_string()));
+        result.map_err(|e| e.to_status(service_name, step_name))
+    }
+
+    /// Polls the unit until it has been active for enough checks in a row.
+    async fn wait_healthy(&self, service_name: &str) -> Result<(), UnitExecutorError> {
+        let HealthCheck { interval, required_checks, timeout } = self.health_check;
+        let started = Instant::now();
+        let mut active_checks = 0;
+
+        while active_checks < required_checks {
+            if started.elapsed() >= timeout {
+                return Err(UnitExecutorError::new(
+                    UnitErrorType::Unhealthy,
+                    format!("{service_name} was not active for {required_checks} checks in a row within {timeout:?}"),
+                ));
+            }
+
+            tokio::time::sleep(interval).await;
+
+            active_checks = match self.unit_executor.unit_active(service_name.to_owned())? {
+                true => active_checks + 1,
+                false => 0,
+            };
+        }
+
+        Ok(())
+    }
+
+    fn delete_service(&self, operation_id: &str, service_name: &String) -> Result<(), Status> {
+        // The name ends up in the path the folder is removed from, every
+        // caller gets it checked here.
+        ServiceDefinition::legacy(service_name.clone())
+            .validate()
+            .map_err(|e| Status::new(Code::InvalidArgument, e))?;
+
+        // Read before the definition file goes, a service that cannot be
+        // read is still deleted but any user it had is left behind.
+        let dedicated_user = match self.read_definition(service_name) {
+            Ok(definition) => definition.dedicated_user,
+            Err(status) => {
+                warn!("{}", status.message());
+                false
+            }
+        };
+
+        // Nothing may still run from the files, and the containers would
+        // keep the volumes in use.
+        self.unit_step(operation_id, service_name, "Stop Unit", |u, n| u.stop_unit(n))?;
+        self.unit_step(operation_id, service_name, "Unlink Unit", |u, n| u.unlink_unit(n))?;
+        self.unit_step(operation_id, service_name, "Compose Down", |u, n| u.compose_down(n))?;
+
+        let steps: [(&'static str, DeleteStepFn); 5] = [
+            ("Delete Definition File", |d, n| d.delete_definition_file(n)),
+            ("Delete Unit File", |d, n| d.delete_systemd_unit(n)),
+            ("Delete Env File", |d, n| d.delete_env_file(n)),
+            ("Delete Compose File", |d, n| d.delete_compose_file(n)),
+            ("Delete Folder", |d, n| d.delete_folder(n)),
+        ];
+
+        for (step_name, step_fn) in steps {
+            info!("Running step '{step_name}' for: {service_name}", step_name = step_name, service_name = service_name);
+            self.operations.step_started(operation_id, service_name, step_name)?;
+
+            match step_fn(self.delete_executor.as_ref(), service_name.clone()) {
+                // A half created service may be missing some of its files, the
+                // folder step still reports a service that does not exist at all.
+                Err(e) if e.kind().code() == Code::NotFound && step_name != "Delete Folder" => {
+                    info!("Skipping step '{step_name}' for {service_name}: {err}", step_name = step_name, service_name = service_name, err = e);
+                    self.operations.step_finished(operation_id, service_name, step_name, None);
+                }
+                Err(e) => {
+                    info!("Step '{step_name}' failed for {service_name} got error {err}", step_name = step_name, service_name = service_name, err = e);
+                    self.operations.step_finished(operation_id, service_name, step_name, Some(e.to_string()));
+                    return Err(e.to_status(service_name, step_name));
+                }
+                Ok(()) => self.operations.step_finished(operation_id, service_name, step_name, None),
+            }
+        }
+
+        info!("Deleted service folder for: {}", service_name);
+
+        if dedicated_user {
+            self.delete_account(operation_id, service_name)?;
+        }
+
+        // Left held if this fails, the next start releases it.
+        if let Err(e) = self.ports.release(service_name) {
+            warn!("Failed to release the ports of {service_name}: {e}");
+        }
+
+        Ok(())
+    }
+
+    /// Removes the named volumes of a deleted service, and with them its data.
+    async fn delete_volumes(&self, operation_id: &str, service_name: &String) -> Result<(), Status> {
+        let step_name = "Delete Volumes";
+        info!("Running step '{step_name}' for: {service_name}", step_name = step_name, service_name = service_name);
+        self.operations.step_started(operation_id, service_name, step_name)?;
+        let result = async {
+            // Compose names the project after the service's folder.
+            for volume in self.volume_executor.project_volumes(service_name.to_lowercase()).await? {
+                self.volume_executor.remove_volume(volume).await?;
+            }
+            Ok(())
+        }
+        .await;
+        self.operations.step_finished(operation_id, service_name, step_name, result.as_ref().err().map(|e: &VolumeExecutorError| e.to_string()));
+        result.map_err(|e| e.to_status(service_name, step_name))
+    }
+
+    fn delete_account(&self, operation_id: &str, service_name: &String) -> Result<(), Status> {
+        let step_name = "Delete Service User";
+        info!("Running step '{step_name}' for: {service_name}", step_name = step_name, service_name = service_name);
+        self.operations.step_started(operation_id, service_name, step_name)?;
+
+        let result = self.passwd.delete_account(&ServiceOwner::dedicated(service_name).user).map_err(|e| {
+            let kind = match e.kind() {
+                std::io::ErrorKind::NotFound => DeleteErrorType::UserDoesNotExist,
+                _ => DeleteErrorType::UserDeletionFailed,
+            };
+            DeleteExecutorError::new(kind, e.to_string())
+        });
+
+        match result {
+            // Already gone, the folder is deleted so the service still is.
+            Err(e) if e.kind().code() == Code::NotFound => {
+                info!("Skipping step '{step_name}' for {service_name}: {err}", step_name = step_name, service_name = service_name, err = e);
+                self.operations.step_finished(operation_id, service_name, step_name, None);
+                Ok(())
+            }
+            Err(e) => {
+                info!("Step '{step_name}' failed for {service_name} got error {err}", step_name = step_name, service_name = service_name, err = e);
+                self.operations.step_finished(operation_id, service_name, step_name, Some(e.to_string()));
+                Err(e.to_status(service_name, step_name))
+            }
+            Ok(()) => {
+                self.operations.step_finished(operation_id, service_name, step_name, None);
+                Ok(())
+            }
+        }
+    }
+
+    /// The undo of a step an earlier run of the daemon recorded, `None` for
+    /// steps that leave nothing of their own to undo.
+    fn undo_recorded(&self, step_name: &str) -> Option<UndoFn> {
+        let undo = match step_name {
+            "Allocate Ports" => self.undo_ports("Allocate Ports"),
+            "Create Service User" => self.undo_account("Create Service User"),
+            "Create Folder" => self.undo_delete("Create Folder", |d, n| d.delete_folder(n)),
+            "Create Compose File" => self.undo_delete("Create Compose File", |d, n| d.delete_compose_file(n)),
+            "Create Env File" => self.undo_delete("Create Env File", |d, n| d.delete_env_file(n)),
+            "Create Unit File" => self.undo_delete("Create Unit File", |d, n| d.delete_systemd_unit(n)),
+            "Link Unit" => self.undo_link("Link Unit"),
+            "Create Definition File" => self.undo_delete("Create Definition File", |d, n| d.delete_definition_file(n)),
+            "Write Compose File" => self.undo_replace("Write Compose File", ServiceFile::Compose),
+            "Write Env File" => self.undo_replace("Write Env File", ServiceFile::Env),
+            "Write Unit File" => self.undo_replace("Write Unit File", ServiceFile::Unit),
+            "Write Definition File" => self.undo_replace("Write Definition File", ServiceFile::Definition),
+            _ => return None,
+        };
+        Some(undo)
+    }
+
+    /// Settles an operation an earlier run of the daemon was stopped in the
+    /// middle of. A delete is finished, a create or update of the service it
+    /// was working on is undone, anything else is left as it is. The
+    /// operation is kept as failed, saying what was done.
+    pub(crate) async fn recover(&self, interrupted: &InterruptedOperation) {
+        let service_name = interrupted.current_service().to_owned();
+        let steps: Vec<_> = interrupted.current_steps().filter(|step| step.took_effect()).collect();
+        let deleting = steps.iter().any(|step| step.name.starts_with("Delete ") || step.name == "Unlink Unit");
+        let purging = steps.iter().any(|step| step.name == "Delete Volumes");
+        let restarted = steps.iter().any(|step| step.name == "Restart Unit");
+        let undo_stack: UndoStack = steps.iter().filter_map(|step| self.undo_recorded(&step.name)).collect();
+
+        warn!(
+            "Recovering {kind} operation {id} for {service_name}, it was interrupted",
+            kind = interrupted.kind,
+            id = interrupted.id
         );
+        let error = if deleting {
+            self.recover_with(&service_name, async |id| {
+                match self.delete_service(id, &service_name) {
+                    // The folder goes last, so the delete had already finished.
+                    Err(status) if status.code() == Code::NotFound => Ok(()),
+                    result => result,
+                }?;
+                // Volumes are only purged if the delete had got as far.
+                match purging {
+                    true => self.delete_volumes(id, &service_name).await,
+                    false => Ok(()),
+                }
+            })
+            .await
+            .map_or_else(
+                |status| format!("interrupted, finishing the delete failed: {}", status.message()),
+                |id| format!("interrupted, the delete was finished by operation {id}"),
+            )
+        } else if !undo_stack.is_empty() {
+            self.unwind(service_name.clone(), undo_stack);
+            match restarted {
+                true => self
+                    .recover_with(&service_name, async |id| self.restart_unit(id, &service_name).await)
+                    .await
+                    .map_or_else(
+                        |status| format!("interrupted and rolled back, restarting failed: {}", status.message()),
+                        |id| format!("interrupted and rolled back, restarted by operation {id}"),
+                    ),
+                false => "interrupted and rolled back".to_owned(),
+            }
+        } else {
+            "interrupted".to_owned()
+        };
 
-        info!("Creating undo queue for: {}", service_name);
-        let mut undo_stack: VecDeque<Box<dyn FnOnce(String) -> () + Send>> = VecDeque::new();
-
-        // Create folder step
-        if let Some(value) = self.run_step(
-            &service_name,
-            "Create Folder",
-            &mut undo_stack,
-            & |n| { self.create_executor.create_folder(n) },
-            & |n| { self.delete_executor.delete_folder(n) }
-        ) {
-            self.unwind(service_name, undo_stack)?;
-            return value;
-        }
-
-        // Create compose file step
-        if let Some(value) = self.run_step(
-            &service_name,
-            "Create Compose File",
-            &mut undo_stack,
-            & |n| { self.create_executor.create_compose_file(n) },
-            & |n| { self.delete_executor.delete_compose_file(n) }
-        ) {
-            self.unwind(service_name, undo_stack)?;
-            return value;
-        }
-
-        // Create env file step
-        if let Some(value) = self.run_step(
-            &service_name,
-            "Create Compose File",
-            &mut undo_stack,
-            & |n| { self.create_executor.create_env_file(n) },
-            & |n| { self.delete_executor.delete_env_file(n) }
-        ) {
-            self.unwind(service_name, undo_stack)?;
-            return value;
-        }
-
-        // Create unit file step
-        if let Some(value) = self.run_step(
-            &service_name,
-            "Create Compose File",
-            &mut undo_stack,
-            & |n| { self.create_executor.create_systemd_unit(n) },
-            & |n| { self.delete_executor.delete_systemd_unit(n) }
-        ) {
-            self.unwind(service_name, undo_stack)?;
-            return value;
-        }
-        
-        info!("Created service folder for: {}", service_name);
-        Ok(Response::new(CreateResponse {}))
+        self.operations.record_interrupted(interrupted, error);
     }
 
-    async fn restart(
+    /// Runs `f` as a `recover` operation of its own, returning its id.
+    async fn recover_with(
         &self,
-        request: Request<RestartRequest>,
-    ) -> Result<Response<RestartResponse>, Status> {
-        info!("Got restart request: {:?}", request.get_ref());
-        Ok(Response::new(RestartResponse {}))
+        service_name: &str,
+        f: impl AsyncFnOnce(&str) -> Result<(), Status>,
+    ) -> Result<String, Status> {
+        let BeginOutcome::Started(id) = self.operations.begin("recover", service_name, "")? else {
+            unreachable!("operations without an idempotency key always start");
+        };
+        let result = f(&id).await;
+        self.operations.finish(&id, result)
     }
 
-    async fn pull(&self, request: Request<PullRequest>) -> Result<Response<PullResponse>, Status> {
-        info!("Got pull request: {:?}", request.get_ref());
-        Ok(Response::new(PullResponse {}))
+    /// Stops new operations from starting, for shutting down.
+    pub(crate) fn stop_operations(&self) {
+        self.operations.close()
     }
 
-    async fn delete(
+    pub(crate) fn running_operations(&self) -> Vec<InterruptedOperation> {
+        self.operations.running()
+    }
+
+    /// The account a service's files and unit belong to.
+    fn owner_for(&self, definition: &ServiceDefinition) -> ServiceOwner {
+        match definition.dedicated_user {
+            true => ServiceOwner::dedicated(&definition.name),
+            false => self.service_owner.clone(),
+        }
+    }
+
+    /// Definition of a service on disk. Services made before definitions were
+    /// stored are treated as the default blueprint with no overrides.
+    fn read_definition(&self, service_name: &str) -> Result<ServiceDefinition, Status> {
+        match self.file_manager.read_definition(service_name.to_owned()) {
+            Ok(Some(json)) => ServiceDefinition::from_json(&json).map_err(|e| {
+                Status::new(Code::Internal, format!("Definition of {service_name} is unreadable: {e}"))
+            }),
+            Ok(None) => Ok(ServiceDefinition::legacy(service_name.to_owned())),
+            Err(e) => Err(Status::new(
+                Code::Internal,
+                format!("Failed to read definition of {service_name} with error: {e}"),
+            )),
+        }
+    }
+
+    /// Looks up the service user and group, before anything is written so a
+    /// missing account fails early.
+    fn resolve_owner(&self, service_owner: &ServiceOwner) -> Result<Owner, Status> {
+        let ServiceOwner { user, group } = service_owner;
+        let lookup_failed = |e: std::io::Error| {
+            Status::new(Code::Internal, format!("Failed to look up the service user with error: {e}"))
+        };
+
+        let uid = self.passwd.user_id(user).map_err(lookup_failed)?;
+        let gid = self.passwd.group_id(group).map_err(lookup_failed)?;
+
+        match (uid, gid) {
+            (Some(uid), Some(gid)) => Ok(Owner { uid, gid }),
+            _ => Err(Status::new(
+                Code::FailedPrecondition,
+                format!("service user '{user}' or group '{group}' does not exist on this host"),
+            )),
+        }
+    }
+
+    fn account_name(&self, uid: Option<u32>, gid: Option<u32>) -> String {
+        let (Some(uid), Some(gid)) = (uid, gid) else {
+            return String::new();
+        };
+        let user = self.passwd.user_name(uid).ok().flatten().unwrap_or_else(|| uid.to_string());
+        let group = self.passwd.group_name(gid).ok().flatten().unwrap_or_else(|| gid.to_string());
+        format!("{user}:{group}")
+    }
+
+    /// Compares the service's folder and files to the permission plan.
+    fn check_permissions(&self, service_name: &str) -> Vec<FilePermission> {
+        let service_owner = match self.read_definition(service_name) {
+            Ok(definition) => self.owner_for(&definition),
+            Err(_) => self.service_owner.clone(),
+        };
+        let owner = self.resolve_owner(&service_owner).ok();
+        let expected_owner = format!("{}:{}", service_owner.user, service_owner.group);
+
+        permissions::check(&ServiceFile::folder(service_name), service_name)
+            .into_iter()
+            .map(|check| {
+                let ok = check.ok(owner);
+                let FileCheck { path, expected_mode, mode, uid, gid } = check;
+                FilePermission {
+                    path: path.display().to_string(),
+                    mode: mode.map(|mode| format!("{mode:04o}")).unwrap_or_default(),
+                    expected_mode: format!("{expected_mode:04o}"),
+                    owner: self.account_name(uid, gid),
+                    expected_owner: expected_owner.clone(),
+                    ok,
+                }
+            })
+            .collect()
+    }
+
+    /// Logs every service file whose owner or mode is off, run on startup so
+    /// files created before permissions were managed get noticed.
+    pub fn warn_on_permissions(&self) {
+        if let Err(status) = self.resolve_owner(&self.service_owner) {
+            warn!("{}", status.message());
+        }
+
+        let services = match self.file_manager.list_services() {
+            Ok(services) => services,
+            Err(e) => return warn!("Failed to list services to check permissions: {e}"),
+        };
+
+        for service_name in services {
+            for file in self.check_permissions(&service_name).into_iter().filter(|file| !file.ok) {
+                warn!(
+                    "{path} is {mode} {owner}, expected {expected_mode} {expected_owner}",
+                    path = file.path,
+                    mode = if file.mode.is_empty() { "missing" } else { &file.mode },
+                    owner = file.owner,
+                    expected_mode = file.expected_mode,
+                    expected_owner = file.expected_owner
+                );
+            }
+        }
+    }
+
+    fn current_definitions(&self) -> Result<BTreeMap<String, ServiceDefinition>, Status> {
+        self.service_names()?
+            .into_iter()
+            .map(|name| self.read_definition(&name).map(|definition| (name, definition)))
+            .collect()
+    }
+
+    /// Rejects writing `changed` and deleting `deleted` if the services'
+    /// limits would then add up to more than the host quota.
+    fn check_quota(&self, changed: &[&ServiceDefinition], deleted: &[&str]) -> Result<(), Status> {
+        if self.quota == services::ResourceLimits::default() {
+            return Ok(());
+        }
+
+        let current = self.current_definitions()?;
+        let mut planned = current.clone();
+        for name in deleted {
+            planned.remove(*name);
+        }
+        for definition in changed {
+            planned.insert(definition.name.clone(), (*definition).clone());
+        }
+
+        services::check_quota(&self.quota, current.values(), planned.values())
+            .map_err(|e| Status::new(Code::ResourceExhausted, e))
+    }
+
+    /// Definition of a service that has to exist.
+    fn existing_definition(&self, service_name: &str) -> Result<ServiceDefinition, Status> {
+        telemetry::record_service(service_name);
+        ServiceDefinition::legacy(service_name.to_owned())
+            .validate()
+            .map_err(|e| Status::new(Code::InvalidArgument, e))?;
+
+        if !self.file_manager.service_folder_exists(service_name.to_owned()) {
+            return Err(Status::new(
+                Code::NotFound,
+                format!("service '{service_name}' does not exist"),
+            ));
+        }
+
+        self.read_definition(service_name)
+    }
+
+    /// Definition of a service that has to exist, with its dump commands.
+    fn backup_target(&self, service_name: &str) -> Result<(ServiceDefinition, DumpCommands), Status> {
+        let definition = self.existing_definition(service_name)?;
+        match validate(&definition)?.render_dump(&definition) {
+            Some(commands) => Ok((definition, commands)),
+            None => Err(Status::new(
+                Code::FailedPrecondition,
+                format!("blueprint '{blueprint}' does not support backups", blueprint = definition.blueprint),
+            )),
+        }
+    }
+
+    fn backup_step<T>(
         &self,
-        request: Request<DeleteRequest>,
-    ) -> Result<Response<DeleteResponse>, Status> {
-        info!("Got delete request: {:?}", request.get_ref());
-        Ok(Response::new(DeleteResponse {}))
+        operation_id: &str,
+        service_name: &str,
+        step_name: &'static str,
+        result: Result<T, BackupExecutorError>,
+    ) -> Result<T, Status> {
+        self.operations.step_finished(operation_id, service_name, step_name, result.as_ref().err().map(|e| e.to_string()));
+        result.map_err(|e| e.to_status(service_name, step_name))
+    }
+
+    /// Dumps the service's data into the backup store under the operation's
+    /// id, then prunes backups past the retention limit.
+    async fn backup_service(
+        &self,
+        operation_id: &str,
+        definition: &ServiceDefinition,
+        commands: DumpCommands,
+    ) -> Result<BackupMetadata, Status> {
+        let service_name = &definition.name;
+        let destination = self.backups.data_path(service_name, operation_id);
+        let write_failed = |e: std::io::Error, path: &std::path::Path| {
+            BackupExecutorError::new(BackupErrorType::FileWriteFailed, e.to_string()).with_path(path)
+        };
+
+        let step_name = "Dump Data";
+        info!("Running step '{step_name}' for: {service_name}", step_name = step_name, service_name = service_name);
+        self.operations.step_started(operation_id, service_name, step_name)?;
+        let result = match self.backups.create_folder(service_name) {
+            Ok(()) => self.backup_executor.dump(commands.container, commands.dump, destination.clone()).await,
+            Err(e) => Err(write_failed(e, &destination)),
+        };
+        let size_bytes = self.backup_step(operation_id, service_name, step_name, result)?;
+
+        let metadata = BackupMetadata {
+            backup_id: operation_id.to_owned(),
+            service_name: service_name.clone(),
+            engine: commands.engine.to_owned(),
+            blueprint: definition.blueprint.clone(),
+            image: validate(definition)?.parameters(definition).remove("image").unwrap_or_default(),
+            created_at_nanos: SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos() as u64,
+            size_bytes,
+        };
+
+        let step_name = "Write Metadata";
+        self.operations.step_started(operation_id, service_name, step_name)?;
+        let result = self.backups.save(&metadata).map_err(|e| write_failed(e, &destination));
+        if let Err(status) = self.backup_step(operation_id, service_name, step_name, result) {
+            let _ = self.backups.remove(service_name, operation_id);
+            return Err(status);
+        }
+
+        // The new backup is complete by now, failing to prune is only logged.
+        let step_name = "Prune Backups";
+        self.operations.step_started(operation_id, service_name, step_name)?;
+        let result = self.backups.prune(service_name).map_err(|e| write_failed(e, &destination));
+        match self.backup_step(operation_id, service_name, step_name, result) {
+            Ok(removed) if !removed.is_empty() => {
+                info!("Removed old backups of {service_name}: {removed}", service_name = service_name, removed = removed.join(", "))
+            }
+            Ok(_) => (),
+            Err(status) => warn!("{}", status.message()),
+        }
+
+        Ok(metadata)
+    }
+
+    /// Pulls the service's image, restarting the unit when `restart_on_change`
+    /// is set and the pull brought a new image. Returns whether it changed.
+    async fn pull_service(
+        &self,
+        operation_id: &str,
+        definition: &ServiceDefinition,
+        restart_on_change: bool,
+    ) -> Result<bool, Status> {
+        let service_name = &definition.name;
+        let image = validate(definition)?.parameters(definition).remove("image").unwrap_or_default();
+
+        let step_name = "Pull Image";
+        info!("Running step '{step_name}' for: {service_name}", step_name = step_name, service_name = service_name);
+        self.operations.step_started(operation_id, service_name, step_name)?;
+        let result = async {
+            let before = self.image_executor.image_id(image.clone()).await?;
+            self.image_executor.pull_image(image.clone()).await?;
+            let after = self.image_executor.image_id(image.clone()).await?;
+            Ok(before != after)
+        }
+        .await;
+        self.operations.step_finished(operation_id, service_name, step_name, result.as_ref().err().map(|e: &ImageExecutorError| e.to_string()));
+        let changed = result.map_err(|e| e.to_status(service_name, step_name))?;
+        self.metrics.pulled(service_name, unix_now());
+
+        match (changed, restart_on_change) {
+            (true, true) => self.restart_unit(operation_id, service_name).await?,
+            (true, false) => info!("Pulled a new {image} for {service_name}, it is used from the next restart"),
+            (false, _) => info!("{image} for {service_name} is already up to date"),
+        }
+
+        Ok(changed)
+    }
+
+    /// Every job the schedules currently give the services.
+    pub(crate) fn scheduled_jobs(&self) -> Result<Vec<Job>, Status> {
+        Ok(self.schedules.jobs(&self.current_definitions()?))
+    }
+
+    pub(crate) fn next_run(&self, job: &Job, now: u64) -> Option<u64> {
+        self.schedules.next_run(job, now)
+    }
+
+    /// Drops the run times of jobs that are no longer scheduled.
+    pub(crate) fn retain_jobs(&self, jobs: &[Job]) {
+        self.schedules.retain(jobs)
+    }
+
+    async fn run_scheduled(&self, operation_id: &str, job: &Job) -> Result<(), Status> {
+        let service_name = &job.service_name;
+        match job.spec.job {
+            JobKind::Backup => {
+                let (definition, commands) = self.backup_target(service_name)?;
+                self.backup_service(operation_id, &definition, commands).await.map(|_| ())
+            }
+            JobKind::Pull => {
+                let definition = self.existing_definition(service_name)?;
+                self.pull_service(operation_id, &definition, job.spec.restart_on_change).await.map(|_| ())
+            }
+            JobKind::HealthCheck => unreachable!("health checks are not run as operations"),
+        }
+    }
+
+    /// Runs a scheduled job as an operation of its own and records the run.
+    #[instrument(
+        name = "job",
+        skip_all,
+        fields(job = job.spec.job.as_str(), service_name = %job.service_name, operation_id = Empty)
+    )]
+    pub(crate) async fn run_job(&self, job: &Job) {
+        let started_at = unix_now();
+        info!("Running scheduled {job} for: {service_name}", job = job.spec.job.as_str(), service_name = job.service_name);
+
+        let begin = match job.spec.job {
+            // Health checks are kept in the service's health history instead.
+            JobKind::HealthCheck => None,
+            kind => Some(self.operations.begin(kind.as_str(), &job.service_name, "")),
+        };
+        let (operation_id, result) = match begin {
+            None => (String::new(), self.monitor_service(&job.service_name).await),
+            Some(Ok(BeginOutcome::Started(id))) => {
+                let result = self.run_scheduled(&id, job).await;
+                (id.clone(), self.operations.finish(&id, result).map(|_| ()))
+            }
+            Some(Ok(BeginOutcome::Existing(record))) => (record.id.clone(), record.replay().map(|_| ())),
+            Some(Err(status)) => (String::new(), Err(status)),
+        };
+
+        if let Err(status) = &result {
+            warn!(
+                "Scheduled {job} for {service_name} failed: {message}",
+                job = job.spec.job.as_str(),
+                service_name = job.service_name,
+                message = status.message()
+            );
+        }
+
+        self.schedules.record(job, LastRun {
+            started_at,
+            succeeded: result.is_ok(),
+            operation_id,
+            error: result.err().map(|status| status.message().to_owned()),
+        });
+    }
+
+    /// Checks the unit, the container and the blueprint's probe, in that
+    /// order, stopping at the first that fails.
+    async fn check_service(&self, definition: &ServiceDefinition) -> HealthCheckResult {
+        let mut check = HealthCheckResult {
+            checked_at: unix_now() as i64,
+            ..Default::default()
+        };
+
+        let status = match self.run_checks(definition, &mut check).await {
+            Ok(()) => HealthStatus::Healthy,
+            Err((status, error)) => {
+                check.error = error;
+                status
+            }
+        };
+        check.status = status as i32;
+        check
+    }
+
+    async fn run_checks(&self, definition: &ServiceDefinition, check: &mut HealthCheckResult) -> Result<(), (HealthStatus, String)> {
+        let unknown = |error: String| (HealthStatus::Unknown, error);
+        let unhealthy = |error: String| (HealthStatus::Unhealthy, error);
+        let service_name = &definition.name;
+        let blueprint = Blueprint::find(&definition.blueprint).map_err(unknown)?;
+
+        check.unit_active = self.unit_executor.unit_active(service_name.clone()).map_err(|e| unknown(e.to_string()))?;
+        if !check.unit_active {
+            return Err(unhealthy(format!("unit {service_name}.service is not active")));
+        }
+
+        let container = blueprint.render_container(definition);
+        let status = self
+            .health_executor
+            .container_status(container.clone())
+            .await
+            .map_err(|e| unknown(e.to_string()))?
+            .ok_or_else(|| unhealthy(format!("container {container} does not exist")))?;
+        check.container_state = status.state.clone();
+        check.container_health = status.health.clone();
+
+        if status.state != "running" {
+            return Err(unhealthy(format!("container {container} is {state}", state = status.state)));
+        }
+        match status.health.as_str() {
+            "unhealthy" => return Err(unhealthy(format!("container {container} reports itself unhealthy"))),
+            "starting" => return Err(unknown(format!("container {container} is still starting"))),
+            _ => (),
+        }
+
+        // A container without an address, for example one on the host's
+        // network, is only checked through docker.
+        let (Some(probe), Some(address)) = (blueprint.probe(), status.address) else {
+            return Ok(());
+        };
+        check.probe = probe.url(address);
+        self.health_executor.probe(address, probe).await.map_err(|e| unhealthy(e.to_string()))?;
+        check.probe_ok = true;
+
+        Ok(())
+    }
+
+    /// Checks a service and adds the result to its history, logging when its
+    /// status changes.
+    async fn check_health(&self, service_name: &str) -> Result<HealthCheckResult, Status> {
+        let definition = self.existing_definition(service_name)?;
+        let check = self.check_service(&definition).await;
+
+        if let Some(previous) = self.health.record(service_name, check.clone()) {
+            let (from, to) = (previous.as_str_name(), check.status().as_str_name());
+            match check.status() {
+                HealthStatus::Unhealthy => warn!("{service_name} went from {from} to {to}: {error}", error = check.error),
+                _ => info!("{service_name} went from {from} to {to}"),
+            }
+        }
+
+        Ok(check)
+    }
+
+    /// Checks a service and restarts it once it has been unhealthy for
+    /// `restart_unhealthy` checks in a row. Fails if it is not healthy.
+    pub(crate) async fn monitor_service(&self, service_name: &str) -> Result<(), Status> {
+        let check = self.check_health(service_name).await?;
+
+        let streak = self.health.unhealthy_streak(service_name);
+        if self.restart_unhealthy > 0 && streak > 0 && streak.is_multiple_of(self.restart_unhealthy) {
+            warn!("Restarting {service_name} after {streak} unhealthy checks in a row");
+            if let Ok(BeginOutcome::Started(id)) = self.operations.begin("restart", service_name, "") {
+                let result = self.restart_unit(&id, &service_name.to_owned()).await;
+                if let Err(status) = self.operations.finish(&id, result) {
+                    warn!("Restarting {service_name} failed: {message}", message = status.message());
+                }
+            }
+        }
+
+        match check.status() {
+            HealthStatus::Healthy => Ok(()),
+            _ => Err(Status::new(Code::FailedPrecondition, check.error)),
+        }
+    }
+
+    pub(crate) fn service_names(&self) -> Result<Vec<String>, Status> {
+        self.file_manager.list_services().map_err(|e| {
+            Status::new(Code::Internal, format!("Failed to list services with error: {e}"))
+        })
+    }
+
+    /// Releases the ports of services that no longer exist, such as ones
+    /// deleted just before a crash.
+    pub(crate) fn release_unused_ports(&self) -> Result<(), Status> {
+        let service_names = self.service_names()?;
+        for service_name in self.ports.service_names().iter().filter(|name| !service_names.contains(name)) {
+            info!("Releasing the ports of {service_name}, it no longer exists");
+            self.ports
+                .release(service_name)
+                .map_err(|e| Status::new(Code::Internal, format!("Failed to release ports: {e}")))?;
+        }
+        Ok(())
+    }
+
+    /// Forgets the health of services that no longer exist.
+    pub(crate) fn retain_health(&self, service_names: &[String]) {
+        self.health.retain(service_names)
+    }
+
+    /// Checks what provisioning needs from the host, named as in
+    /// `dependencies::ALL`.
+    pub(crate) async fn check_dependencies(&self) -> Vec<(&'static str, Result<(), HealthExecutorError>)> {
+        vec![
+            (dependencies::DOCKER, self.health_executor.ping_docker().await),
+            (dependencies::SYSTEMD, self.health_executor.ping_systemd().await),
+            (dependencies::PROVISIONING_ROOT, self.health_executor.check_root_writable().await),
+        ]
+    }
+
+    /// Unix time of the service's last successful pull, from this run of the
+    /// daemon or else from its pull schedule.
+    fn last_pull(&self, service_name: &str, jobs: &[Job]) -> Option<u64> {
+        self.metrics.last_pull(service_name).or_else(|| {
+            jobs.iter()
+                .filter(|job| job.service_name == service_name && job.spec.job == JobKind::Pull)
+                .filter_map(|job| self.schedules.last_run(job))
+                .filter(|run| run.succeeded)
+                .map(|run| run.started_at)
+                .max()
+        })
+    }
+
+    /// The daemon's counters plus gauges for every service, in the Prometheus
+    /// text format.
+    pub(crate) fn render_metrics(&self) -> String {
+        let mut format = TextFormat::default();
+        self.metrics.write(&mut format);
+
+        let service_names = self.service_names().unwrap_or_else(|status| {
+            warn!("Failed to list services for metrics: {}", status.message());
+            Vec::new()
+        });
+        let jobs = self.scheduled_jobs().unwrap_or_default();
+
+        // Checks that could not tell are left out rather than reported as down.
+        let checks: Vec<(&str, HealthCheckResult)> = service_names
+            .iter()
+            .filter_map(|name| Some((name.as_str(), self.health.get(name)?.history.pop()?)))
+            .filter(|(_, check)| check.status() != HealthStatus::Unknown)
+            .collect();
+        format.family("provisiond_service_unit_active", "gauge", "Whether the service's systemd unit was active at the last check.");
+        for (name, check) in &checks {
+            format.sample("provisiond_service_unit_active", &[("service", name)], f64::from(u8::from(check.unit_active)));
+        }
+        format.family(
+            "provisiond_service_container_healthy",
+            "gauge",
+            "Whether the service's container was running, and healthy if it has a healthcheck, at the last check.",
+        );
+        for (name, check) in &checks {
+            let healthy = check.container_state == "running" && matches!(check.container_health.as_str(), "" | "healthy");
+            format.sample("provisiond_service_container_healthy", &[("service", name)], f64::from(u8::from(healthy)));
+        }
+
+        format.family(
+            "provisiond_service_last_backup_timestamp_seconds",
+            "gauge",
+            "Unix time of the service's newest stored backup.",
+        );
+        for name in &service_names {
+            let newest = self.backups.list(name).ok().and_then(|backups| backups.iter().map(|b| b.created_at_nanos).max());
+            if let Some(nanos) = newest {
+                format.sample("provisiond_service_last_backup_timestamp_seconds", &[("service", name)], (nanos / 1_000_000_000) as f64);
+            }
+        }
+
+        format.family(
+            "provisiond_service_last_pull_timestamp_seconds",
+            "gauge",
+            "Unix time of the service's last successful image pull.",
+        );
+        for name in &service_names {
+            if let Some(pulled) = self.last_pull(name, &jobs) {
+                format.sample("provisiond_service_last_pull_timestamp_seconds", &[("service", name)], pulled as f64);
+            }
+        }
+
+        format.finish()
+    }
+
+    /// Checks the caller may run the command and starts it, recording the
+    /// attempt either way. Returns the entry to record when it finishes.
+    async fn start_exec(&self, metadata: &MetadataMap, start: ExecStart) -> Result<(ExecSession, AuditEntry), Status> {
+        let ExecStart {
+            service_name,
+            compose_service,
+            command,
+            tty,
+            size,
+        } = start;
+        if command.is_empty() {
+            return Err(Status::new(Code::InvalidArgument, "a command is required"));
+        }
+
+        let mut entry = AuditEntry {
+            time: unix_now(),
+            action: "exec",
+            service_name: service_name.clone(),
+            command: command.clone(),
+            ..Default::default()
+        };
+        let denied = |mut entry: AuditEntry, status: Status| {
+            entry.error = Some(status.message().to_owned());
+            self.audit.record(&entry);
+            Err(status)
+        };
+
+        let subject = match self.policy.authenticate(metadata) {
+            Ok(subject) => subject,
+            Err(status) => return denied(entry, status),
+        };
+        entry.subject = subject.name.clone();
+        if !subject.may_exec(&service_name, &command) {
+            let status = Status::new(
+                Code::PermissionDenied,
+                format!("'{subject}' may not run '{program}' in '{service_name}'", subject = subject.name, program = command[0]),
+            );
+            return denied(entry, status);
+        }
+
+        let to_status = |e: ExecExecutorError| e.to_status(&service_name, "Exec");
+        let result = async {
+            let definition = self.existing_definition(&service_name)?;
+            let blueprint = validate(&definition)?;
+            entry.container = if compose_service.is_empty() {
+                blueprint.render_container(&definition)
+            } else {
+                // Compose names the project after the service's folder.
+                self.exec_executor
+                    .find_container(service_name.to_lowercase(), compose_service.clone())
+                    .await
+                    .map_err(to_status)?
+                    .ok_or_else(|| {
+                        Status::new(Code::NotFound, format!("'{compose_service}' is not running in '{service_name}'"))
+                    })?
+            };
+
+            let session = self
+                .exec_executor
+                .start(entry.container.clone(), command.clone(), tty)
+                .await
+                .map_err(to_status)?;
+            if let Some(size) = size.filter(|_| tty) {
+                self.exec_executor
+                    .resize(session.id.clone(), size.rows, size.columns)
+                    .await
+                    .map_err(to_status)?;
+            }
+            Ok::<_, Status>(session)
+        }
+        .await;
+
+        entry.allowed = true;
+        entry.error = result.as_ref().err().map(|status| status.message().to_owned());
+        self.audit.record(&entry);
+        entry.error = None;
+
+        result.map(|session| (session, entry))
+    }
+
+    /// Stops at the first service that fails, that service is unwound but the
+    /// ones before it keep their changes.
+    async fn apply_changes(&self, operation_id: &str, plan: &[PlannedChange]) -> Result<(), Status> {
+        for change in plan {
+            match (&change.action, &change.definition) {
+                (Action::Create, Some(definition)) => self.create_service(operation_id, definition)?,
+                // Schedules are read by the daemon, the service itself does not
+                // need a restart for them.
+                (Action::Update(fields), Some(definition)) => {
+                    let restart = fields.iter().any(|field| field != "schedules");
+                    self.update_service(operation_id, definition, restart).await?
+                }
+                (Action::Delete, _) => self.delete_service(operation_id, &change.service_name)?,
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ProvisionerImpl {
+    pub fn with_backup(backup: Backup) -> Self {
+        Self {
+            create_executor: Arc::new(RealCreateExecutor::new(backup)),
+            file_manager: Arc::new(RealFileManager::default().with_backup(backup)),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_service_owner(mut self, service_owner: ServiceOwner) -> Self {
+        self.service_owner = service_owner;
+        self
+    }
+
+    pub fn with_backup_store(mut self, backups: BackupStore) -> Self {
+        self.backups = backups;
+        self
+    }
+
+    pub fn with_schedule_store(mut self, schedules: ScheduleStore) -> Self {
+        self.schedules = schedules;
+        self
+    }
+
+    pub fn with_restart_unhealthy(mut self, restart_unhealthy: u32) -> Self {
+        self.restart_unhealthy = restart_unhealthy;
+        self
+    }
+
+    pub fn with_port_pool(mut self, ports: PortPool) -> Self {
+        self.ports = Arc::new(ports);
+        self
+    }
+
+    pub fn with_quota(mut self, quota: services::ResourceLimits) -> Self {
+        self.quota = quota;
+        self
+    }
+
+    pub fn with_policy(mut self, policy: Arc<Policy>) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn with_audit_log(mut self, audit: AuditLog) -> Self {
+        self.audit = Arc::new(audit);
+        self
+    }
+
+    /// Shares `metrics` with the RPC layer, the operation store counts its
+    /// steps there too.
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = metrics.clone();
+        self.map_operations(|operations| operations.with_metrics(metrics))
+    }
+
+    /// Journals every operation and its steps. Operations the journal holds
+    /// from an earlier run are left for `recover`.
+    pub fn with_journal(self, journal: OperationJournal) -> Self {
+        self.map_operations(|operations| operations.with_journal(journal))
+    }
+
+    /// Changes the operation store in place, so the settings above keep each
+    /// other whatever order they are made in.
+    fn map_operations(mut self, f: impl FnOnce(OperationStore) -> OperationStore) -> Self {
+        let operations = Arc::into_inner(self.operations).expect("the operation store is not shared while it is set up");
+        self.operations = Arc::new(f(operations));
+        self
+    }
+}
+
+impl Default for ProvisionerImpl {
+    fn default() -> Self {
+        let metrics = Arc::new(Metrics::default());
+        Self {
+            create_executor: Arc::new(RealCreateExecutor::default()),
+            delete_executor: Arc::new(RealDeleteExecutor),
+            unit_executor: Arc::new(RealUnitExecutor),
+            backup_executor: Arc::new(RealBackupExecutor),
+            image_executor: Arc::new(RealImageExecutor),
+            health_executor: Arc::new(RealHealthExecutor),
+            log_executor: Arc::new(RealLogExecutor),
+            exec_executor: Arc::new(RealExecExecutor),
+            volume_executor: Arc::new(RealVolumeExecutor),
+            file_manager: Arc::new(RealFileManager::default()),
+            passwd: Arc::new(EtcPasswd::default()),
+            operations: Arc::new(OperationStore::default().with_metrics(metrics.clone())),
+            health_check: HealthCheck::default(),
+            service_owner: ServiceOwner::default(),
+            backups: BackupStore::default(),
+            schedules: ScheduleStore::default(),
+            health: HealthHistory::default(),
+            restart_unhealthy: 0,
+            policy: Arc::new(Policy::default()),
+            audit: Arc::new(AuditLog::default()),
+            metrics,
+            ports: Arc::new(PortPool::default()),
+            quota: services::ResourceLimits::default(),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl Provisioner for ProvisionerImpl {
+    type LogsStream = Pin<Box<dyn Stream<Item = Result<LogLine, Status>> + Send>>;
+    type ExecStream = Pin<Box<dyn Stream<Item = Result<ExecOutput, Status>> + Send>>;
+
+    async fn create(
+        &self,
+        request: Request<CreateRequest>,
+    ) -> Result<Response<CreateResponse>, Status> {
+        let request = request.into_inner();
+        let idempotency_key = request.idempotency_key.clone();
+        let definition = ServiceDefinition::try_from(request).map_err(|e| Status::new(Code::InvalidArgument, e))?;
+        info!(
+            "Got create request to make service with name: {}",
+            definition.name
+        );
+
+        validate(&definition)?;
+        self.check_quota(&[&definition], &[])?;
+
+        let operation_id = match self.operations.begin("create", &definition.name, &idempotency_key)? {
+            BeginOutcome::Started(id) => id,
+            BeginOutcome::Existing(record) => {
+                return record.replay().map(|operation_id| Response::new(CreateResponse { operation_id }));
+            }
+        };
+
+        let result = self.create_service(&operation_id, &definition);
+        let operation_id = self.operations.finish(&operation_id, result)?;
+
+        Ok(Response::new(CreateResponse { operation_id }))
+    }
+
+    async fn restart(
+        &self,
+        request: Request<RestartRequest>,
+    ) -> Result<Response<RestartResponse>, Status> {
+        info!("Got restart request: {:?}", request.get_ref());
+
+        let request = request.into_inner();
+        let service_name = self.existing_definition(&request.service_name)?.name;
+
+        let operation_id = match self.operations.begin("restart", &service_name, &request.idempotency_key)? {
+            BeginOutcome::Started(id) => id,
+            BeginOutcome::Existing(record) => {
+                return record.replay().map(|operation_id| Response::new(RestartResponse { operation_id }));
+            }
+        };
+
+        let result = self.restart_unit(&operation_id, &service_name).await;
+        let operation_id = self.operations.finish(&operation_id, result)?;
+        Ok(Response::new(RestartResponse { operation_id }))
+    }
+
+    async fn pull(&self, request: Request<PullRequest>) -> Result<Response<PullResponse>, Status> {
+        info!("Got pull request: {:?}", request.get_ref());
+
+        let request = request.into_inner();
+        let definition = self.existing_definition(&request.service_name)?;
+
+        let operation_id = match self.operations.begin("pull", &request.service_name, &request.idempotency_key)? {
+            BeginOutcome::Started(id) => id,
+            BeginOutcome::Existing(record) => {
+                return record.replay().map(|operation_id| Response::new(PullResponse { operation_id }));
+            }
+        };
+
+        let result = self.pull_service(&operation_id, &definition, request.restart_on_change).await;
+        let operation_id = self.operations.finish(&operation_id, result.map(|_| ()))?;
+        Ok(Response::new(PullResponse { operation_id }))
+    }
+
+    async fn delete(
+        &self,
+        request: Request<DeleteRequest>,
+    ) -> Result<Response<DeleteResponse>, Status> {
+        info!("Got delete request: {:?}", request.get_ref());
+
+        let DeleteRequest {
+            idempotency_key,
+            service_name,
+            purge_volumes,
+        } = request.into_inner();
+
+        ServiceDefinition::legacy(service_name.clone())
+            .validate()
+            .map_err(|e| Status::new(Code::InvalidArgument, e))?;
+
+        let operation_id = match self.operations.begin("delete", &service_name, &idempotency_key)? {
+            BeginOutcome::Started(id) => id,
+            BeginOutcome::Existing(record) => {
+                return record.replay().map(|operation_id| Response::new(DeleteResponse { operation_id }));
+            }
+        };
+
+        // Volumes are kept unless asked for, so data is not lost by accident.
+        let result = match self.delete_service(&operation_id, &service_name) {
+            Ok(()) if purge_volumes => self.delete_volumes(&operation_id, &service_name).await,
+            result => result,
+        };
+        let operation_id = self.operations.finish(&operation_id, result)?;
+        Ok(Response::new(DeleteResponse { operation_id }))
+    }
+
+    async fn apply(&self, request: Request<ApplyRequest>) -> Result<Response<ApplyResponse>, Status> {
+        let ApplyRequest {
+            services,
+            prune,
+            dry_run,
+            idempotency_key,
+        } = request.into_inner();
+        info!("Got apply request for {count} services", count = services.len());
+
+        let current = self.current_definitions()?;
+        let desired = services
+            .into_iter()
+            .map(ServiceDefinition::try_from)
+            .collect::<Result<_, _>>()
+            .map_err(|e| Status::new(Code::InvalidArgument, e))?;
+        let plan = services::plan(desired, &current, prune)
+            .map_err(|e| Status::new(Code::InvalidArgument, e))?;
+        let changes: Vec<ServiceChange> = plan.iter().map(ServiceChange::from).collect();
+        let changed: Vec<&ServiceDefinition> = plan.iter().filter_map(|change| change.definition.as_ref()).collect();
+        let deleted: Vec<&str> = plan
+            .iter()
+            .filter(|change| change.action == Action::Delete)
+            .map(|change| change.service_name.as_str())
+            .collect();
+        self.check_quota(&changed, &deleted)?;
+
+        if dry_run {
+            return Ok(Response::new(ApplyResponse {
+                operation_id: String::new(),
+                changes,
+            }));
+        }
+
+        let operation_id = match self.operations.begin("apply", "", &idempotency_key)? {
+            BeginOutcome::Started(id) => id,
+            BeginOutcome::Existing(record) => {
+                return record
+                    .replay()
+                    .map(|operation_id| Response::new(ApplyResponse { operation_id, changes }));
+            }
+        };
+
+        let result = self.apply_changes(&operation_id, &plan).await;
+        let operation_id = self.operations.finish(&operation_id, result)?;
+
+        Ok(Response::new(ApplyResponse { operation_id, changes }))
+    }
+
+    async fn update_service(
+        &self,
+        request: Request<UpdateServiceRequest>,
+    ) -> Result<Response<UpdateServiceResponse>, Status> {
+        info!("Got update request: {:?}", request.get_ref());
+
+        let request = request.into_inner();
+        let service_name = &request.service_name;
+
+        ServiceDefinition::legacy(service_name.clone())
+            .validate()
+            .map_err(|e| Status::new(Code::InvalidArgument, e))?;
+
+        if !self.file_manager.service_folder_exists(service_name.clone()) {
+            return Err(Status::new(
+                Code::NotFound,
+                format!("service '{service_name}' does not exist"),
+            ));
+        }
+
+        let current = self.read_definition(service_name)?;
+        let desired = current.with_changes(&request);
+        let changed_fields = changed_fields(validate(&desired)?, &current, &desired);
+        self.check_quota(&[&desired], &[])?;
+
+        let operation_id = match self.operations.begin("update", service_name, &request.idempotency_key)? {
+            BeginOutcome::Started(id) => id,
+            BeginOutcome::Existing(record) => {
+                return record.replay().map(|operation_id| {
+                    Response::new(UpdateServiceResponse { operation_id, changed_fields })
+                });
+            }
+        };
+
+        let result = match changed_fields.is_empty() {
+            true => Ok(()),
+            false => self.update_service(&operation_id, &desired, !request.skip_restart).await,
+        };
+        let operation_id = self.operations.finish(&operation_id, result)?;
+
+        Ok(Response::new(UpdateServiceResponse { operation_id, changed_fields }))
+    }
+
+    async fn list(&self, _request: Request<ListRequest>) -> Result<Response<ListResponse>, Status> {
+        info!("Got list request");
+
+        let services = self.file_manager.list_services().map_err(|e| {
+            Status::new(Code::Internal, format!("Failed to list services with error: {e}"))
+        })?;
+
+        Ok(Response::new(ListResponse {
+            services: services
+                .into_iter()
+                .map(|name| ServiceSummary { name })
+                .collect(),
+        }))
+    }
+
+    async fn describe(
+        &self,
+        request: Request<DescribeRequest>,
+    ) -> Result<Response<ServiceDescription>, Status> {
+        let service_name = &request.get_ref().service_name;
+        info!("Got describe request for: {}", service_name);
+
+        ServiceDefinition::legacy(service_name.clone())
+            .validate()
+            .map_err(|e| Status::new(Code::InvalidArgument, e))?;
+
+        if !self.file_manager.service_folder_exists(service_name.clone()) {
+            return Err(Status::new(
+                Code::NotFound,
+                format!("service '{service_name}' does not exist"),
+            ));
+        }
+
+        let definition = self.read_definition(service_name)?;
+        let blueprint = validate(&definition)?;
+        let parameters = blueprint.parameters(&definition);
+        let resources = &definition.resources;
+        let ports = self
+            .ports
+            .ports(service_name)
+            .into_iter()
+            .map(|(name, host_port)| PublishedPort {
+                container_port: blueprint.container_port(&name).map_or(0, u32::from),
+                name,
+                host_port: u32::from(host_port),
+            })
+            .collect();
+
+        Ok(Response::new(ServiceDescription {
+            name: definition.name.clone(),
+            blueprint: definition.blueprint.clone(),
+            parameters,
+            env_keys: definition.env.keys().cloned().collect(),
+            resources: Some(ResourceLimits {
+                cpus: resources.cpus.clone().unwrap_or_default(),
+                memory: resources.memory.clone().unwrap_or_default(),
+                pids: resources.pids.unwrap_or_default(),
+            }),
+            files: self.check_permissions(service_name),
+            ports,
+        }))
+    }
+
+    async fn backup(&self, request: Request<BackupRequest>) -> Result<Response<BackupResponse>, Status> {
+        info!("Got backup request: {:?}", request.get_ref());
+
+        let BackupRequest {
+            idempotency_key,
+            service_name,
+        } = request.into_inner();
+        let (definition, commands) = self.backup_target(&service_name)?;
+
+        let operation_id = match self.operations.begin("backup", &service_name, &idempotency_key)? {
+            BeginOutcome::Started(id) => id,
+            BeginOutcome::Existing(record) => {
+                // The backup is stored under the id of the operation that made it.
+                let operation_id = record.replay()?;
+                let backup = self.backups.get(&service_name, &operation_id).ok().flatten().map(Into::into);
+                return Ok(Response::new(BackupResponse { operation_id, backup }));
+            }
+        };
+
+        let result = self.backup_service(&operation_id, &definition, commands).await;
+        let backup = result.as_ref().ok().cloned().map(Into::into);
+        let operation_id = self.operations.finish(&operation_id, result.map(|_| ()))?;
+
+        Ok(Response::new(BackupResponse { operation_id, backup }))
+    }
+
+    async fn list_backups(
+        &self,
+        request: Request<ListBackupsRequest>,
+    ) -> Result<Response<ListBackupsResponse>, Status> {
+        let service_name = &request.get_ref().service_name;
+        info!("Got list backups request for: {}", service_name);
+
+        ServiceDefinition::legacy(service_name.clone())
+            .validate()
+            .map_err(|e| Status::new(Code::InvalidArgument, e))?;
+
+        // Backups outlive their service, so a deleted service still lists them.
+        let backups = self.backups.list(service_name).map_err(|e| {
+            Status::new(Code::Internal, format!("Failed to list backups of {service_name} with error: {e}"))
+        })?;
+
+        Ok(Response::new(ListBackupsResponse {
+            backups: backups.into_iter().map(Into::into).collect(),
+        }))
+    }
+
+    async fn restore(&self, request: Request<RestoreRequest>) -> Result<Response<RestoreResponse>, Status> {
+        info!("Got restore request: {:?}", request.get_ref());
+
+        let RestoreRequest {
+            idempotency_key,
+            service_name,
+            backup_id,
+        } = request.into_inner();
+        let (_, commands) = self.backup_target(&service_name)?;
+
+        let backup = self
+            .backups
+            .get(&service_name, &backup_id)
+            .map_err(|e| Status::new(Code::Internal, format!("Failed to read backup {backup_id} with error: {e}")))?
+            .ok_or_else(|| {
+                Status::new(
+                    Code::NotFound,
+                    format!("service '{service_name}' has no backup '{backup_id}'"),
+                )
+            })?;
+
+        if backup.engine != commands.engine {
+            return Err(Status::new(
+                Code::FailedPrecondition,
+                format!(
+                    "backup '{backup_id}' is a {engine} dump, service '{service_name}' runs {current}",
+                    engine = backup.engine,
+                    current = commands.engine
+                ),
+            ));
+        }
+
+        let operation_id = match self.operations.begin("restore", &service_name, &idempotency_key)? {
+            BeginOutcome::Started(id) => id,
+            BeginOutcome::Existing(record) => {
+                return record.replay().map(|operation_id| Response::new(RestoreResponse { operation_id }));
+            }
+        };
+
+        let step_name = "Restore Data";
+        info!("Running step '{step_name}' for: {service_name}", step_name = step_name, service_name = service_name);
+        let result = match self.operations.step_started(&operation_id, &service_name, step_name) {
+            Ok(()) => {
+                let result = self
+                    .backup_executor
+                    .restore(commands.container, commands.restore, self.backups.data_path(&service_name, &backup_id))
+                    .await;
+                self.backup_step(&operation_id, &service_name, step_name, result)
+            }
+            Err(status) => Err(status),
+        };
+        let operation_id = self.operations.finish(&operation_id, result)?;
+
+        Ok(Response::new(RestoreResponse { operation_id }))
+    }
+
+    async fn list_schedules(
+        &self,
+        request: Request<ListSchedulesRequest>,
+    ) -> Result<Response<ListSchedulesResponse>, Status> {
+        let service_name = &request.get_ref().service_name;
+        info!("Got list schedules request for: {}", service_name);
+
+        if !service_name.is_empty() {
+            self.existing_definition(service_name)?;
+        }
+
+        let now = unix_now();
+        let jobs = self
+            .scheduled_jobs()?
+            .into_iter()
+            .filter(|job| service_name.is_empty() || &job.service_name == service_name)
+            .map(|job| {
+                let next_run = self.schedules.next_run(&job, now).unwrap_or_default() as i64;
+                let last_run = self.schedules.last_run(&job);
+                let last_status = match &last_run {
+                    None => OperationStatus::Unspecified,
+                    Some(run) if run.succeeded => OperationStatus::Succeeded,
+                    Some(_) => OperationStatus::Failed,
+                };
+                let last_run = last_run.unwrap_or(LastRun {
+                    started_at: 0,
+                    succeeded: false,
+                    operation_id: String::new(),
+                    error: None,
+                });
+
+                ScheduledJob {
+                    service_name: job.service_name,
+                    schedule: Some(Schedule::from(&job.spec)),
+                    global: job.global,
+                    next_run,
+                    last_run: last_run.started_at as i64,
+                    last_status: last_status as i32,
+                    last_operation_id: last_run.operation_id,
+                    last_error: last_run.error.unwrap_or_default(),
+                }
+            })
+            .collect();
+
+        Ok(Response::new(ListSchedulesResponse { jobs }))
+    }
+
+    async fn get_health(&self, request: Request<GetHealthRequest>) -> Result<Response<GetHealthResponse>, Status> {
+        let service_name = &request.get_ref().service_name;
+        info!("Got get health request for: {}", service_name);
+
+        self.existing_definition(service_name)?;
+
+        // Services the monitor has not reached yet are checked on the spot.
+        if self.health.get(service_name).is_none() {
+            self.check_health(service_name).await?;
+        }
+
+        self.health
+            .get(service_name)
+            .map(Response::new)
+            .ok_or_else(|| Status::new(Code::Internal, format!("no health recorded for '{service_name}'")))
+    }
+
+    async fn logs(&self, request: Request<LogsRequest>) -> Result<Response<Self::LogsStream>, Status> {
+        info!("Got logs request: {:?}", request.get_ref());
+
+        let LogsRequest {
+            service_name,
+            follow,
+            since,
+            tail,
+            journal,
+        } = request.into_inner();
+        let definition = self.existing_definition(&service_name)?;
+        let container = validate(&definition)?.render_container(&definition);
+        let options = LogOptions { follow, since, tail };
+
+        let mut lines = self.log_executor.container_logs(container, options);
+        if journal {
+            lines = Box::pin(stream::select(lines, self.log_executor.journal(service_name.clone(), options)));
+        }
+        let to_status = move |e: LogExecutorError| e.to_status(&service_name, "Stream Logs");
+
+        if follow || !journal {
+            return Ok(Response::new(Box::pin(lines.map_err(to_status))));
+        }
+
+        // Without follow both sources are read to the end, so they can be
+        // merged in order rather than as they arrived.
+        let mut lines: Vec<LogLine> = lines.try_collect().await.map_err(to_status)?;
+        lines.sort_by_key(|line| line.timestamp_nanos);
+        if tail > 0 {
+            lines.drain(..lines.len().saturating_sub(tail as usize));
+        }
+        Ok(Response::new(Box::pin(stream::iter(lines.into_iter().map(Ok)))))
+    }
+
+    async fn exec(&self, request: Request<Streaming<ExecInput>>) -> Result<Response<Self::ExecStream>, Status> {
+        let metadata = request.metadata().clone();
+        let mut input = request.into_inner();
+
+        let start = match input.message().await? {
+            Some(ExecInput {
+                input: Some(exec_input::Input::Start(start)),
+            }) => start,
+            _ => return Err(Status::new(Code::InvalidArgument, "the first message has to start the command")),
+        };
+        info!("Got exec request: {:?}", start);
+
+        let service_name = start.service_name.clone();
+        let (session, entry) = self.start_exec(&metadata, start).await?;
+        let ExecSession { id, output, input: mut stdin } = session;
+
+        let exec_executor = self.exec_executor.clone();
+        let resize_id = id.clone();
+        tokio::spawn(async move {
+            // The client closing its side is the end of the command's input.
+            while let Ok(Some(message)) = input.message().await {
+                match message.input {
+                    Some(exec_input::Input::Stdin(bytes)) => {
+                        if stdin.write_all(&bytes).await.is_err() {
+                            break;
+                        }
+                    }
+                    Some(exec_input::Input::Resize(size)) => {
+                        if let Err(e) = exec_executor.resize(resize_id.clone(), size.rows, size.columns).await {
+                            warn!("Failed to resize exec {resize_id}: {e}");
+                        }
+                    }
+                    Some(exec_input::Input::CloseStdin(_)) => break,
+                    Some(exec_input::Input::Start(_)) | None => (),
+                }
+            }
+            let _ = stdin.shutdown().await;
+        });
+
+        let exec_executor = self.exec_executor.clone();
+        let audit = self.audit.clone();
+        let output_service = service_name.clone();
+        let output = output
+            .map_ok(|output| ExecOutput { output: Some(output) })
+            .map_err(move |e| e.to_status(&output_service, "Exec"));
+        let exit = stream::once(async move {
+            let exit_code = exec_executor
+                .exit_code(id)
+                .await
+                .map_err(|e| e.to_status(&service_name, "Exec"))?
+                .ok_or_else(|| Status::new(Code::Internal, "the command ended without an exit code"))?;
+            audit.record(&AuditEntry {
+                time: unix_now(),
+                action: "exec_finished",
+                exit_code: Some(exit_code),
+                ..entry
+            });
+            Ok(ExecOutput {
+                output: Some(exec_output::Output::ExitCode(exit_code)),
+            })
+        });
+
+        Ok(Response::new(Box::pin(output.chain(exit))))
+    }
+
+    async fn get_operation(
+        &self,
+        request: Request<GetOperationRequest>,
+    ) -> Result<Response<Operation>, Status> {
+        let operation_id = &request.get_ref().operation_id;
+        info!("Got get operation request for: {}", operation_id);
+
+        self.operations
+            .get(operation_id)
+            .map(Response::new)
+            .ok_or_else(|| {
+                Status::new(
+                    Code::NotFound,
+                    format!("no operation with id '{operation_id}' is in flight or recently finished"),
+                )
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executors::{ContainerStatus, HealthErrorType, HealthExecutorError, MockHealthExecutor, MockLogExecutor};
+    use crate::executors::{MockExecExecutor, MockVolumeExecutor, VolumeErrorType};
+    use libprovision::hello_world::LogSource;
+    use crate::executors::{MockBackupExecutor, MockCreateExecutor, MockDeleteExecutor, MockImageExecutor, MockUnitExecutor};
+    use crate::schedules::ScheduleSpec;
+    use crate::io::MockFileManager;
+    use futures_util::FutureExt;
+    use std::collections::BTreeSet;
+    use std::panic::AssertUnwindSafe;
+    use crate::permissions::FakePasswd;
+
+    fn provisioner(unit_executor: MockUnitExecutor) -> ProvisionerImpl {
+        ProvisionerImpl {
+            unit_executor: Arc::new(unit_executor),
+            health_check: HealthCheck {
+                interval: Duration::ZERO,
+                required_checks: 3,
+                timeout: Duration::from_secs(5),
+            },
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    pub async fn test_flapping_unit_needs_consecutive_checks() {
+        let mut states = vec![true, false, true, true, true].into_iter();
+        let mut unit_executor = MockUnitExecutor::new();
+        unit_executor
+            .expect_unit_active()
+            .times(5)
+            .returning(move |_| Ok(states.next().unwrap()));
+
+        let result = provisioner(unit_executor).wait_healthy("test_service").await;
+
+        assert!(result.is_ok(), "Unit should be healthy after three active checks in a row");
+    }
+
+    #[tokio::test]
+    pub async fn test_inactive_unit_times_out() {
+        let mut unit_executor = MockUnitExecutor::new();
+        unit_executor.expect_unit_active().returning(|_| Ok(false));
+
+        let mut provisioner = provisioner(unit_executor);
+        provisioner.health_check.timeout = Duration::from_millis(20);
+
+        let err = provisioner
+            .wait_healthy("test_service")
+            .await
+            .expect_err("Inactive unit should never be healthy");
+
+        assert_eq!(err.to_status("test_service", "Wait For Healthy").code(), Code::FailedPrecondition);
+    }
+
+    #[tokio::test]
+    pub async fn test_restart_restarts_unit() {
+        let mut unit_executor = MockUnitExecutor::new();
+        unit_executor.expect_reload_units().times(1).returning(|| Ok(()));
+        unit_executor
+            .expect_restart_unit()
+            .withf(|name| name == "test_service")
+            .times(1)
+            .returning(|_| Ok(()));
+        unit_executor.expect_unit_active().returning(|_| Ok(true));
+
+        let mut file_manager = MockFileManager::default();
+        file_manager.expect_service_folder_exists().returning(|_| true);
+        file_manager.expect_read_definition().returning(|_| Ok(None));
+
+        let provisioner = ProvisionerImpl {
+            file_manager: Arc::new(file_manager),
+            ..provisioner(unit_executor)
+        };
+
+        provisioner
+            .restart(Request::new(RestartRequest {
+                service_name: "test_service".to_owned(),
+                ..Default::default()
+            }))
+            .await
+            .expect("Restart should succeed");
+    }
+
+    /// Units that stop and come down without a fuss, for deletes.
+    fn stopped_units() -> Arc<MockUnitExecutor> {
+        let mut unit_executor = MockUnitExecutor::new();
+        unit_executor.expect_link_unit().returning(|_| Ok(()));
+        unit_executor.expect_unlink_unit().returning(|_| Ok(()));
+        unit_executor.expect_stop_unit().returning(|_| Ok(()));
+        unit_executor.expect_compose_down().returning(|_| Ok(()));
+        Arc::new(unit_executor)
+    }
+
+    fn dedicated_definition() -> ServiceDefinition {
+        let mut definition = ServiceDefinition::legacy("test_service".to_owned());
+        definition.dedicated_user = true;
+        definition
+    }
+
+    #[test]
+    pub fn test_dedicated_user_removed_when_create_fails() {
+        let passwd = Arc::new(FakePasswd::new(&[("server-daemon", 999)]));
+        let created = passwd.clone();
+        let mut create_executor = MockCreateExecutor::new();
+        create_executor.expect_create_folder().returning(move |_| {
+            assert_eq!(created.user_groups("svc-test_service").unwrap(), [DOCKER_GROUP], "The user should be able to use docker");
+            Err(CreateExecutorError::new(CreateErrorType::FolderCreateFailed, "disk full".to_owned()))
+        });
+
+        let provisioner = ProvisionerImpl {
+            create_executor: Arc::new(create_executor),
+            passwd: passwd.clone(),
+            ..Default::default()
+        };
+
+        let result = provisioner.create_service("op", &dedicated_definition());
+
+        assert!(result.is_err());
+        assert!(!passwd.exists("svc-test_service"), "The user should be removed when the create is unwound");
+    }
+
+    #[test]
+    pub fn test_failed_create_in_metrics() {
+        let mut create_executor = MockCreateExecutor::new();
+        create_executor.expect_create_folder().returning(|_| {
+            Err(CreateExecutorError::new(CreateErrorType::FolderCreateFailed, "disk full".to_owned()))
+        });
+        let mut file_manager = MockFileManager::default();
+        file_manager.expect_list_services().returning(|| Ok(vec!["test_service".to_owned()]));
+        file_manager.expect_read_definition().returning(|_| Ok(None));
+        file_manager.expect_service_folder_exists().returning(|_| false);
+
+        let provisioner = ProvisionerImpl {
+            create_executor: Arc::new(create_executor),
+            file_manager: Arc::new(file_manager),
+            passwd: Arc::new(FakePasswd::new(&[("server-daemon", 999)])),
+            ..Default::default()
+        }
+        .with_metrics(Arc::new(Metrics::default()));
+        provisioner.health.record("test_service", HealthCheckResult {
+            checked_at: 10,
+            status: HealthStatus::Unhealthy as i32,
+            unit_active: true,
+            container_state: "running".to_owned(),
+            container_health: "unhealthy".to_owned(),
+            ..Default::default()
+        });
+
+        let BeginOutcome::Started(id) = provisioner.operations.begin("create", "test_service", "").unwrap() else {
+            panic!("Operation should start");
+        };
+        assert!(provisioner.create_service(&id, &dedicated_definition()).is_err());
+
+        let text = provisioner.render_metrics();
+        for line in [
+            r#"provisiond_step_failures_total{step="Create Folder"} 1"#,
+            "provisiond_rollbacks_total 1",
+            r#"provisiond_service_unit_active{service="test_service"} 1"#,
+            r#"provisiond_service_container_healthy{service="test_service"} 0"#,
+        ] {
+            assert!(text.lines().any(|l| l == line), "Missing {line} in\n{text}");
+        }
+    }
+
+    #[test]
+    pub fn test_journal_and_metrics_set_in_any_order() {
+        let path = std::path::PathBuf::from("/tmp/provisiond_tests/test_journal_and_metrics_set_in_any_order");
+        let _ = std::fs::remove_dir_all(&path);
+        // Left running by an earlier run of the daemon.
+        let earlier = OperationStore::default().with_journal(OperationJournal::open(path.join("journal")).unwrap());
+        let _ = earlier.begin("create", "test_service", "").unwrap();
+        drop(earlier);
+
+        let provisioner = ProvisionerImpl::default()
+            .with_journal(OperationJournal::open(path.join("journal")).unwrap())
+            .with_metrics(Arc::new(Metrics::default()));
+        assert_eq!(provisioner.running_operations().len(), 1, "The journaled operation should still be running");
+
+        let BeginOutcome::Started(id) = provisioner.operations.begin("create", "other_service", "").unwrap() else {
+            panic!("Operation should start");
+        };
+        provisioner.operations.step_started(&id, "other_service", "Create Folder").unwrap();
+        provisioner.operations.step_finished(&id, "other_service", "Create Folder", Some("disk full".to_owned()));
+        let text = provisioner.render_metrics();
+        let line = r#"provisiond_step_failures_total{step="Create Folder"} 1"#;
+        assert!(text.lines().any(|l| l == line), "Missing {line} in\n{text}");
+        assert_eq!(OperationJournal::open(path.join("journal")).unwrap().interrupted().len(), 2);
+
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[tokio::test]
+    pub async fn test_create_over_quota_rejected() {
+        let mut existing = ServiceDefinition::legacy("existing".to_owned());
+        existing.resources.memory = Some("768m".to_owned());
+        let existing = existing.to_json();
+        let mut file_manager = MockFileManager::default();
+        file_manager.expect_list_services().returning(|| Ok(vec!["existing".to_owned()]));
+        file_manager.expect_read_definition().returning(move |_| Ok(Some(existing.clone())));
+
+        let provisioner = ProvisionerImpl {
+            file_manager: Arc::new(file_manager),
+            ..Default::default()
+        }
+        .with_quota(services::ResourceLimits {
+            memory: Some("1g".to_owned()),
+            ..Default::default()
+        });
+
+        let status = provisioner
+            .create(Request::new(CreateRequest {
+                service_name: "test_service".to_owned(),
+                resources: Some(ResourceLimits {
+                    memory: "512m".to_owned(),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }))
+            .await
+            .expect_err("Create should go over the quota");
+
+        assert_eq!(status.code(), Code::ResourceExhausted);
+        assert_eq!(status.message(), "memory limits would total 1280m, over the host quota of 1g");
+    }
+
+    #[tokio::test]
+    pub async fn test_create_without_free_port_rejected() {
+        let path = std::path::PathBuf::from("/tmp/provisiond_tests/test_create_without_free_port_rejected");
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        let ports = PortPool::load("20000-20000".parse().unwrap(), path.join("ports.json")).unwrap();
+        ports.allocate("existing", &["postgres"]).unwrap();
+        let mut file_manager = MockFileManager::default();
+        file_manager.expect_list_services().returning(|| Ok(vec!["existing".to_owned()]));
+        file_manager
+            .expect_read_definition()
+            .returning(|name| Ok(Some(ServiceDefinition::legacy(name.to_owned()).to_json())));
+        file_manager.expect_service_folder_exists().returning(|_| false);
+
+        let provisioner = ProvisionerImpl {
+            file_manager: Arc::new(file_manager),
+            ..Default::default()
+        }
+        .with_port_pool(ports);
+
+        let status = provisioner
+            .create(Request::new(CreateRequest {
+                service_name: "test_service".to_owned(),
+                ..Default::default()
+            }))
+            .await
+            .expect_err("Create should find no free port");
+
+        assert_eq!(status.code(), Code::ResourceExhausted);
+        assert!(provisioner.ports.ports("test_service").is_empty());
+        assert_eq!(provisioner.ports.ports("existing").get("postgres"), Some(&20000));
+
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[tokio::test]
+    pub async fn test_dependencies_reported_to_health_service() {
+        use tonic_health::pb::health_check_response::ServingStatus;
+        use tonic_health::pb::health_server::Health;
+        use tonic_health::pb::HealthCheckRequest;
+        use tonic_health::server::{HealthReporter, HealthService};
+
+        let mut health_executor = MockHealthExecutor::new();
+        health_executor
+            .expect_ping_docker()
+            .returning(|| Err(HealthExecutorError::new(HealthErrorType::DockerUnavailable, "no socket".to_owned())));
+        health_executor.expect_ping_systemd().returning(|| Ok(()));
+        health_executor.expect_check_root_writable().returning(|| Ok(()));
+        let provisioner = ProvisionerImpl {
+            health_executor: Arc::new(health_executor),
+            ..Default::default()
+        };
+
+        let reporter = HealthReporter::new();
+        let service = HealthService::from_health_reporter(reporter.clone());
+        let mut failing = BTreeSet::new();
+        crate::scheduler::update_dependencies(&provisioner, &reporter, &mut failing).await;
+
+        for (name, expected) in [
+            ("", ServingStatus::NotServing),
+            ("provision.Provisioner", ServingStatus::NotServing),
+            ("docker", ServingStatus::NotServing),
+            ("systemd", ServingStatus::Serving),
+            ("provisioning_root", ServingStatus::Serving),
+        ] {
+            let request = Request::new(HealthCheckRequest { service: name.to_owned() });
+            let response = service.check(request).await.unwrap().into_inner();
+            assert_eq!(response.status(), expected, "status of '{name}'");
+        }
+        assert_eq!(failing, BTreeSet::from(["docker"]));
+    }
+
+    #[test]
+    pub fn test_dedicated_user_deleted_with_service() {
+        let mut delete_executor = MockDeleteExecutor::new();
+        delete_executor.expect_delete_definition_file().returning(|_| Ok(()));
+        delete_executor.expect_delete_systemd_unit().returning(|_| Ok(()));
+        delete_executor.expect_delete_env_file().returning(|_| Ok(()));
+        delete_executor.expect_delete_compose_file().returning(|_| Ok(()));
+        delete_executor.expect_delete_folder().returning(|_| Ok(()));
+
+        let mut file_manager = MockFileManager::default();
+        file_manager
+            .expect_read_definition()
+            .returning(|_| Ok(Some(dedicated_definition().to_json())));
+
+        let passwd = Arc::new(FakePasswd::new(&[("server-daemon", 999), ("svc-test_service", 1000)]));
+
+        let provisioner = ProvisionerImpl {
+            delete_executor: Arc::new(delete_executor),
+            unit_executor: stopped_units(),
+            file_manager: Arc::new(file_manager),
+            passwd: passwd.clone(),
+            ..Default::default()
+        };
+
+        provisioner
+            .delete_service("op", &"test_service".to_owned())
+            .expect("Delete should succeed");
+
+        assert!(!passwd.exists("svc-test_service"), "The service's user should be deleted");
+        assert!(passwd.exists("server-daemon"));
+    }
+
+    #[tokio::test]
+    pub async fn test_volumes_purged_only_when_asked() {
+        let mut delete_executor = MockDeleteExecutor::new();
+        delete_executor.expect_delete_definition_file().returning(|_| Ok(()));
+        delete_executor.expect_delete_systemd_unit().returning(|_| Ok(()));
+        delete_executor.expect_delete_env_file().returning(|_| Ok(()));
+        delete_executor.expect_delete_compose_file().returning(|_| Ok(()));
+        delete_executor.expect_delete_folder().returning(|_| Ok(()));
+
+        let mut file_manager = MockFileManager::default();
+        file_manager
+            .expect_read_definition()
+            .returning(|name| Ok(Some(ServiceDefinition::legacy(name.to_owned()).to_json())));
+
+        let mut volume_executor = MockVolumeExecutor::new();
+        volume_executor
+            .expect_project_volumes()
+            .withf(|project| project == "purged")
+            .times(1)
+            .returning(|_| Ok(vec!["purged_data".to_owned()]));
+        volume_executor
+            .expect_remove_volume()
+            .withf(|name| name == "purged_data")
+            .times(1)
+            .returning(|_| Ok(()));
+
+        let provisioner = ProvisionerImpl {
+            delete_executor: Arc::new(delete_executor),
+            unit_executor: stopped_units(),
+            file_manager: Arc::new(file_manager),
+            volume_executor: Arc::new(volume_executor),
+            ..Default::default()
+        };
+
+        for (service_name, purge_volumes) in [("kept", false), ("Purged", true)] {
+            provisioner
+                .delete(Request::new(DeleteRequest {
+                    service_name: service_name.to_owned(),
+                    purge_volumes,
+                    ..Default::default()
+                }))
+                .await
+                .expect("Delete should succeed");
+        }
+    }
+
+    #[test]
+    pub fn test_delete_rejects_path_in_name() {
+        // No expectations, any call to the executors fails the test.
+        let provisioner = ProvisionerImpl {
+            delete_executor: Arc::new(MockDeleteExecutor::new()),
+            file_manager: Arc::new(MockFileManager::default()),
+            ..Default::default()
+        };
+
+        let status = provisioner
+            .delete_service("op", &"../..".to_owned())
+            .expect_err("A name with a path should be rejected");
+
+        assert_eq!(status.code(), Code::InvalidArgument);
+    }
+
+    #[tokio::test]
+    pub async fn test_containers_down_before_files_and_volume_in_use_reported() {
+        let mut order = mockall::Sequence::new();
+        let mut unit_executor = MockUnitExecutor::new();
+        unit_executor.expect_stop_unit().times(1).in_sequence(&mut order).returning(|_| Ok(()));
+        unit_executor.expect_unlink_unit().times(1).in_sequence(&mut order).returning(|_| Ok(()));
+        unit_executor.expect_compose_down().times(1).in_sequence(&mut order).returning(|_| Ok(()));
+        let mut delete_executor = MockDeleteExecutor::new();
+        delete_executor.expect_delete_definition_file().times(1).in_sequence(&mut order).returning(|_| Ok(()));
+        delete_executor.expect_delete_systemd_unit().returning(|_| Ok(()));
+        delete_executor.expect_delete_env_file().returning(|_| Ok(()));
+        delete_executor.expect_delete_compose_file().returning(|_| Ok(()));
+        delete_executor.expect_delete_folder().returning(|_| Ok(()));
+
+        let mut file_manager = MockFileManager::default();
+        file_manager.expect_read_definition().returning(|_| Ok(None));
+
+        // Another container outside the service still mounts the volume.
+        let mut volume_executor = MockVolumeExecutor::new();
+        volume_executor.expect_project_volumes().returning(|_| Ok(vec!["test_service_data".to_owned()]));
+        volume_executor.expect_remove_volume().returning(|name| {
+            Err(VolumeExecutorError::new(VolumeErrorType::VolumeInUse, format!("volume {name} is in use")))
+        });
+
+        let provisioner = ProvisionerImpl {
+            delete_executor: Arc::new(delete_executor),
+            unit_executor: Arc::new(unit_executor),
+            file_manager: Arc::new(file_manager),
+            volume_executor: Arc::new(volume_executor),
+            ..Default::default()
+        };
+
+        let status = provisioner
+            .delete(Request::new(DeleteRequest {
+                service_name: "test_service".to_owned(),
+                purge_volumes: true,
+                ..Default::default()
+            }))
+            .await
+            .expect_err("A volume in use should fail the delete");
+
+        assert_eq!(status.code(), Code::FailedPrecondition);
+    }
+
+    fn interrupted(kind: &str, steps: &[(&str, &str)]) -> InterruptedOperation {
+        serde_json::from_value(serde_json::json!({
+            "id": "interrupted-op",
+            "kind": kind,
+            "service_name": "test_service",
+            "steps": steps
+                .iter()
+                .map(|(name, status)| serde_json::json!({"name": name, "service_name": "test_service", "status": status}))
+                .collect::<Vec<_>>(),
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    pub async fn test_interrupted_create_rolled_back() {
+        let mut sequence = mockall::Sequence::new();
+        let mut delete_executor = MockDeleteExecutor::new();
+        delete_executor.expect_delete_env_file().times(1).in_sequence(&mut sequence).returning(|_| Ok(()));
+        delete_executor.expect_delete_compose_file().times(1).in_sequence(&mut sequence).returning(|_| Ok(()));
+        delete_executor.expect_delete_folder().times(1).in_sequence(&mut sequence).returning(|_| Ok(()));
+        delete_executor.expect_delete_systemd_unit().never();
+
+        let provisioner = ProvisionerImpl {
+            delete_executor: Arc::new(delete_executor),
+            ..Default::default()
+        };
+
+        let operation = interrupted("create", &[
+            ("Create Folder", "succeeded"),
+            ("Create Compose File", "succeeded"),
+            ("Create Env File", "running"),
+        ]);
+        provisioner.recover(&operation).await;
+
+        let recorded = provisioner.operations.get("interrupted-op").expect("Interrupted operation should be kept");
+        assert_eq!(recorded.status, OperationStatus::Failed as i32);
+        assert_eq!(recorded.error, "interrupted and rolled back");
+    }
+
+    #[tokio::test]
+    pub async fn test_interrupted_delete_finished() {
+        let mut delete_executor = MockDeleteExecutor::new();
+        delete_executor.expect_delete_definition_file().returning(|_| {
+            Err(DeleteExecutorError::new(DeleteErrorType::DefinitionFileDoesNotExist, "gone".to_owned()))
+        });
+        delete_executor.expect_delete_systemd_unit().times(1).returning(|_| Ok(()));
+        delete_executor.expect_delete_env_file().times(1).returning(|_| Ok(()));
+        delete_executor.expect_delete_compose_file().times(1).returning(|_| Ok(()));
+        delete_executor.expect_delete_folder().times(1).returning(|_| Ok(()));
+        let mut file_manager = MockFileManager::default();
+        file_manager.expect_read_definition().returning(|_| Ok(None));
+
+        let provisioner = ProvisionerImpl {
+            delete_executor: Arc::new(delete_executor),
+            unit_executor: stopped_units(),
+            file_manager: Arc::new(file_manager),
+            ..Default::default()
+        };
+
+        let operation = interrupted("delete", &[("Delete Definition File", "succeeded"), ("Delete Unit File", "running")]);
+        provisioner.recover(&operation).await;
+
+        let recorded = provisioner.operations.get("interrupted-op").unwrap();
+        assert!(
+            recorded.error.starts_with("interrupted, the delete was finished by operation"),
+            "Unexpected error: {}",
+            recorded.error
+        );
+    }
+
+    /// A service's files kept in memory, for cutting operations short. The
+    /// daemon "crashes" by unwinding out of the executor call `crash_at`
+    /// counts down to, before or after the call took effect.
+    #[derive(Clone, Default)]
+    struct FakeHost {
+        files: Arc<std::sync::Mutex<BTreeMap<String, String>>>,
+        crash_at: Arc<std::sync::Mutex<Option<usize>>>,
+        crash_after_effect: bool,
+    }
+
+    impl FakeHost {
+        /// A service written with `contents`, with backups left from the
+        /// update before holding "older".
+        fn with_files(contents: &str) -> Self {
+            let host = FakeHost::default();
+            let mut files = host.files.lock().unwrap();
+            for key in ["folder", "compose", "env", "unit", "definition"] {
+                files.insert(key.to_owned(), contents.to_owned());
+            }
+            for key in ["compose", "env", "unit", "definition"] {
+                files.insert(format!("{key}.bak"), "older".to_owned());
+            }
+            drop(files);
+            host
+        }
+
+        fn files(&self) -> BTreeMap<String, String> {
+            self.files.lock().unwrap().clone()
+        }
+
+        fn call<T>(&self, effect: impl FnOnce(&mut BTreeMap<String, String>) -> T) -> T {
+            let crash = match self.crash_at.lock().unwrap().as_mut() {
+                Some(0) => true,
+                Some(calls) => {
+                    *calls -= 1;
+                    false
+                }
+                None => false,
+            };
+            if crash && !self.crash_after_effect {
+                std::panic::resume_unwind(Box::new("crash"));
+            }
+            let result = effect(&mut self.files.lock().unwrap());
+            if crash {
+                std::panic::resume_unwind(Box::new("crash"));
+            }
+            result
+        }
+
+        fn create(&self, key: &str, contents: &str, exists: CreateErrorType) -> Result<(), CreateExecutorError> {
+            self.call(|files| match files.contains_key(key) {
+                true => Err(CreateExecutorError::new(exists, key.to_owned())),
+                false => {
+                    files.insert(key.to_owned(), contents.to_owned());
+                    Ok(())
+                }
+            })
+        }
+
+        fn delete(&self, key: &str, missing: DeleteErrorType) -> Result<(), DeleteExecutorError> {
+            self.call(|files| match files.remove(key) {
+                Some(_) => Ok(()),
+                None => Err(DeleteExecutorError::new(missing, key.to_owned())),
+            })
+        }
+    }
+
+    fn file_key(file: ServiceFile) -> &'static str {
+        match file {
+            ServiceFile::Compose => "compose",
+            ServiceFile::Env => "env",
+            ServiceFile::Unit => "unit",
+            ServiceFile::Definition => "definition",
+        }
+    }
+
+    impl CreateExecutor for FakeHost {
+        fn create_folder(&self, _: String) -> Result<(), CreateExecutorError> {
+            self.create("folder", "", CreateErrorType::FolderExists)
+        }
+        fn create_compose_file(&self, _: String, contents: &str) -> Result<(), CreateExecutorError> {
+            self.create("compose", contents, CreateErrorType::ComposeFileExists)
+        }
+        fn create_env_file(&self, _: String, contents: &str) -> Result<(), CreateExecutorError> {
+            self.create("env", contents, CreateErrorType::EnvFileExists)
+        }
+        fn create_systemd_unit(&self, _: String, contents: &str) -> Result<(), CreateExecutorError> {
+            self.create("unit", contents, CreateErrorType::UnitFileExists)
+        }
+        fn create_definition_file(&self, _: String, contents: &str) -> Result<(), CreateExecutorError> {
+            self.create("definition", contents, CreateErrorType::DefinitionFileExists)
+        }
+        fn replace_file(&self, _: String, file: ServiceFile, contents: &str) -> Result<(), CreateExecutorError> {
+            let key = file_key(file);
+            self.call(|files| {
+                if let Some(previous) = files.insert(key.to_owned(), contents.to_owned()) {
+                    files.insert(format!("{key}.bak"), previous);
+                }
+                Ok(())
+            })
+        }
+        fn restore_file(&self, _: String, file: ServiceFile) -> Result<(), CreateExecutorError> {
+            let key = file_key(file);
+            self.call(|files| {
+                match files.remove(&format!("{key}.bak")) {
+                    Some(previous) => files.insert(key.to_owned(), previous),
+                    None => files.remove(key),
+                };
+                Ok(())
+            })
+        }
+        fn back_up_file(&self, _: String, file: ServiceFile) -> Result<(), CreateExecutorError> {
+            let key = file_key(file);
+            self.call(|files| {
+                match files.get(key).cloned() {
+                    Some(current) => files.insert(format!("{key}.bak"), current),
+                    None => files.remove(&format!("{key}.bak")),
+                };
+                Ok(())
+            })
+        }
+        fn set_permissions(&self, _: String, _: Owner) -> Result<(), CreateExecutorError> {
+            self.call(|_| Ok(()))
+        }
+    }
+
+    impl DeleteExecutor for FakeHost {
+        fn delete_folder(&self, _: String) -> Result<(), DeleteExecutorError> {
+            self.call(|files| match files.contains_key("folder") {
+                true => {
+                    files.clear();
+                    Ok(())
+                }
+                false => Err(DeleteExecutorError::new(DeleteErrorType::FolderDoesNotExist, "folder".to_owned())),
+            })
+        }
+        fn delete_compose_file(&self, _: String) -> Result<(), DeleteExecutorError> {
+            self.delete("compose", DeleteErrorType::ComposeFileDoesNotExist)
+        }
+        fn delete_env_file(&self, _: String) -> Result<(), DeleteExecutorError> {
+            self.delete("env", DeleteErrorType::EnvFileDoesNotExist)
+        }
+        fn delete_systemd_unit(&self, _: String) -> Result<(), DeleteExecutorError> {
+            self.delete("unit", DeleteErrorType::UnitFileDoesNotExist)
+        }
+        fn delete_definition_file(&self, _: String) -> Result<(), DeleteExecutorError> {
+            self.delete("definition", DeleteErrorType::DefinitionFileDoesNotExist)
+        }
+    }
+
+    fn journaled(host: &FakeHost, journal: &std::path::Path) -> ProvisionerImpl {
+        let mut file_manager = MockFileManager::default();
+        file_manager.expect_read_definition().returning(|_| Ok(None));
+        let files = host.files.clone();
+        file_manager
+            .expect_service_folder_exists()
+            .returning(move |_| files.lock().unwrap().contains_key("folder"));
+
+        ProvisionerImpl {
+            create_executor: Arc::new(host.clone()),
+            delete_executor: Arc::new(host.clone()),
+            unit_executor: stopped_units(),
+            file_manager: Arc::new(file_manager),
+            passwd: Arc::new(FakePasswd::new(&[("server-daemon", 999)])),
+            ..Default::default()
+        }
+        .with_journal(OperationJournal::open(journal.to_owned()).unwrap())
+    }
+
+    /// Runs `operation` on `host`, crashing at every executor call in turn,
+    /// both before and after the call takes effect. After each crash the
+    /// daemon starts again from the journal and recovers, and the host has to
+    /// end up as `consistent` says. Returns how many crashes were tried.
+    async fn crash_at_every_step(
+        name: &str,
+        initial: FakeHost,
+        operation: impl AsyncFn(&ProvisionerImpl, String) -> Result<(), Status>,
+        consistent: impl Fn(&BTreeMap<String, String>) -> bool,
+    ) -> usize {
+        let path = std::path::PathBuf::from(format!("/tmp/provisiond_tests/{name}"));
+        let mut crashes = 0;
+
+        for crash_after_effect in [false, true] {
+            for crash_at in 0.. {
+                let _ = std::fs::remove_dir_all(&path);
+                let host = FakeHost {
+                    files: Arc::new(std::sync::Mutex::new(initial.files())),
+                    crash_at: Arc::new(std::sync::Mutex::new(Some(crash_at))),
+                    crash_after_effect,
+                };
+
+                let provisioner = journaled(&host, &path.join("journal"));
+                let BeginOutcome::Started(id) = provisioner.operations.begin("test", "test_service", "").unwrap() else {
+                    panic!("Operation should start");
+                };
+                let run = AssertUnwindSafe(operation(&provisioner, id.clone())).catch_unwind().await;
+                let crashed = match run {
+                    Ok(result) => {
+                        provisioner.operations.finish(&id, result).unwrap();
+                        false
+                    }
+                    Err(_) => true,
+                };
+                drop(provisioner);
+
+                *host.crash_at.lock().unwrap() = None;
+                let restarted = journaled(&host, &path.join("journal"));
+                for operation in restarted.running_operations() {
+                    restarted.recover(&operation).await;
+                }
+
+                let files = host.files();
+                assert!(restarted.running_operations().is_empty());
+                assert!(
+                    consistent(&files),
+                    "Crash at call {crash_at} (after effect: {crash_after_effect}) left {files:?}"
+                );
+                if !crashed {
+                    break;
+                }
+                crashes += 1;
+            }
+        }
+
+        std::fs::remove_dir_all(&path).unwrap();
+        crashes
+    }
+
+    #[tokio::test]
+    pub async fn test_create_recovered_after_crash_at_every_step() {
+        let definition = ServiceDefinition::legacy("test_service".to_owned());
+
+        let crashes = crash_at_every_step(
+            "test_create_recovered_after_crash_at_every_step",
+            FakeHost::default(),
+            async |provisioner, id| provisioner.create_service(&id, &definition),
+            // Either the whole service or nothing of it.
+            |files| files.is_empty() || files.len() == 5,
+        )
+        .await;
+
+        assert_eq!(crashes, 12, "Six steps, crashed before and after each");
+    }
+
+    #[test]
+    pub fn test_second_create_keeps_ports_of_existing_service() {
+        let path = std::path::PathBuf::from("/tmp/provisiond_tests/test_second_create_keeps_ports_of_existing_service");
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        let ports = PortPool::load("20000-20001".parse().unwrap(), path.join("ports.json")).unwrap();
+        let provisioner = journaled(&FakeHost::default(), &path.join("journal")).with_port_pool(ports);
+        let definition = ServiceDefinition::legacy("test_service".to_owned());
+
+        let create = || {
+            let BeginOutcome::Started(id) = provisioner.operations.begin("create", "test_service", "").unwrap() else {
+                panic!("Operation should start");
+            };
+            provisioner.create_service(&id, &definition)
+        };
+        create().expect("First create should succeed");
+        let held = provisioner.ports.ports("test_service");
+        assert_eq!(held.len(), 1);
+
+        let status = create().expect_err("Second create should find the service");
+
+        assert_eq!(status.code(), Code::AlreadyExists);
+        assert_eq!(provisioner.ports.ports("test_service"), held, "The existing service should keep its ports");
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[tokio::test]
+    pub async fn test_update_allocates_ports_of_service_from_before_pool() {
+        let path = std::path::PathBuf::from("/tmp/provisiond_tests/test_update_allocates_ports_of_service_from_before_pool");
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        let ports = PortPool::load("20000-20000".parse().unwrap(), path.join("ports.json")).unwrap();
+        ports.allocate("existing", &["postgres"]).unwrap();
+        let host = FakeHost::with_files("old");
+        let provisioner = journaled(&host, &path.join("journal")).with_port_pool(ports);
+        let definition = ServiceDefinition::legacy("test_service".to_owned());
+
+        let update = async || {
+            let BeginOutcome::Started(id) = provisioner.operations.begin("update", "test_service", "").unwrap() else {
+                panic!("Operation should start");
+            };
+            let result = provisioner.update_service(&id, &definition, false).await;
+            let steps: Vec<_> = provisioner.operations.get(&id).unwrap().steps.into_iter().map(|step| step.name).collect();
+            (result, steps)
+        };
+
+        let (result, steps) = update().await;
+        assert_eq!(result.unwrap_err().code(), Code::ResourceExhausted);
+        assert_eq!(steps, ["Allocate Ports"], "Nothing should be written without ports");
+        assert_eq!(host.files.lock().unwrap()["compose"], "old");
+
+        provisioner.ports.release("existing").unwrap();
+        let (result, steps) = update().await;
+        result.expect("Update should succeed once a port is free");
+        assert_eq!(steps.first().map(String::as_str), Some("Allocate Ports"));
+        assert_eq!(provisioner.ports.ports("test_service").get("postgres"), Some(&20000));
+
+        // The port is held from here on, a rollback must not give it back.
+        let (result, steps) = update().await;
+        result.expect("Second update should succeed");
+        assert!(!steps.iter().any(|step| step == "Allocate Ports"), "Ports already held: {steps:?}");
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    pub fn test_unit_linked_on_create_and_unlinked_on_rollback() {
+        let mut order = mockall::Sequence::new();
+        let mut create_executor = MockCreateExecutor::new();
+        let mut unit_executor = MockUnitExecutor::new();
+        create_executor.expect_create_folder().returning(|_| Ok(()));
+        create_executor.expect_create_compose_file().returning(|_, _| Ok(()));
+        create_executor.expect_create_env_file().returning(|_, _| Ok(()));
+        create_executor.expect_create_systemd_unit().times(1).in_sequence(&mut order).returning(|_, _| Ok(()));
+        unit_executor.expect_link_unit().times(1).in_sequence(&mut order).returning(|_| Ok(()));
+        create_executor.expect_create_definition_file().times(1).in_sequence(&mut order).returning(|_, _| {
+            Err(CreateExecutorError::new(CreateErrorType::FileCreateFailed, "disk full".to_owned()))
+        });
+        unit_executor.expect_unlink_unit().times(1).in_sequence(&mut order).returning(|_| Ok(()));
+
+        let mut delete_executor = MockDeleteExecutor::new();
+        delete_executor.expect_delete_systemd_unit().times(1).returning(|_| Ok(()));
+        delete_executor.expect_delete_env_file().returning(|_| Ok(()));
+        delete_executor.expect_delete_compose_file().returning(|_| Ok(()));
+        delete_executor.expect_delete_folder().returning(|_| Ok(()));
+        let mut file_manager = MockFileManager::default();
+        file_manager.expect_service_folder_exists().returning(|_| false);
+
+        let provisioner = ProvisionerImpl {
+            create_executor: Arc::new(create_executor),
+            delete_executor: Arc::new(delete_executor),
+            unit_executor: Arc::new(unit_executor),
+            file_manager: Arc::new(file_manager),
+            passwd: Arc::new(FakePasswd::new(&[("server-daemon", 999)])),
+            ..Default::default()
+        };
+
+        let status = provisioner
+            .create_service("op", &ServiceDefinition::legacy("test_service".to_owned()))
+            .expect_err("Create should fail at the definition file");
+
+        assert!(status.message().contains("disk full"), "{}", status.message());
+    }
+
+    #[tokio::test]
+    pub async fn test_update_recovered_after_crash_at_every_step() {
+        let mut definition = ServiceDefinition::legacy("test_service".to_owned());
+        definition.env.insert("POSTGRES_DB".to_owned(), "new".to_owned());
+
+        crash_at_every_step(
+            "test_update_recovered_after_crash_at_every_step",
+            FakeHost::with_files("old"),
+            async |provisioner, id| provisioner.update_service(&id, &definition, false).await,
+            // Either every file is the previous version or none is.
+            |files| {
+                let current: Vec<_> = ["compose", "env", "unit", "definition"].iter().map(|key| &files[*key]).collect();
+                current.iter().all(|contents| *contents == "old")
+                    || current.iter().all(|contents| !["old", "older"].contains(&contents.as_str()))
+            },
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    pub async fn test_delete_recovered_after_crash_at_every_step() {
+        crash_at_every_step(
+            "test_delete_recovered_after_crash_at_every_step",
+            FakeHost::with_files("old"),
+            async |provisioner, id| provisioner.delete_service(&id, &"test_service".to_owned()),
+            |files| files.is_empty(),
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    pub async fn test_backups_stored_and_pruned() {
+        let path = std::path::PathBuf::from("/tmp/provisiond_tests/test_backups_stored_and_pruned");
+        let _ = std::fs::remove_dir_all(&path);
+
+        let mut backup_executor = MockBackupExecutor::new();
+        backup_executor.expect_dump().returning(|container, command, destination| {
+            assert_eq!(container, "test_service-service.db");
+            assert_eq!(command[0], "pg_dump");
+            std::fs::write(destination, "dump").unwrap();
+            Ok(4)
+        });
+
+        let mut file_manager = MockFileManager::default();
+        file_manager.expect_service_folder_exists().returning(|_| true);
+        file_manager.expect_read_definition().returning(|_| Ok(None));
+
+        let provisioner = ProvisionerImpl {
+            backup_executor: Arc::new(backup_executor),
+            file_manager: Arc::new(file_manager),
+            backups: BackupStore::new(path.clone(), 2),
+            ..Default::default()
+        };
+
+        let mut backup_ids = Vec::new();
+        for _ in 0..3 {
+            let response = provisioner
+                .backup(Request::new(BackupRequest {
+                    service_name: "test_service".to_owned(),
+                    ..Default::default()
+                }))
+                .await
+                .expect("Backup should succeed");
+            backup_ids.push(response.into_inner().backup.expect("Backup should be returned").backup_id);
+        }
+
+        let listed: Vec<String> = provisioner
+            .backups
+            .list("test_service")
+            .unwrap()
+            .into_iter()
+            .map(|backup| backup.backup_id)
+            .collect();
+
+        assert_eq!(listed.len(), 2, "Only the newest two backups should be kept");
+        assert!(listed.contains(&backup_ids[2]), "The newest backup should be kept");
+
+        std::fs::remove_dir_all(&path)
+            .unwrap_or_else(|_| panic!("Failed to delete test path at {}", path.display()));
+    }
+
+    #[tokio::test]
+    pub async fn test_scheduled_pull_restarts_on_new_image() {
+        let mut ids = vec![Some("sha256:old".to_owned()), Some("sha256:new".to_owned())].into_iter();
+        let mut image_executor = MockImageExecutor::new();
+        image_executor.expect_image_id().times(2).returning(move |_| Ok(ids.next().unwrap()));
+        image_executor.expect_pull_image().times(1).returning(|_| Ok(()));
+
+        let mut unit_executor = MockUnitExecutor::new();
+        unit_executor.expect_reload_units().times(1).returning(|| Ok(()));
+        unit_executor.expect_restart_unit().times(1).returning(|_| Ok(()));
+        unit_executor.expect_unit_active().returning(|_| Ok(true));
+
+        let mut file_manager = MockFileManager::default();
+        file_manager.expect_service_folder_exists().returning(|_| true);
+        file_manager.expect_read_definition().returning(|_| Ok(None));
+
+        let provisioner = ProvisionerImpl {
+            image_executor: Arc::new(image_executor),
+            file_manager: Arc::new(file_manager),
+            ..provisioner(unit_executor)
+        };
+        let job = Job {
+            service_name: "test_service".to_owned(),
+            spec: ScheduleSpec {
+                job: JobKind::Pull,
+                cron: "@weekly".to_owned(),
+                jitter_seconds: 0,
+                restart_on_change: true,
+            },
+            global: false,
+        };
+
+        provisioner.run_job(&job).await;
+
+        let last_run = provisioner.schedules.last_run(&job).expect("The run should be recorded");
+        assert!(last_run.succeeded, "Run should succeed: {:?}", last_run.error);
+        let operation = provisioner.operations.get(&last_run.operation_id).expect("The run should be an operation");
+        let steps: Vec<&str> = operation.steps.iter().map(|step| step.name.as_str()).collect();
+        assert_eq!(steps, vec!["Pull Image", "Reload Units", "Restart Unit", "Wait For Healthy"]);
+    }
+
+    #[tokio::test]
+    pub async fn test_unhealthy_service_restarted() {
+        let mut health_executor = MockHealthExecutor::new();
+        health_executor.expect_container_status().returning(|_| {
+            Ok(Some(ContainerStatus {
+                state: "running".to_owned(),
+                health: String::new(),
+                address: Some("172.18.0.2".parse().unwrap()),
+            }))
+        });
+        health_executor
+            .expect_probe()
+            .returning(|_, _| Err(HealthExecutorError::new(HealthErrorType::ProbeFailed, "connection refused".to_owned())));
+
+        let mut unit_executor = MockUnitExecutor::new();
+        unit_executor.expect_unit_active().returning(|_| Ok(true));
+        unit_executor.expect_reload_units().times(1).returning(|| Ok(()));
+        unit_executor.expect_restart_unit().times(1).returning(|_| Ok(()));
+
+        let mut file_manager = MockFileManager::default();
+        file_manager.expect_service_folder_exists().returning(|_| true);
+        file_manager.expect_read_definition().returning(|_| Ok(None));
+
+        let provisioner = ProvisionerImpl {
+            health_executor: Arc::new(health_executor),
+            file_manager: Arc::new(file_manager),
+            restart_unhealthy: 2,
+            ..provisioner(unit_executor)
+        };
+
+        for _ in 0..3 {
+            let result = provisioner.monitor_service("test_service").await;
+            assert_eq!(result.expect_err("Service should be unhealthy").code(), Code::FailedPrecondition);
+        }
+
+        let health = provisioner.health.get("test_service").expect("Checks should be recorded");
+        assert_eq!(health.history.len(), 3);
+        assert_eq!(health.status(), HealthStatus::Unhealthy);
+        assert_eq!(health.history[0].probe, "tcp://172.18.0.2:5432");
+        assert!(health.history[0].error.contains("connection refused"));
+    }
+
+    fn log_line(timestamp_nanos: i64, source: LogSource, message: &str) -> Result<LogLine, LogExecutorError> {
+        Ok(LogLine {
+            timestamp_nanos,
+            source: source as i32,
+            origin: String::new(),
+            message: message.to_owned(),
+        })
+    }
+
+    #[tokio::test]
+    pub async fn test_logs_merged_with_journal() {
+        let mut log_executor = MockLogExecutor::new();
+        log_executor.expect_container_logs().returning(|container, _| {
+            assert_eq!(container, "test_service-service.db");
+            Box::pin(stream::iter(vec![
+                log_line(1, LogSource::Stdout, "starting"),
+                log_line(4, LogSource::Stderr, "ready"),
+            ]))
+        });
+        log_executor.expect_journal().returning(|_, _| {
+            Box::pin(stream::iter(vec![
+                log_line(2, LogSource::Journal, "Started test_service.service"),
+                log_line(3, LogSource::Journal, "Reloading"),
+            ]))
+        });
+
+        let mut file_manager = MockFileManager::default();
+        file_manager.expect_service_folder_exists().returning(|_| true);
+        file_manager.expect_read_definition().returning(|_| Ok(None));
+
+        let provisioner = ProvisionerImpl {
+            log_executor: Arc::new(log_executor),
+            file_manager: Arc::new(file_manager),
+            ..Default::default()
+        };
+
+        let lines: Vec<String> = provisioner
+            .logs(Request::new(LogsRequest {
+                service_name: "test_service".to_owned(),
+                tail: 3,
+                journal: true,
+                ..Default::default()
+            }))
+            .await
+            .expect("Logs should stream")
+            .into_inner()
+            .map_ok(|line| line.message)
+            .try_collect()
+            .await
+            .expect("Every line should be read");
+
+        assert_eq!(lines, vec!["Started test_service.service", "Reloading", "ready"]);
+    }
+
+    fn exec_provisioner(exec_executor: MockExecExecutor, audit_path: &std::path::Path) -> ProvisionerImpl {
+        let mut file_manager = MockFileManager::default();
+        file_manager.expect_service_folder_exists().returning(|_| true);
+        file_manager.expect_read_definition().returning(|_| Ok(None));
+
+        let policy = serde_json::from_str(
+            r#"{"subjects": [{"name": "ops", "token": "ops-token", "exec": {"services": ["*"], "commands": ["psql"]}}]}"#,
+        )
+        .unwrap();
+
+        ProvisionerImpl {
+            exec_executor: Arc::new(exec_executor),
+            file_manager: Arc::new(file_manager),
+            ..Default::default()
+        }
+        .with_policy(Arc::new(policy))
+        .with_audit_log(AuditLog::new(audit_path.to_owned()))
+    }
+
+    fn exec_metadata(token: &str) -> MetadataMap {
+        let mut metadata = MetadataMap::new();
+        metadata.insert("authorization", format!("Bearer {token}").parse().unwrap());
+        metadata
+    }
+
+    fn exec_start(compose_service: &str, command: &str) -> ExecStart {
+        ExecStart {
+            service_name: "test_service".to_owned(),
+            compose_service: compose_service.to_owned(),
+            command: vec![command.to_owned()],
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    pub async fn test_exec_denied_and_audited() {
+        let path = std::path::PathBuf::from("/tmp/provisiond_tests/test_exec_denied_and_audited");
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).expect("Failed to create test path");
+
+        // Nothing may reach the container.
+        let provisioner = exec_provisioner(MockExecExecutor::new(), &path.join("audit.log"));
+
+        let status = provisioner.start_exec(&exec_metadata("ops-token"), exec_start("", "sh")).await.err().unwrap();
+        assert_eq!(status.code(), Code::PermissionDenied);
+        let status = provisioner.start_exec(&exec_metadata("wrong"), exec_start("", "psql")).await.err().unwrap();
+        assert_eq!(status.code(), Code::Unauthenticated);
+
+        let audit = std::fs::read_to_string(path.join("audit.log")).unwrap();
+        let entries: Vec<serde_json::Value> = audit.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0]["subject"], "ops");
+        assert_eq!(entries[0]["command"][0], "sh");
+        assert_eq!(entries[0]["allowed"], false);
+        assert_eq!(entries[1]["subject"], "");
+
+        std::fs::remove_dir_all(&path)
+            .unwrap_or_else(|_| panic!("Failed to delete test path at {}", path.display()));
+    }
+
+    #[tokio::test]
+    pub async fn test_exec_started_in_compose_service() {
+        let path = std::path::PathBuf::from("/tmp/provisiond_tests/test_exec_started_in_compose_service");
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).expect("Failed to create test path");
+
+        let mut exec_executor = MockExecExecutor::new();
+        exec_executor
+            .expect_find_container()
+            .withf(|project, compose_service| project == "test_service" && compose_service == "postgres")
+            .returning(|_, _| Ok(Some("test_service-postgres-1".to_owned())));
+        exec_executor
+            .expect_start()
+            .withf(|container, command, tty| container == "test_service-postgres-1" && command == &["psql"] && !tty)
+            .returning(|_, _, _| {
+                Ok(ExecSession {
+                    id: "exec-1".to_owned(),
+                    output: Box::pin(stream::iter(vec![Ok(exec_output::Output::Stdout(b"psql (16.4)".to_vec()))])),
+                    input: Box::pin(tokio::io::sink()),
+                })
+            });
+        let provisioner = exec_provisioner(exec_executor, &path.join("audit.log"));
+
+        let (session, entry) = provisioner
+            .start_exec(&exec_metadata("ops-token"), exec_start("postgres", "psql"))
+            .await
+            .expect("Exec should start");
+        assert_eq!(session.id, "exec-1");
+        assert_eq!(entry.container, "test_service-postgres-1");
+
+        let audit = std::fs::read_to_string(path.join("audit.log")).unwrap();
+        let entry: serde_json::Value = serde_json::from_str(audit.trim()).unwrap();
+        assert_eq!(entry["allowed"], true);
+        assert_eq!(entry["container"], "test_service-postgres-1");
+
+        std::fs::remove_dir_all(&path)
+            .unwrap_or_else(|_| panic!("Failed to delete test path at {}", path.display()));
     }
 }
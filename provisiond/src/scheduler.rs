@@ -0,0 +1,118 @@
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use libprovision::hello_world::ProvisionerServer;
+use tonic_health::ServingStatus;
+use tonic_health::server::HealthReporter;
+use tracing::{info, warn};
+
+use crate::provisioner_server::ProvisionerImpl;
+
+/// How often the schedules are checked for due jobs.
+const TICK: Duration = Duration::from_secs(15);
+
+/// How often docker, systemd and the provisioning root are checked.
+const DEPENDENCY_TICK: Duration = Duration::from_secs(10);
+
+/// Runs scheduled jobs as they come due, one at a time, for as long as the
+/// daemon runs.
+pub async fn run(provisioner: Arc<ProvisionerImpl>) {
+    let mut interval = tokio::time::interval(TICK);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+        interval.tick().await;
+
+        let jobs = match provisioner.scheduled_jobs() {
+            Ok(jobs) => jobs,
+            Err(status) => {
+                warn!("Failed to read schedules: {message}", message = status.message());
+                continue;
+            }
+        };
+        provisioner.retain_jobs(&jobs);
+
+        for job in &jobs {
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+            if provisioner.next_run(job, now).is_some_and(|next| next <= now) {
+                provisioner.run_job(job).await;
+            }
+        }
+    }
+}
+
+/// Checks the health of every service each `interval`, restarting the ones
+/// that stay unhealthy if the daemon is configured to.
+pub async fn monitor(provisioner: Arc<ProvisionerImpl>, interval: Duration) {
+    let mut interval = tokio::time::interval(interval);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+        interval.tick().await;
+
+        let service_names = match provisioner.service_names() {
+            Ok(service_names) => service_names,
+            Err(status) => {
+                warn!("Failed to list services for health checks: {message}", message = status.message());
+                continue;
+            }
+        };
+        provisioner.retain_health(&service_names);
+
+        for service_name in &service_names {
+            // Unhealthy services are logged when they change status, a
+            // failed check needs nothing more here.
+            let _ = provisioner.monitor_service(service_name).await;
+        }
+    }
+}
+
+/// Keeps the standard gRPC health service up to date with whether docker,
+/// systemd and the provisioning root work, for as long as the daemon runs.
+pub async fn report_dependencies(provisioner: Arc<ProvisionerImpl>, reporter: HealthReporter) {
+    let mut interval = tokio::time::interval(DEPENDENCY_TICK);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    let mut failing = BTreeSet::new();
+    loop {
+        interval.tick().await;
+        update_dependencies(&provisioner, &reporter, &mut failing).await;
+    }
+}
+
+/// Reports every dependency under its own name. The daemon as a whole, and
+/// the Provisioner service, only count as serving while all of them work.
+/// `failing` holds the dependencies that failed last time, so changes are
+/// logged once.
+pub(crate) async fn update_dependencies(
+    provisioner: &ProvisionerImpl,
+    reporter: &HealthReporter,
+    failing: &mut BTreeSet<&'static str>,
+) {
+    for (dependency, result) in provisioner.check_dependencies().await {
+        let status = match result {
+            Ok(()) => {
+                if failing.remove(dependency) {
+                    info!("Dependency {dependency} works again");
+                }
+                ServingStatus::Serving
+            }
+            Err(e) => {
+                if failing.insert(dependency) {
+                    warn!("Dependency {dependency} failed its check: {e}");
+                }
+                ServingStatus::NotServing
+            }
+        };
+        reporter.set_service_status(dependency, status).await;
+    }
+
+    if failing.is_empty() {
+        reporter.set_service_status("", ServingStatus::Serving).await;
+        reporter.set_serving::<ProvisionerServer<ProvisionerImpl>>().await;
+    } else {
+        reporter.set_service_status("", ServingStatus::NotServing).await;
+        reporter.set_not_serving::<ProvisionerServer<ProvisionerImpl>>().await;
+    }
+}
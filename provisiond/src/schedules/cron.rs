@@ -0,0 +1,200 @@
+use std::str::FromStr;
+
+const MINUTES_PER_DAY: u64 = 24 * 60;
+
+/// How far ahead `next_after` looks, long enough for `0 0 29 2 *`.
+const SEARCH_DAYS: u64 = 8 * 366;
+
+/// A five field cron expression (minute, hour, day of month, month, day of
+/// week), evaluated in UTC. As in Vixie cron, when both day fields are
+/// restricted a day matching either of them matches.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cron {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    days_restricted: bool,
+    weekdays_restricted: bool,
+}
+
+/// One field as a bit set of the values it allows.
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64, String> {
+    let mut bits = 0;
+
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().ok().filter(|s| *s > 0)),
+            None => (part, Some(1)),
+        };
+        let Some(step) = step else {
+            return Err(format!("invalid step in '{part}'"));
+        };
+
+        let (start, end) = match range {
+            "*" => (min, max),
+            _ => {
+                let (start, end) = range.split_once('-').unwrap_or((range, range));
+                let parse = |value: &str| {
+                    value
+                        .parse::<u32>()
+                        .ok()
+                        .filter(|v| (min..=max).contains(v))
+                        .ok_or_else(|| format!("'{value}' is not between {min} and {max}"))
+                };
+                // `a/n` runs from a to the end of the field.
+                let end = match (range.contains('-'), part.contains('/')) {
+                    (false, true) => max,
+                    _ => parse(end)?,
+                };
+                (parse(start)?, end)
+            }
+        };
+        if start > end {
+            return Err(format!("range '{range}' is backwards"));
+        }
+
+        for value in (start..=end).step_by(step as usize) {
+            bits |= 1 << value;
+        }
+    }
+
+    Ok(bits)
+}
+
+fn has(bits: u64, value: u64) -> bool {
+    bits & (1 << value) != 0
+}
+
+/// Year, month and day of a count of days since 1970-01-01.
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    // Howard Hinnant's algorithm, shifted so the year starts in March.
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let day_of_era = z - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    (year, month, day)
+}
+
+impl Cron {
+    /// The first minute strictly after `after`, both as unix seconds.
+    pub fn next_after(&self, after: u64) -> Option<u64> {
+        let start = after / 60 + 1;
+        let (mut day, mut first_minute) = (start / MINUTES_PER_DAY, start % MINUTES_PER_DAY);
+
+        for _ in 0..SEARCH_DAYS {
+            if self.day_matches(day) {
+                let minute = (first_minute..MINUTES_PER_DAY)
+                    .find(|minute| has(self.hours, minute / 60) && has(self.minutes, minute % 60));
+                if let Some(minute) = minute {
+                    return Some((day * MINUTES_PER_DAY + minute) * 60);
+                }
+            }
+            day += 1;
+            first_minute = 0;
+        }
+
+        None
+    }
+
+    fn day_matches(&self, days: u64) -> bool {
+        let (_, month, day) = civil_from_days(days);
+        // 1970-01-01 was a Thursday.
+        let weekday = (days + 4) % 7;
+
+        if !has(self.months, month) {
+            return false;
+        }
+
+        let day_ok = has(self.days, day);
+        let weekday_ok = has(self.weekdays, weekday);
+        match (self.days_restricted, self.weekdays_restricted) {
+            (true, true) => day_ok || weekday_ok,
+            _ => day_ok && weekday_ok,
+        }
+    }
+}
+
+impl FromStr for Cron {
+    type Err = String;
+
+    fn from_str(expression: &str) -> Result<Self, Self::Err> {
+        let expression = match expression.trim() {
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            "@monthly" => "0 0 1 * *",
+            "@weekly" => "0 0 * * 0",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@hourly" => "0 * * * *",
+            expression => expression,
+        };
+
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        let [minutes, hours, days, months, weekdays] = fields[..] else {
+            return Err(format!("invalid schedule '{expression}', expected five fields or a macro like @daily"));
+        };
+        let invalid = |e: String| format!("invalid schedule '{expression}': {e}");
+
+        let mut weekday_bits = parse_field(weekdays, 0, 7).map_err(invalid)?;
+        // 7 is another way of writing Sunday.
+        if has(weekday_bits, 7) {
+            weekday_bits = (weekday_bits | 1) & !(1 << 7);
+        }
+
+        Ok(Cron {
+            minutes: parse_field(minutes, 0, 59).map_err(invalid)?,
+            hours: parse_field(hours, 0, 23).map_err(invalid)?,
+            days: parse_field(days, 1, 31).map_err(invalid)?,
+            months: parse_field(months, 1, 12).map_err(invalid)?,
+            weekdays: weekday_bits,
+            days_restricted: days != "*",
+            weekdays_restricted: weekdays != "*",
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 2026-10-19 00:00:00 UTC, a Monday.
+    const MONDAY: u64 = 1_792_368_000;
+
+    fn next(expression: &str, after: u64) -> u64 {
+        expression
+            .parse::<Cron>()
+            .expect("Expression should parse")
+            .next_after(after)
+            .expect("Expression should match")
+    }
+
+    #[test]
+    pub fn test_next_runs() {
+        assert_eq!(next("* * * * *", MONDAY), MONDAY + 60, "Runs strictly after the given time");
+        assert_eq!(next("0 3 * * *", MONDAY), MONDAY + 3 * 3600);
+        assert_eq!(next("0 3 * * *", MONDAY + 3 * 3600), MONDAY + 27 * 3600, "A run that just happened is not repeated");
+        assert_eq!(next("*/15 * * * *", MONDAY + 60), MONDAY + 15 * 60);
+        assert_eq!(next("@weekly", MONDAY), MONDAY + 6 * 86_400, "Weeks start on Sunday");
+        assert_eq!(next("0 0 * * 7", MONDAY), MONDAY + 6 * 86_400, "7 is Sunday too");
+        assert_eq!(next("0 0 1 * *", MONDAY), MONDAY + 13 * 86_400);
+    }
+
+    #[test]
+    pub fn test_restricted_days_match_either() {
+        // The 20th is a Tuesday and the 23rd a Friday.
+        assert_eq!(next("0 0 23 * 2", MONDAY), MONDAY + 86_400);
+        assert_eq!(next("0 0 23 * *", MONDAY), MONDAY + 4 * 86_400);
+    }
+
+    #[test]
+    pub fn test_invalid_expressions_rejected() {
+        for expression in ["", "* * * *", "60 * * * *", "* * 0 * *", "*/0 * * * *", "5-1 * * * *", "@sometimes"] {
+            assert!(expression.parse::<Cron>().is_err(), "'{expression}' should be rejected");
+        }
+    }
+}
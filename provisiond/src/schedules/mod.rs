@@ -0,0 +1,6 @@
+mod cron;
+mod schedule_spec;
+mod schedule_store;
+
+pub use schedule_spec::{JobKind, ScheduleSpec};
+pub use schedule_store::{Job, LastRun, ScheduleStore, load_global};
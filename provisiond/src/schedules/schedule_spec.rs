@@ -0,0 +1,92 @@
+use libprovision::hello_world::{self as proto, Schedule};
+use serde::{Deserialize, Serialize};
+
+use crate::schedules::cron::Cron;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum JobKind {
+    Backup,
+    Pull,
+    HealthCheck,
+}
+
+impl JobKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobKind::Backup => "backup",
+            JobKind::Pull => "pull",
+            JobKind::HealthCheck => "health_check",
+        }
+    }
+}
+
+/// A job and when to run it, stored in the service's definition or in the
+/// daemon's global schedules file.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ScheduleSpec {
+    pub job: JobKind,
+    pub cron: String,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub jitter_seconds: u32,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub restart_on_change: bool,
+}
+
+fn is_zero(value: &u32) -> bool {
+    *value == 0
+}
+
+impl ScheduleSpec {
+    pub fn cron(&self) -> Result<Cron, String> {
+        self.cron.parse()
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        self.cron()?;
+
+        if self.restart_on_change && self.job != JobKind::Pull {
+            return Err(format!("restart_on_change only applies to pull jobs, not {job}", job = self.job.as_str()));
+        }
+
+        Ok(())
+    }
+}
+
+impl TryFrom<Schedule> for ScheduleSpec {
+    type Error = String;
+
+    fn try_from(schedule: Schedule) -> Result<Self, Self::Error> {
+        let job = match schedule.job() {
+            proto::JobKind::Backup => JobKind::Backup,
+            proto::JobKind::Pull => JobKind::Pull,
+            proto::JobKind::HealthCheck => JobKind::HealthCheck,
+            proto::JobKind::Unspecified => return Err(format!("schedule '{cron}' has no job", cron = schedule.cron)),
+        };
+
+        Ok(Self {
+            job,
+            cron: schedule.cron,
+            jitter_seconds: schedule.jitter_seconds,
+            restart_on_change: schedule.restart_on_change,
+        })
+    }
+}
+
+impl From<&ScheduleSpec> for Schedule {
+    fn from(spec: &ScheduleSpec) -> Self {
+        let job = match spec.job {
+            JobKind::Backup => proto::JobKind::Backup,
+            JobKind::Pull => proto::JobKind::Pull,
+            JobKind::HealthCheck => proto::JobKind::HealthCheck,
+        };
+
+        Schedule {
+            job: job as i32,
+            cron: spec.cron.clone(),
+            jitter_seconds: spec.jitter_seconds,
+            restart_on_change: spec.restart_on_change,
+        }
+    }
+}
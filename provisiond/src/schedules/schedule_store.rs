@@ -0,0 +1,277 @@
+use std::collections::BTreeMap;
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::io::{Backup, write_atomic};
+use crate::schedules::schedule_spec::ScheduleSpec;
+use crate::services::ServiceDefinition;
+
+/// A schedule applied to one service.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Job {
+    pub service_name: String,
+    pub spec: ScheduleSpec,
+    /// Comes from the global schedules rather than the service's definition.
+    pub global: bool,
+}
+
+impl Job {
+    fn key(&self) -> String {
+        format!("{service}/{job}", service = self.service_name, job = self.spec.job.as_str())
+    }
+
+    /// Seconds added to every run of this job. It is fixed per job so runs
+    /// stay evenly spaced, while services sharing a schedule are spread out.
+    fn jitter(&self) -> u64 {
+        if self.spec.jitter_seconds == 0 {
+            return 0;
+        }
+        let mut hasher = DefaultHasher::new();
+        self.key().hash(&mut hasher);
+        hasher.finish() % (u64::from(self.spec.jitter_seconds) + 1)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct LastRun {
+    /// Unix time in seconds.
+    pub started_at: u64,
+    pub succeeded: bool,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub operation_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+struct JobState {
+    /// When the job was first seen, runs are counted from here until the
+    /// first one happens.
+    since: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    last_run: Option<LastRun>,
+}
+
+/// Reads the global schedules, a JSON list of schedules every service gets.
+/// A missing file means there are none.
+pub fn load_global(path: &Path) -> Result<Vec<ScheduleSpec>, String> {
+    let json = match fs::read_to_string(path) {
+        Ok(json) => json,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("failed to read {path}: {e}", path = path.display())),
+    };
+
+    let specs: Vec<ScheduleSpec> =
+        serde_json::from_str(&json).map_err(|e| format!("invalid schedules in {path}: {e}", path = path.display()))?;
+    for spec in &specs {
+        spec.validate()?;
+    }
+
+    Ok(specs)
+}
+
+/// Keeps the global schedules and when each job last ran. The run times are
+/// written to `state_path` so a restart neither repeats nor forgets runs.
+#[derive(Default)]
+pub struct ScheduleStore {
+    state_path: Option<PathBuf>,
+    global: Vec<ScheduleSpec>,
+    state: Mutex<BTreeMap<String, JobState>>,
+}
+
+impl ScheduleStore {
+    pub fn load(state_path: PathBuf, global: Vec<ScheduleSpec>) -> io::Result<Self> {
+        let state = match fs::read_to_string(&state_path) {
+            Ok(json) => serde_json::from_str(&json).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?,
+            Err(e) if e.kind() == ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e),
+        };
+
+        Ok(Self {
+            state_path: Some(state_path),
+            global,
+            state: Mutex::new(state),
+        })
+    }
+
+    /// Every scheduled job. A service's own schedule for a job replaces the
+    /// global one for the same job.
+    pub fn jobs(&self, definitions: &BTreeMap<String, ServiceDefinition>) -> Vec<Job> {
+        let mut jobs = Vec::new();
+
+        for (service_name, definition) in definitions {
+            let own = definition.schedules.iter().map(|spec| (spec, false));
+            let global = self
+                .global
+                .iter()
+                .filter(|spec| !definition.schedules.iter().any(|own| own.job == spec.job))
+                .map(|spec| (spec, true));
+
+            jobs.extend(own.chain(global).map(|(spec, global)| Job {
+                service_name: service_name.clone(),
+                spec: spec.clone(),
+                global,
+            }));
+        }
+
+        jobs
+    }
+
+    /// When the job runs next, in unix seconds. A run missed while the
+    /// daemon was down is due straight away.
+    pub fn next_run(&self, job: &Job, now: u64) -> Option<u64> {
+        let mut state = self.state.lock().unwrap();
+        let key = job.key();
+        if !state.contains_key(&key) {
+            state.insert(key.clone(), JobState { since: now, last_run: None });
+            self.persist(&state);
+        }
+
+        let job_state = &state[&key];
+        let base = job_state.last_run.as_ref().map_or(job_state.since, |run| run.started_at);
+
+        // The jitter is taken off the base too, otherwise a run that started
+        // late would count as the current slot's run and skip the next one.
+        let cron = job.spec.cron().ok()?;
+        cron.next_after(base.saturating_sub(job.jitter())).map(|next| next + job.jitter())
+    }
+
+    pub fn last_run(&self, job: &Job) -> Option<LastRun> {
+        self.state.lock().unwrap().get(&job.key()).and_then(|state| state.last_run.clone())
+    }
+
+    pub fn record(&self, job: &Job, run: LastRun) {
+        let mut state = self.state.lock().unwrap();
+        let job_state = state.entry(job.key()).or_insert_with(|| JobState {
+            since: run.started_at,
+            last_run: None,
+        });
+        job_state.last_run = Some(run);
+        self.persist(&state);
+    }
+
+    /// Drops the state of jobs that are no longer scheduled.
+    pub fn retain(&self, jobs: &[Job]) {
+        let mut state = self.state.lock().unwrap();
+        let before = state.len();
+        state.retain(|key, _| jobs.iter().any(|job| &job.key() == key));
+        if state.len() != before {
+            self.persist(&state);
+        }
+    }
+
+    fn persist(&self, state: &BTreeMap<String, JobState>) {
+        let Some(path) = &self.state_path else {
+            return;
+        };
+        let json = serde_json::to_string_pretty(state).expect("schedule state is always serializable");
+        if let Err(e) = write_atomic(path, json.as_bytes(), Backup::Previous) {
+            warn!("Failed to save schedule state to {path}: {e}", path = path.display());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schedules::JobKind;
+
+    /// 2026-10-19 00:00:00 UTC.
+    const MIDNIGHT: u64 = 1_792_368_000;
+
+    fn spec(job: JobKind, cron: &str) -> ScheduleSpec {
+        ScheduleSpec {
+            job,
+            cron: cron.to_owned(),
+            jitter_seconds: 0,
+            restart_on_change: false,
+        }
+    }
+
+    fn definitions() -> BTreeMap<String, ServiceDefinition> {
+        let mut own = ServiceDefinition::legacy("own".to_owned());
+        own.schedules.push(spec(JobKind::Backup, "0 1 * * *"));
+
+        [own, ServiceDefinition::legacy("plain".to_owned())]
+            .into_iter()
+            .map(|definition| (definition.name.clone(), definition))
+            .collect()
+    }
+
+    #[test]
+    pub fn test_own_schedules_replace_global_ones() {
+        let store = ScheduleStore {
+            global: vec![spec(JobKind::Backup, "@daily"), spec(JobKind::HealthCheck, "* * * * *")],
+            ..Default::default()
+        };
+
+        let jobs: Vec<(String, JobKind, String, bool)> = store
+            .jobs(&definitions())
+            .into_iter()
+            .map(|job| (job.service_name, job.spec.job, job.spec.cron, job.global))
+            .collect();
+
+        assert_eq!(
+            jobs,
+            vec![
+                ("own".to_owned(), JobKind::Backup, "0 1 * * *".to_owned(), false),
+                ("own".to_owned(), JobKind::HealthCheck, "* * * * *".to_owned(), true),
+                ("plain".to_owned(), JobKind::Backup, "@daily".to_owned(), true),
+                ("plain".to_owned(), JobKind::HealthCheck, "* * * * *".to_owned(), true),
+            ]
+        );
+    }
+
+    #[test]
+    pub fn test_runs_persisted_and_caught_up() {
+        let path = PathBuf::from("/tmp/provisiond_tests/test_runs_persisted_and_caught_up");
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).expect("Failed to create test path");
+        let state_path = path.join("schedules.json");
+
+        let job = Job {
+            service_name: "own".to_owned(),
+            spec: spec(JobKind::Backup, "0 1 * * *"),
+            global: false,
+        };
+
+        let store = ScheduleStore::load(state_path.clone(), Vec::new()).unwrap();
+        assert_eq!(store.next_run(&job, MIDNIGHT), Some(MIDNIGHT + 3600));
+        store.record(&job, LastRun {
+            started_at: MIDNIGHT + 3600,
+            succeeded: true,
+            operation_id: "op".to_owned(),
+            error: None,
+        });
+
+        // The daemon was down for two days, the missed run is due at once
+        // rather than waiting for the next slot.
+        let store = ScheduleStore::load(state_path, Vec::new()).unwrap();
+        assert_eq!(store.last_run(&job).map(|run| run.operation_id), Some("op".to_owned()));
+        assert_eq!(store.next_run(&job, MIDNIGHT + 2 * 86_400), Some(MIDNIGHT + 86_400 + 3600));
+
+        fs::remove_dir_all(&path)
+            .unwrap_or_else(|_| panic!("Failed to delete test path at {}", path.display()));
+    }
+
+    #[test]
+    pub fn test_jitter_stays_within_bounds() {
+        let mut job = Job {
+            service_name: "own".to_owned(),
+            spec: spec(JobKind::Backup, "0 1 * * *"),
+            global: false,
+        };
+        job.spec.jitter_seconds = 600;
+
+        let next = ScheduleStore::default().next_run(&job, MIDNIGHT).unwrap();
+
+        assert!((MIDNIGHT + 3600..=MIDNIGHT + 4200).contains(&next));
+    }
+}
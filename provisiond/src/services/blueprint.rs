@@ -0,0 +1,393 @@
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+
+use uuid::Uuid;
+
+use crate::permissions::ServiceOwner;
+use crate::services::service_definition::{ResourceLimits, ServiceDefinition};
+
+pub const DEFAULT_BLUEPRINT: &str = "postgres";
+
+/// Templates a service is generated from. Placeholders are written as
+/// `{{name}}` and filled from the blueprint's parameters plus `service_name`,
+/// the unit also gets `service_user` and `service_group`. A `{{limits}}` line
+/// in the compose file becomes the service's resource limits, and the compose
+/// and env files get `{{port:<name>}}` for the host ports the daemon assigned,
+/// which compose publishes on `{{port_address}}`. Any template may use
+/// `{{secret:<name>}}` for the values generated when the service was created.
+pub struct Blueprint {
+    pub name: &'static str,
+    compose_template: &'static str,
+    env_template: &'static str,
+    unit_template: &'static str,
+    /// Parameters the blueprint accepts and their defaults.
+    parameters: &'static [(&'static str, &'static str)],
+    /// Ports published on the host, by name and container port.
+    ports: &'static [(&'static str, u16)],
+    /// Secrets generated for each service, by name and the value services
+    /// created before secrets were generated had.
+    secrets: &'static [(&'static str, &'static str)],
+    /// Name of the service's container, `{{KEY}}` placeholders are filled
+    /// from the rendered .env.
+    container: &'static str,
+    /// How the data is backed up, `None` if the blueprint cannot be.
+    dump: Option<DumpTemplate>,
+    /// How to tell the service answers, `None` if only the unit and the
+    /// container are checked.
+    probe: Option<Probe>,
+}
+
+/// Commands run in the service's container to dump and restore its data.
+/// Placeholders are `{{KEY}}` and filled from the rendered .env.
+pub struct DumpTemplate {
+    pub engine: &'static str,
+    dump: &'static [&'static str],
+    restore: &'static [&'static str],
+}
+
+/// A check run from the host against the container's address.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Probe {
+    /// The port accepts connections.
+    Tcp { port: u16 },
+    /// A GET of `path` answers with a 2xx or 3xx status.
+    // No blueprint serves HTTP yet.
+    #[allow(dead_code)]
+    Http { port: u16, path: &'static str },
+}
+
+impl Probe {
+    /// Where the probe goes for a container at `address`, for display.
+    pub fn url(&self, address: IpAddr) -> String {
+        let host = match address {
+            IpAddr::V4(address) => address.to_string(),
+            IpAddr::V6(address) => format!("[{address}]"),
+        };
+        match self {
+            Probe::Tcp { port } => format!("tcp://{host}:{port}"),
+            Probe::Http { port, path } => format!("http://{host}:{port}{path}"),
+        }
+    }
+}
+
+/// A `DumpTemplate` filled in for one service.
+#[derive(Debug, PartialEq)]
+pub struct DumpCommands {
+    pub engine: &'static str,
+    pub container: String,
+    pub dump: Vec<String>,
+    pub restore: Vec<String>,
+}
+
+pub const BLUEPRINTS: &[Blueprint] = &[Blueprint {
+    name: "postgres",
+    compose_template: include_str!("../../res/template/docker-compose.yaml"),
+    env_template: include_str!("../../res/template/env"),
+    unit_template: include_str!("../../res/template/unit.service"),
+    parameters: &[("image", "postgres:16")],
+    ports: &[("postgres", 5432)],
+    secrets: &[("password", "{{service_name}}-password")],
+    // Matches container_name in the compose template.
+    container: "{{POSTGRES_USER}}.db",
+    dump: Some(DumpTemplate {
+        engine: "postgres",
+        dump: &["pg_dump", "--clean", "--if-exists", "--username={{POSTGRES_USER}}", "--dbname={{POSTGRES_DB}}"],
+        restore: &["psql", "--quiet", "--set=ON_ERROR_STOP=1", "--username={{POSTGRES_USER}}", "--dbname={{POSTGRES_DB}}"],
+    }),
+    probe: Some(Probe::Tcp { port: 5432 }),
+}];
+
+impl Blueprint {
+    pub fn find(name: &str) -> Result<&'static Blueprint, String> {
+        BLUEPRINTS.iter().find(|b| b.name == name).ok_or_else(|| {
+            let known: Vec<&str> = BLUEPRINTS.iter().map(|b| b.name).collect();
+            format!("unknown blueprint '{name}', expected one of: {known}", known = known.join(", "))
+        })
+    }
+
+    pub fn validate(&self, definition: &ServiceDefinition) -> Result<(), String> {
+        definition.validate()?;
+
+        for key in definition.parameters.keys() {
+            if !self.parameters.iter().any(|(name, _)| name == key) {
+                return Err(format!(
+                    "blueprint '{blueprint}' has no parameter '{key}'",
+                    blueprint = self.name
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The definition's parameters with the blueprint defaults filled in.
+    pub fn parameters(&self, definition: &ServiceDefinition) -> BTreeMap<String, String> {
+        let mut parameters: BTreeMap<String, String> = self
+            .parameters
+            .iter()
+            .map(|(name, default)| (name.to_string(), default.to_string()))
+            .collect();
+        parameters.extend(definition.parameters.clone());
+        parameters
+    }
+
+    /// Names of the ports the blueprint publishes.
+    pub fn port_names(&self) -> Vec<&'static str> {
+        self.ports.iter().map(|(name, _)| *name).collect()
+    }
+
+    pub fn container_port(&self, name: &str) -> Option<u16> {
+        self.ports.iter().find(|(port, _)| *port == name).map(|(_, port)| *port)
+    }
+
+    /// The definition with a value for each of the blueprint's secrets. An
+    /// existing service keeps the values in its `current` definition, or the
+    /// old fixed one if it predates them, a new service gets random ones.
+    pub fn with_secrets(&self, definition: &ServiceDefinition, current: Option<&ServiceDefinition>) -> ServiceDefinition {
+        let mut definition = definition.clone();
+        for (name, legacy) in self.secrets {
+            if definition.secrets.contains_key(*name) {
+                continue;
+            }
+            let value = match current {
+                Some(current) => match current.secrets.get(*name) {
+                    Some(value) => value.clone(),
+                    None => legacy.replace("{{service_name}}", &definition.name),
+                },
+                None => Uuid::new_v4().simple().to_string(),
+            };
+            definition.secrets.insert(name.to_string(), value);
+        }
+        definition
+    }
+
+    pub fn render_compose(&self, definition: &ServiceDefinition, ports: &BTreeMap<String, u16>, address: IpAddr) -> String {
+        // Compose wants IPv6 addresses in brackets in front of a port.
+        let address = match address {
+            IpAddr::V4(address) => address.to_string(),
+            IpAddr::V6(address) => format!("[{address}]"),
+        };
+        let rendered = fill_ports(&self.render(self.compose_template, definition), ports).replace("{{port_address}}", &address);
+        let Some(at) = rendered.find("{{limits}}") else {
+            return rendered;
+        };
+
+        // The limits take the placeholder's line and indentation, the line
+        // goes away if there are none.
+        let line_start = rendered[..at].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = rendered[at..].find('\n').map_or(rendered.len(), |i| at + i + 1);
+        let limits = render_limits(&definition.resources, &rendered[line_start..at]);
+        format!("{}{limits}{}", &rendered[..line_start], &rendered[line_end..])
+    }
+
+    pub fn render_unit(&self, definition: &ServiceDefinition, owner: &ServiceOwner) -> String {
+        self.render(self.unit_template, definition)
+            .replace("{{service_user}}", &owner.user)
+            .replace("{{service_group}}", &owner.group)
+    }
+
+    /// Renders the env template, then applies the definition's env on top.
+    /// Overridden keys keep their place, new keys are appended in order.
+    pub fn render_env(&self, definition: &ServiceDefinition, ports: &BTreeMap<String, u16>) -> String {
+        let mut remaining = definition.env.clone();
+        let mut lines: Vec<String> = fill_ports(&self.render(self.env_template, definition), ports)
+            .lines()
+            .map(|line| match line.split_once('=') {
+                Some((key, _)) => match remaining.remove(key) {
+                    Some(value) => format!("{key}={value}"),
+                    None => line.to_owned(),
+                },
+                None => line.to_owned(),
+            })
+            .collect();
+
+        lines.extend(remaining.iter().map(|(key, value)| format!("{key}={value}")));
+        lines.join("\n") + "\n"
+    }
+
+    pub fn render_container(&self, definition: &ServiceDefinition) -> String {
+        self.fill_env(definition, &[self.container]).remove(0)
+    }
+
+    pub fn probe(&self) -> Option<Probe> {
+        self.probe
+    }
+
+    /// The dump and restore commands for a service, `None` if the blueprint
+    /// has no way to back up its data.
+    pub fn render_dump(&self, definition: &ServiceDefinition) -> Option<DumpCommands> {
+        let template = self.dump.as_ref()?;
+        let fill = |values: &[&str]| self.fill_env(definition, values);
+
+        Some(DumpCommands {
+            engine: template.engine,
+            container: self.render_container(definition),
+            dump: fill(template.dump),
+            restore: fill(template.restore),
+        })
+    }
+
+    /// Fills `{{KEY}}` placeholders in each value from the rendered .env.
+    /// The values do not refer to host ports.
+    fn fill_env(&self, definition: &ServiceDefinition, values: &[&str]) -> Vec<String> {
+        let env = self.render_env(definition, &BTreeMap::new());
+        values
+            .iter()
+            .map(|value| {
+                env.lines()
+                    .filter_map(|line| line.split_once('='))
+                    .fold(value.to_string(), |value, (key, env_value)| value.replace(&format!("{{{{{key}}}}}"), env_value))
+            })
+            .collect()
+    }
+
+    fn render(&self, template: &str, definition: &ServiceDefinition) -> String {
+        let mut rendered = template.replace("{{service_name}}", &definition.name);
+        for (name, value) in self.parameters(definition) {
+            rendered = rendered.replace(&format!("{{{{{name}}}}}"), &value);
+        }
+        for (name, value) in &definition.secrets {
+            rendered = rendered.replace(&format!("{{{{secret:{name}}}}}"), value);
+        }
+        rendered
+    }
+}
+
+fn fill_ports(template: &str, ports: &BTreeMap<String, u16>) -> String {
+    ports
+        .iter()
+        .fold(template.to_owned(), |rendered, (name, port)| rendered.replace(&format!("{{{{port:{name}}}}}"), &port.to_string()))
+}
+
+/// The compose `deploy` block for `limits`. Docker enforces them on the
+/// container, a systemd limit would only apply to `docker compose` itself as
+/// the container runs under dockerd.
+fn render_limits(limits: &ResourceLimits, indent: &str) -> String {
+    let values = [
+        limits.cpus.as_ref().map(|cpus| format!("cpus: '{cpus}'")),
+        limits.memory.as_ref().map(|memory| format!("memory: {memory}")),
+        limits.pids.map(|pids| format!("pids: {pids}")),
+    ];
+    let values: Vec<String> = values.into_iter().flatten().collect();
+    if values.is_empty() {
+        return String::new();
+    }
+
+    let mut block = format!("{indent}deploy:\n{indent}  resources:\n{indent}    limits:\n");
+    for value in values {
+        block.push_str(&format!("{indent}      {value}\n"));
+    }
+    block
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn definition() -> ServiceDefinition {
+        ServiceDefinition::legacy("test_service".to_owned())
+    }
+
+    const ADDRESS: IpAddr = IpAddr::V4(std::net::Ipv4Addr::LOCALHOST);
+
+    fn ports() -> BTreeMap<String, u16> {
+        BTreeMap::from([("postgres".to_owned(), 20000)])
+    }
+
+    #[test]
+    pub fn test_defaults_rendered() {
+        let blueprint = Blueprint::find(DEFAULT_BLUEPRINT).expect("Default blueprint should exist");
+
+        let compose = blueprint.render_compose(&definition(), &ports(), ADDRESS);
+        let unit = blueprint.render_unit(&definition(), &ServiceOwner::default());
+
+        assert!(compose.contains("image: postgres:16"), "Compose file should use the default image");
+        assert!(compose.contains("- \"127.0.0.1:20000:5432\""), "Compose file should publish the assigned port");
+        assert!(unit.contains("WorkingDirectory=/mnt/srv/test_service"));
+        assert!(unit.contains("User=server-daemon\nGroup=server-daemon"));
+        assert!(!compose.contains("{{") && !unit.contains("{{"), "No placeholders should be left");
+    }
+
+    #[test]
+    pub fn test_parameters_and_env_applied() {
+        let blueprint = Blueprint::find(DEFAULT_BLUEPRINT).unwrap();
+        let mut definition = definition();
+        definition.parameters.insert("image".to_owned(), "postgres:17".to_owned());
+        definition.env.insert("POSTGRES_PASSWORD".to_owned(), "secret".to_owned());
+        definition.env.insert("TZ".to_owned(), "UTC".to_owned());
+
+        assert!(blueprint.render_compose(&definition, &ports(), ADDRESS).contains("image: postgres:17"));
+        assert_eq!(
+            blueprint.render_env(&definition, &ports()),
+            "POSTGRES_DB=test_service-db\nPOSTGRES_USER=test_service-service\nPOSTGRES_PASSWORD=secret\nHOST_PORT=20000\nTZ=UTC\n"
+        );
+    }
+
+    #[test]
+    pub fn test_secrets_generated_once() {
+        let blueprint = Blueprint::find(DEFAULT_BLUEPRINT).unwrap();
+
+        let created = blueprint.with_secrets(&definition(), None);
+        let password = created.secrets["password"].clone();
+        assert_eq!(password.len(), 32);
+        assert_ne!(blueprint.with_secrets(&definition(), None).secrets["password"], password, "Every service should get its own");
+        assert!(blueprint.render_env(&created, &ports()).contains(&format!("POSTGRES_PASSWORD={password}\n")));
+
+        let updated = blueprint.with_secrets(&definition(), Some(&created));
+        assert_eq!(updated.secrets["password"], password, "Updates should keep the password");
+        let legacy = blueprint.with_secrets(&definition(), Some(&definition()));
+        assert_eq!(legacy.secrets["password"], "test_service-password", "Older services should keep theirs");
+    }
+
+    #[test]
+    pub fn test_limits_rendered_into_compose() {
+        let blueprint = Blueprint::find(DEFAULT_BLUEPRINT).unwrap();
+        let mut definition = definition();
+        assert!(!blueprint.render_compose(&definition, &ports(), ADDRESS).contains("deploy:"), "No limits should leave no block");
+
+        definition.resources.cpus = Some("0.5".to_owned());
+        definition.resources.pids = Some(100);
+
+        assert!(blueprint.render_compose(&definition, &ports(), ADDRESS).contains(
+            "      - data:/var/lib/postgresql/data\n    deploy:\n      resources:\n        limits:\n          cpus: '0.5'\n          pids: 100\n\nvolumes:"
+        ));
+    }
+
+    #[test]
+    pub fn test_dump_commands_use_env() {
+        let mut definition = definition();
+        definition.env.insert("POSTGRES_USER".to_owned(), "app".to_owned());
+
+        let commands = Blueprint::find(DEFAULT_BLUEPRINT)
+            .unwrap()
+            .render_dump(&definition)
+            .expect("Postgres should support backups");
+
+        assert_eq!(commands.container, "app.db");
+        assert!(commands.dump.contains(&"--username=app".to_owned()));
+        assert!(commands.restore.contains(&"--dbname=test_service-db".to_owned()));
+    }
+
+    #[test]
+    pub fn test_container_and_probe_rendered() {
+        let blueprint = Blueprint::find(DEFAULT_BLUEPRINT).unwrap();
+        let address: IpAddr = "172.18.0.2".parse().unwrap();
+
+        assert_eq!(blueprint.render_container(&definition()), "test_service-service.db");
+        assert_eq!(blueprint.probe().map(|probe| probe.url(address)), Some("tcp://172.18.0.2:5432".to_owned()));
+        assert_eq!(
+            Probe::Http { port: 8080, path: "/healthz" }.url("fd00::2".parse().unwrap()),
+            "http://[fd00::2]:8080/healthz"
+        );
+    }
+
+    #[test]
+    pub fn test_unknown_blueprint_and_parameter_rejected() {
+        assert!(Blueprint::find("mysql").is_err());
+
+        let mut definition = definition();
+        definition.parameters.insert("tag".to_owned(), "17".to_owned());
+
+        assert!(Blueprint::find(DEFAULT_BLUEPRINT).unwrap().validate(&definition).is_err());
+    }
+}
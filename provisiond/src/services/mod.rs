@@ -0,0 +1,9 @@
+mod blueprint;
+mod quota;
+mod reconcile;
+mod service_definition;
+
+pub use blueprint::{Blueprint, DumpCommands, Probe};
+pub use reconcile::{Action, PlannedChange, changed_fields, plan};
+pub use quota::{check_quota, validate_limits};
+pub use service_definition::{ResourceLimits, ServiceDefinition};
@@ -0,0 +1,176 @@
+use crate::services::service_definition::{ResourceLimits, ServiceDefinition};
+
+/// A CPU limit such as "0.5" or "2" in thousandths of a CPU.
+pub fn parse_cpus(cpus: &str) -> Result<u64, String> {
+    let invalid = || format!("invalid cpus '{cpus}', expected a number of CPUs such as 0.5 or 2");
+    let (whole, fraction) = cpus.split_once('.').unwrap_or((cpus, ""));
+    if whole.is_empty() && fraction.is_empty()
+        || fraction.len() > 3
+        || !whole.chars().chain(fraction.chars()).all(|c| c.is_ascii_digit())
+    {
+        return Err(invalid());
+    }
+
+    let whole: u64 = match whole {
+        "" => 0,
+        whole => whole.parse().map_err(|_| invalid())?,
+    };
+    let fraction: u64 = format!("{fraction:0<3}").parse().map_err(|_| invalid())?;
+    match whole.checked_mul(1000).and_then(|millis| millis.checked_add(fraction)) {
+        Some(0) | None => Err(invalid()),
+        Some(millis) => Ok(millis),
+    }
+}
+
+/// A memory limit such as "512m" in bytes, with the suffixes docker takes.
+pub fn parse_memory(memory: &str) -> Result<u64, String> {
+    let invalid = || format!("invalid memory '{memory}', expected a size such as 512m or 2g");
+    let lower = memory.to_ascii_lowercase();
+    let digits = lower.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    let unit: u64 = match &lower[digits.len()..] {
+        "" | "b" => 1,
+        "k" | "kb" => 1 << 10,
+        "m" | "mb" => 1 << 20,
+        "g" | "gb" => 1 << 30,
+        _ => return Err(invalid()),
+    };
+
+    match digits.parse::<u64>().ok().and_then(|size| size.checked_mul(unit)) {
+        Some(0) | None => Err(invalid()),
+        Some(bytes) => Ok(bytes),
+    }
+}
+
+pub fn validate_limits(limits: &ResourceLimits) -> Result<(), String> {
+    if let Some(cpus) = &limits.cpus {
+        parse_cpus(cpus)?;
+    }
+    if let Some(memory) = &limits.memory {
+        parse_memory(memory)?;
+    }
+    match limits.pids {
+        Some(pids) if pids <= 0 => Err(format!("invalid pids '{pids}', expected a positive number")),
+        _ => Ok(()),
+    }
+}
+
+/// Limits of a set of services added up, in thousandths of a CPU, bytes and
+/// processes. Services without a limit do not count.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct Totals {
+    cpus: u64,
+    memory: u64,
+    pids: i64,
+}
+
+impl Totals {
+    fn of<'a>(services: impl IntoIterator<Item = &'a ServiceDefinition>) -> Result<Self, String> {
+        let mut totals = Totals::default();
+        // Each limit is valid on its own, together they can still be too
+        // large to count.
+        let overflow = |resource: &str| format!("{resource} limits add up to more than the host could have");
+        for limits in services.into_iter().map(|definition| &definition.resources) {
+            let cpus = limits.cpus.as_deref().map_or(Ok(0), parse_cpus)?;
+            totals.cpus = totals.cpus.checked_add(cpus).ok_or_else(|| overflow("cpus"))?;
+            let memory = limits.memory.as_deref().map_or(Ok(0), parse_memory)?;
+            totals.memory = totals.memory.checked_add(memory).ok_or_else(|| overflow("memory"))?;
+            totals.pids = totals.pids.checked_add(limits.pids.unwrap_or_default()).ok_or_else(|| overflow("pids"))?;
+        }
+        Ok(totals)
+    }
+}
+
+/// Checks that going from the `current` services to the `planned` ones does
+/// not take a total over the host's `quota`. A total that is already over,
+/// because the quota was lowered, may stay where it is or go down.
+pub fn check_quota<'a>(
+    quota: &ResourceLimits,
+    current: impl IntoIterator<Item = &'a ServiceDefinition>,
+    planned: impl IntoIterator<Item = &'a ServiceDefinition>,
+) -> Result<(), String> {
+    let (before, after) = (Totals::of(current)?, Totals::of(planned)?);
+    let over = |resource: &str, total: String, quota: &str| {
+        Err(format!("{resource} limits would total {total}, over the host quota of {quota}"))
+    };
+
+    if let Some(quota) = &quota.cpus
+        && after.cpus > before.cpus
+        && after.cpus > parse_cpus(quota)?
+    {
+        return over("cpus", format!("{}", after.cpus as f64 / 1000.0), quota);
+    }
+    if let Some(quota) = &quota.memory
+        && after.memory > before.memory
+        && after.memory > parse_memory(quota)?
+    {
+        return over("memory", format!("{}m", after.memory.div_ceil(1 << 20)), quota);
+    }
+    if let Some(quota) = quota.pids
+        && after.pids > before.pids
+        && after.pids > quota
+    {
+        return over("pids", after.pids.to_string(), &quota.to_string());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn service(name: &str, cpus: &str, memory: &str) -> ServiceDefinition {
+        let mut definition = ServiceDefinition::legacy(name.to_owned());
+        definition.resources.cpus = Some(cpus.to_owned());
+        definition.resources.memory = Some(memory.to_owned());
+        definition
+    }
+
+    #[test]
+    pub fn test_limits_parsed() {
+        assert_eq!(parse_cpus("0.5"), Ok(500));
+        assert_eq!(parse_cpus("2"), Ok(2000));
+        assert_eq!(parse_cpus(".25"), Ok(250));
+        for cpus in ["", ".", "0", "-1", "0.0001", "1e3", "a"] {
+            assert!(parse_cpus(cpus).is_err(), "'{cpus}' should be rejected");
+        }
+
+        assert_eq!(parse_memory("512m"), Ok(512 << 20));
+        assert_eq!(parse_memory("2GB"), Ok(2 << 30));
+        assert_eq!(parse_memory("1024"), Ok(1024));
+        for memory in ["", "m", "0m", "1.5g", "1t", "-1m"] {
+            assert!(parse_memory(memory).is_err(), "'{memory}' should be rejected");
+        }
+    }
+
+    #[test]
+    pub fn test_overcommit_rejected() {
+        let quota = ResourceLimits {
+            cpus: Some("2".to_owned()),
+            memory: Some("1g".to_owned()),
+            pids: None,
+        };
+        let current = [service("first", "1.5", "512m")];
+
+        let planned = [service("first", "1.5", "512m"), service("second", "0.5", "512m")];
+        assert_eq!(check_quota(&quota, &current, &planned), Ok(()));
+
+        let planned = [service("first", "1.5", "512m"), service("second", "0.5", "513m")];
+        assert_eq!(
+            check_quota(&quota, &current, &planned),
+            Err("memory limits would total 1025m, over the host quota of 1g".to_owned())
+        );
+
+        // Already over a lowered quota, shrinking is still allowed.
+        let current = [service("first", "3", "512m")];
+        let planned = [service("first", "2.5", "512m")];
+        assert_eq!(check_quota(&quota, &current, &planned), Ok(()));
+
+        // Too large to add up rather than wrapping around under the quota.
+        let planned = [service("first", "1", "17179869183g"), service("second", "1", "17179869183g")];
+        assert_eq!(
+            check_quota(&quota, &current, &planned),
+            Err("memory limits add up to more than the host could have".to_owned())
+        );
+    }
+}
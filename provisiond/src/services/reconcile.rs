@@ -0,0 +1,207 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use libprovision::hello_world::{ChangeAction, ServiceChange};
+
+use crate::services::blueprint::Blueprint;
+use crate::services::service_definition::ServiceDefinition;
+
+#[derive(Debug, PartialEq)]
+pub enum Action {
+    Unchanged,
+    Create,
+    /// Carries the fields that differ, for example `parameters.image`.
+    Update(Vec<String>),
+    Delete,
+}
+
+#[derive(Debug)]
+pub struct PlannedChange {
+    pub service_name: String,
+    pub action: Action,
+    /// The desired definition, `None` for deletes.
+    pub definition: Option<ServiceDefinition>,
+}
+
+/// Works out what has to happen for `current` to match `desired`. Creates and
+/// updates keep the order of `desired`, deletes come last.
+pub fn plan(
+    desired: Vec<ServiceDefinition>,
+    current: &BTreeMap<String, ServiceDefinition>,
+    prune: bool,
+) -> Result<Vec<PlannedChange>, String> {
+    let mut seen = BTreeSet::new();
+    let mut changes = Vec::new();
+
+    for definition in desired {
+        let blueprint = Blueprint::find(&definition.blueprint)?;
+        blueprint.validate(&definition)?;
+
+        if !seen.insert(definition.name.clone()) {
+            return Err(format!("service '{name}' is listed more than once", name = definition.name));
+        }
+
+        let action = match current.get(&definition.name) {
+            None => Action::Create,
+            Some(existing) if existing.blueprint != definition.blueprint => {
+                return Err(format!(
+                    "service '{name}' uses blueprint '{existing}', it has to be deleted to switch to '{desired}'",
+                    name = definition.name,
+                    existing = existing.blueprint,
+                    desired = definition.blueprint
+                ));
+            }
+            Some(existing) if existing.dedicated_user != definition.dedicated_user => {
+                return Err(format!(
+                    "service '{name}' has to be deleted to change whether it runs as its own user",
+                    name = definition.name
+                ));
+            }
+            Some(existing) => match changed_fields(blueprint, existing, &definition) {
+                fields if fields.is_empty() => Action::Unchanged,
+                fields => Action::Update(fields),
+            },
+        };
+
+        changes.push(PlannedChange {
+            service_name: definition.name.clone(),
+            action,
+            definition: Some(definition),
+        });
+    }
+
+    if prune {
+        for name in current.keys().filter(|name| !seen.contains(*name)) {
+            changes.push(PlannedChange {
+                service_name: name.clone(),
+                action: Action::Delete,
+                definition: None,
+            });
+        }
+    }
+
+    Ok(changes)
+}
+
+/// Compares two definitions of the same blueprint, parameters are compared
+/// with defaults filled in so spelling out a default is not a change.
+pub fn changed_fields(
+    blueprint: &Blueprint,
+    current: &ServiceDefinition,
+    desired: &ServiceDefinition,
+) -> Vec<String> {
+    let mut fields = Vec::new();
+
+    fields.extend(diff_maps("parameters", &blueprint.parameters(current), &blueprint.parameters(desired)));
+    fields.extend(diff_maps("env", &current.env, &desired.env));
+    if current.schedules != desired.schedules {
+        fields.push("schedules".to_owned());
+    }
+
+    let (current, desired) = (&current.resources, &desired.resources);
+    if current.cpus != desired.cpus {
+        fields.push("resources.cpus".to_owned());
+    }
+    if current.memory != desired.memory {
+        fields.push("resources.memory".to_owned());
+    }
+    if current.pids != desired.pids {
+        fields.push("resources.pids".to_owned());
+    }
+
+    fields
+}
+
+fn diff_maps(
+    prefix: &str,
+    current: &BTreeMap<String, String>,
+    desired: &BTreeMap<String, String>,
+) -> Vec<String> {
+    current
+        .keys()
+        .chain(desired.keys())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .filter(|key| current.get(*key) != desired.get(*key))
+        .map(|key| format!("{prefix}.{key}"))
+        .collect()
+}
+
+impl From<&PlannedChange> for ServiceChange {
+    fn from(change: &PlannedChange) -> Self {
+        let (action, fields) = match &change.action {
+            Action::Unchanged => (ChangeAction::Unchanged, Vec::new()),
+            Action::Create => (ChangeAction::Create, Vec::new()),
+            Action::Update(fields) => (ChangeAction::Update, fields.clone()),
+            Action::Delete => (ChangeAction::Delete, Vec::new()),
+        };
+
+        ServiceChange {
+            service_name: change.service_name.clone(),
+            action: action as i32,
+            fields,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn definition(name: &str) -> ServiceDefinition {
+        ServiceDefinition::legacy(name.to_owned())
+    }
+
+    fn current(definitions: &[ServiceDefinition]) -> BTreeMap<String, ServiceDefinition> {
+        definitions.iter().map(|d| (d.name.clone(), d.clone())).collect()
+    }
+
+    #[test]
+    pub fn test_plan_actions() {
+        let mut updated = definition("updated");
+        updated.parameters.insert("image".to_owned(), "postgres:17".to_owned());
+        updated.env.insert("TZ".to_owned(), "UTC".to_owned());
+
+        let current = current(&[definition("updated"), definition("unchanged"), definition("removed")]);
+        let desired = vec![definition("new"), updated, definition("unchanged")];
+
+        let changes = plan(desired, &current, true).expect("Plan should succeed");
+        let actions: Vec<(&str, &Action)> = changes
+            .iter()
+            .map(|c| (c.service_name.as_str(), &c.action))
+            .collect();
+
+        assert_eq!(
+            actions,
+            vec![
+                ("new", &Action::Create),
+                ("updated", &Action::Update(vec!["parameters.image".to_owned(), "env.TZ".to_owned()])),
+                ("unchanged", &Action::Unchanged),
+                ("removed", &Action::Delete),
+            ]
+        );
+    }
+
+    #[test]
+    pub fn test_plan_keeps_unlisted_without_prune() {
+        let current = current(&[definition("kept")]);
+
+        let changes = plan(Vec::new(), &current, false).expect("Plan should succeed");
+
+        assert!(changes.is_empty(), "Nothing should be deleted without prune");
+    }
+
+    #[test]
+    pub fn test_explicit_default_is_not_a_change() {
+        let mut desired = definition("service");
+        desired.parameters.insert("image".to_owned(), "postgres:16".to_owned());
+
+        let changes = plan(vec![desired], &current(&[definition("service")]), false).unwrap();
+
+        assert_eq!(changes[0].action, Action::Unchanged);
+    }
+
+    #[test]
+    pub fn test_duplicate_services_rejected() {
+        assert!(plan(vec![definition("a"), definition("a")], &BTreeMap::new(), false).is_err());
+    }
+}
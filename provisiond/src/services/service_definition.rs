@@ -0,0 +1,260 @@
+use std::collections::BTreeMap;
+
+use libprovision::hello_world::{self as proto, CreateRequest, ServiceSpec, UpdateServiceRequest};
+use serde::{Deserialize, Serialize};
+
+use crate::schedules::ScheduleSpec;
+use crate::services::quota::validate_limits;
+use crate::services::blueprint::DEFAULT_BLUEPRINT;
+
+/// Unset fields mean no limit.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct ResourceLimits {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpus: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pids: Option<i64>,
+}
+
+/// Everything a service was created from. It is stored next to the generated
+/// files so later applies and updates can tell what changed.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ServiceDefinition {
+    pub name: String,
+    pub blueprint: String,
+    #[serde(default)]
+    pub parameters: BTreeMap<String, String>,
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    #[serde(default)]
+    pub resources: ResourceLimits,
+    /// Runs as `svc-<name>` rather than the shared service user.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub dedicated_user: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub schedules: Vec<ScheduleSpec>,
+    /// Values generated when the service was created, such as passwords,
+    /// kept so every update renders the same ones.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub secrets: BTreeMap<String, String>,
+}
+
+impl ServiceDefinition {
+    /// Definition assumed for services created before definitions were stored.
+    pub fn legacy(name: String) -> Self {
+        Self {
+            name,
+            blueprint: DEFAULT_BLUEPRINT.to_owned(),
+            parameters: BTreeMap::new(),
+            env: BTreeMap::new(),
+            resources: ResourceLimits::default(),
+            dedicated_user: false,
+            schedules: Vec::new(),
+            secrets: BTreeMap::new(),
+        }
+    }
+
+    /// Rejects anything that would escape the service folder or break the
+    /// generated files.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.is_empty()
+            || !self
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(format!(
+                "invalid service name '{name}', only letters, digits, '-' and '_' are allowed",
+                name = self.name
+            ));
+        }
+
+        // useradd only takes lower case names of up to 32 characters.
+        if self.dedicated_user
+            && (self.name.len() > 28 || self.name.starts_with('-') || self.name.chars().any(|c| c.is_ascii_uppercase()))
+        {
+            return Err(format!(
+                "service name '{name}' cannot be used for a user, it has to be lower case and at most 28 characters",
+                name = self.name
+            ));
+        }
+
+        for (key, value) in self.parameters.iter().chain(&self.env) {
+            if key.is_empty() || key.contains(['=', '\n', '\r']) || value.contains(['\n', '\r']) {
+                return Err(format!("invalid parameter or env entry '{key}'"));
+            }
+        }
+
+        validate_limits(&self.resources)?;
+
+        for (index, schedule) in self.schedules.iter().enumerate() {
+            schedule.validate()?;
+            if self.schedules[..index].iter().any(|other| other.job == schedule.job) {
+                return Err(format!("service '{name}' schedules {job} more than once", name = self.name, job = schedule.job.as_str()));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The definition with an update applied on top, validation is left to
+    /// the caller.
+    pub fn with_changes(&self, request: &UpdateServiceRequest) -> Self {
+        let mut definition = self.clone();
+
+        definition.parameters.extend(request.parameters.clone());
+        for key in &request.unset_parameters {
+            definition.parameters.remove(key);
+        }
+
+        definition.env.extend(request.env.clone());
+        for key in &request.unset_env {
+            definition.env.remove(key);
+        }
+
+        let limits = ResourceLimits::from(request.resources.clone());
+        let resources = &mut definition.resources;
+        resources.cpus = limits.cpus.or(resources.cpus.take());
+        resources.memory = limits.memory.or(resources.memory.take());
+        resources.pids = limits.pids.or(resources.pids.take());
+
+        definition
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("definitions are always serializable")
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        serde_json::from_str(json).map_err(|e| e.to_string())
+    }
+}
+
+impl From<Option<proto::ResourceLimits>> for ResourceLimits {
+    fn from(limits: Option<proto::ResourceLimits>) -> Self {
+        let limits = limits.unwrap_or_default();
+        Self {
+            cpus: Some(limits.cpus).filter(|c| !c.is_empty()),
+            memory: Some(limits.memory).filter(|m| !m.is_empty()),
+            pids: Some(limits.pids).filter(|p| *p != 0),
+        }
+    }
+}
+
+fn schedules(schedules: Vec<proto::Schedule>) -> Result<Vec<ScheduleSpec>, String> {
+    schedules.into_iter().map(ScheduleSpec::try_from).collect()
+}
+
+impl TryFrom<ServiceSpec> for ServiceDefinition {
+    type Error = String;
+
+    fn try_from(spec: ServiceSpec) -> Result<Self, Self::Error> {
+        Ok(Self {
+            name: spec.name,
+            blueprint: default_blueprint(spec.blueprint),
+            parameters: spec.parameters,
+            env: spec.env,
+            resources: spec.resources.into(),
+            dedicated_user: spec.dedicated_user,
+            schedules: schedules(spec.schedules)?,
+            secrets: BTreeMap::new(),
+        })
+    }
+}
+
+impl TryFrom<CreateRequest> for ServiceDefinition {
+    type Error = String;
+
+    fn try_from(request: CreateRequest) -> Result<Self, Self::Error> {
+        Ok(Self {
+            name: request.service_name,
+            blueprint: default_blueprint(request.blueprint),
+            parameters: request.parameters,
+            env: request.env,
+            resources: request.resources.into(),
+            dedicated_user: request.dedicated_user,
+            schedules: schedules(request.schedules)?,
+            secrets: BTreeMap::new(),
+        })
+    }
+}
+
+fn default_blueprint(blueprint: String) -> String {
+    match blueprint.is_empty() {
+        true => DEFAULT_BLUEPRINT.to_owned(),
+        false => blueprint,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_definition_round_trips() {
+        let mut definition = ServiceDefinition::legacy("test_service".to_owned());
+        definition.parameters.insert("image".to_owned(), "postgres:17".to_owned());
+        definition.resources.memory = Some("512m".to_owned());
+
+        let parsed = ServiceDefinition::from_json(&definition.to_json()).expect("Failed to parse definition");
+
+        assert_eq!(parsed, definition);
+    }
+
+    #[test]
+    pub fn test_changes_applied() {
+        let mut current = ServiceDefinition::legacy("test_service".to_owned());
+        current.env.insert("TZ".to_owned(), "UTC".to_owned());
+        current.resources.memory = Some("512m".to_owned());
+
+        let request = UpdateServiceRequest {
+            service_name: "test_service".to_owned(),
+            parameters: BTreeMap::from([("image".to_owned(), "postgres:17".to_owned())]),
+            unset_env: vec!["TZ".to_owned()],
+            resources: Some(proto::ResourceLimits {
+                cpus: "0.5".to_owned(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let updated = current.with_changes(&request);
+
+        assert_eq!(updated.parameters.get("image").map(String::as_str), Some("postgres:17"));
+        assert!(updated.env.is_empty(), "Unset env should be removed");
+        assert_eq!(updated.resources.cpus.as_deref(), Some("0.5"));
+        assert_eq!(updated.resources.memory.as_deref(), Some("512m"), "Unset limits should be kept");
+    }
+
+    #[test]
+    pub fn test_invalid_names_rejected() {
+        for name in ["", "../etc", "a/b", "a b"] {
+            let definition = ServiceDefinition::legacy(name.to_owned());
+            assert!(definition.validate().is_err(), "'{name}' should be rejected");
+        }
+
+        assert!(ServiceDefinition::legacy("test-service_1".to_owned()).validate().is_ok());
+    }
+
+    #[test]
+    pub fn test_user_names_checked_for_dedicated_users() {
+        let mut definition = ServiceDefinition::legacy("Test_Service".to_owned());
+        assert!(definition.validate().is_ok(), "Only dedicated users need lower case names");
+
+        definition.dedicated_user = true;
+        assert!(definition.validate().is_err());
+
+        definition.name = "test_service".to_owned();
+        assert!(definition.validate().is_ok());
+    }
+
+    #[test]
+    pub fn test_multiline_values_rejected() {
+        let mut definition = ServiceDefinition::legacy("test_service".to_owned());
+        definition.parameters.insert("image".to_owned(), "postgres\n    privileged: true".to_owned());
+
+        assert!(definition.validate().is_err());
+    }
+}
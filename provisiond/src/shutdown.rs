@@ -0,0 +1,49 @@
+use std::future::Future;
+use std::time::Duration;
+
+use tokio::signal::unix::{SignalKind, signal};
+use tracing::{info, warn};
+
+use crate::provisioner_server::ProvisionerImpl;
+
+/// How long running operations get to finish unless
+/// `PROVISIOND_SHUTDOWN_TIMEOUT` says otherwise, well within systemd's stop
+/// timeout.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// How often running operations are checked while shutting down.
+const POLL: Duration = Duration::from_millis(100);
+
+/// How long handlers get to send their responses once no operation runs.
+/// Streams such as followed logs do not hold up the shutdown.
+const FLUSH: Duration = Duration::from_secs(1);
+
+/// Resolves once systemd or a terminal asks the daemon to stop.
+pub async fn requested() {
+    let mut terminate = signal(SignalKind::terminate()).expect("Failed to listen for SIGTERM");
+
+    tokio::select! {
+        _ = terminate.recv() => info!("Got SIGTERM, shutting down"),
+        _ = tokio::signal::ctrl_c() => info!("Got SIGINT, shutting down"),
+    }
+}
+
+/// Lets running operations finish or roll back, for at most `timeout`.
+/// `server` has already been told to stop taking requests. Operations still
+/// running after that stay in the journal for the next start to recover.
+pub async fn drain(provisioner: &ProvisionerImpl, server: impl Future, timeout: Duration) {
+    provisioner.stop_operations();
+
+    let _ = tokio::time::timeout(timeout, async {
+        while !provisioner.running_operations().is_empty() {
+            tokio::time::sleep(POLL).await;
+        }
+        let _ = tokio::time::timeout(FLUSH, server).await;
+    })
+    .await;
+
+    let interrupted = provisioner.running_operations();
+    if !interrupted.is_empty() {
+        warn!("{count} operations did not finish within {timeout:?}, they are recovered on the next start", count = interrupted.len());
+    }
+}
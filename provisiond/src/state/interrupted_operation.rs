@@ -0,0 +1,97 @@
+use std::time::Instant;
+
+use libprovision::hello_world::OperationStatus;
+use serde::{Deserialize, Serialize};
+use tracing::Span;
+
+use crate::state::operation_record::{OperationRecord, StepRecord};
+
+/// A step of an operation the daemon stopped in the middle of.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct InterruptedStep {
+    pub name: String,
+    pub service_name: String,
+    /// `running` if the step had not finished, otherwise `succeeded` or
+    /// `failed`.
+    pub status: String,
+}
+
+impl InterruptedStep {
+    /// Whatever the step did is still on the host, so it is undone or
+    /// finished on recovery.
+    pub fn took_effect(&self) -> bool {
+        self.status != "failed"
+    }
+}
+
+/// An operation the daemon stopped in the middle of.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct InterruptedOperation {
+    pub id: String,
+    pub kind: String,
+    pub service_name: String,
+    pub steps: Vec<InterruptedStep>,
+}
+
+impl InterruptedOperation {
+    /// The service the operation was working on when it stopped. Apply runs
+    /// one service after the other, the ones before this one are done.
+    pub fn current_service(&self) -> &str {
+        self.steps
+            .last()
+            .map_or(self.service_name.as_str(), |step| step.service_name.as_str())
+    }
+
+    /// Steps run for the current service, oldest first.
+    pub fn current_steps(&self) -> impl Iterator<Item = &InterruptedStep> {
+        let service_name = self.current_service();
+        self.steps.iter().filter(move |step| step.service_name == service_name)
+    }
+}
+
+impl From<&OperationRecord> for InterruptedOperation {
+    fn from(record: &OperationRecord) -> Self {
+        InterruptedOperation {
+            id: record.id.clone(),
+            kind: record.kind.clone(),
+            service_name: record.service_name.clone(),
+            steps: record
+                .steps
+                .iter()
+                .map(|step| InterruptedStep {
+                    name: step.name.clone(),
+                    service_name: step.service_name.clone(),
+                    status: match step.status {
+                        OperationStatus::Succeeded => "succeeded",
+                        OperationStatus::Failed => "failed",
+                        _ => "running",
+                    }
+                    .to_owned(),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<&InterruptedOperation> for OperationRecord {
+    fn from(operation: &InterruptedOperation) -> Self {
+        let mut record = OperationRecord::new(operation.id.clone(), operation.kind.clone(), operation.service_name.clone());
+        record.steps = operation
+            .steps
+            .iter()
+            .map(|step| StepRecord {
+                name: step.name.clone(),
+                service_name: step.service_name.clone(),
+                status: match step.status.as_str() {
+                    "succeeded" => OperationStatus::Succeeded,
+                    "failed" => OperationStatus::Failed,
+                    _ => OperationStatus::Running,
+                },
+                error: None,
+                started_at: Instant::now(),
+                span: Span::none(),
+            })
+            .collect();
+        record
+    }
+}
@@ -0,0 +1,8 @@
+mod interrupted_operation;
+mod operation_journal;
+mod operation_record;
+mod operation_store;
+
+pub use interrupted_operation::InterruptedOperation;
+pub use operation_journal::OperationJournal;
+pub use operation_store::{BeginOutcome, OperationStore};
@@ -0,0 +1,227 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, ErrorKind, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::state::interrupted_operation::{InterruptedOperation, InterruptedStep};
+
+/// One line of the journal.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum JournalEntry {
+    Began {
+        id: String,
+        kind: String,
+        service_name: String,
+    },
+    /// Written before the step runs.
+    StepStarted {
+        id: String,
+        step: String,
+        service_name: String,
+    },
+    /// Written once the step has run.
+    StepFinished {
+        id: String,
+        step: String,
+        service_name: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    Finished {
+        id: String,
+    },
+}
+
+/// Write-ahead log of operations and their steps. Every entry is synced to
+/// disk before the daemon goes on, so after a crash the journal tells which
+/// operations were cut short and how far they got.
+pub struct OperationJournal {
+    path: PathBuf,
+    file: Mutex<File>,
+    /// Operations the journal held when it was opened that never finished.
+    interrupted: Vec<InterruptedOperation>,
+}
+
+impl OperationJournal {
+    /// Opens the journal at `path`, reading the operations an earlier run of
+    /// the daemon left unfinished.
+    pub fn open(path: PathBuf) -> io::Result<Self> {
+        if let Some(folder) = path.parent() {
+            fs::create_dir_all(folder)?;
+        }
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e),
+        };
+        let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+        // Entries after a line cut short start on a line of their own.
+        if !contents.is_empty() && !contents.ends_with('\n') {
+            file.write_all(b"\n")?;
+        }
+
+        Ok(Self {
+            path,
+            file: Mutex::new(file),
+            interrupted: replay(&contents),
+        })
+    }
+
+    pub fn interrupted(&self) -> &[InterruptedOperation] {
+        &self.interrupted
+    }
+
+    /// Writes `entry` and syncs it to disk, failing if either does.
+    pub fn append(&self, entry: &JournalEntry) -> io::Result<()> {
+        let mut line = serde_json::to_string(entry).expect("journal entries are always serializable");
+        line.push('\n');
+
+        let mut file = self.file.lock().unwrap();
+        file.write_all(line.as_bytes())
+            .and_then(|()| file.sync_data())
+            .map_err(|e| io::Error::new(e.kind(), format!("{path}: {e}", path = self.path.display())))
+    }
+
+    /// Empties the journal, for when no operation is running.
+    pub fn truncate(&self) {
+        let file = self.file.lock().unwrap();
+        if let Err(e) = file.set_len(0).and_then(|()| file.sync_data()) {
+            warn!("Failed to truncate the operation journal {path}: {e}", path = self.path.display());
+        }
+    }
+}
+
+#[cfg(test)]
+impl OperationJournal {
+    /// Makes every later write fail, as a full disk would.
+    pub fn fail_writes(&self) {
+        *self.file.lock().unwrap() = File::open(&self.path).unwrap();
+    }
+}
+
+/// The operations in a journal that have no `finished` entry, in the order
+/// they began.
+fn replay(journal: &str) -> Vec<InterruptedOperation> {
+    let mut order = Vec::new();
+    let mut operations: BTreeMap<String, InterruptedOperation> = BTreeMap::new();
+
+    for line in journal.lines() {
+        // The daemon can die halfway through a line, that entry never counted.
+        let Ok(entry) = serde_json::from_str::<JournalEntry>(line) else {
+            warn!("Skipping unreadable operation journal entry: {line}");
+            continue;
+        };
+
+        match entry {
+            JournalEntry::Began { id, kind, service_name } => {
+                order.push(id.clone());
+                operations.insert(id.clone(), InterruptedOperation {
+                    id,
+                    kind,
+                    service_name,
+                    steps: Vec::new(),
+                });
+            }
+            JournalEntry::StepStarted { id, step, service_name } => {
+                if let Some(operation) = operations.get_mut(&id) {
+                    operation.steps.push(InterruptedStep {
+                        name: step,
+                        service_name,
+                        status: "running".to_owned(),
+                    });
+                }
+            }
+            JournalEntry::StepFinished { id, step, service_name, error } => {
+                let step = operations.get_mut(&id).and_then(|operation| {
+                    operation
+                        .steps
+                        .iter_mut()
+                        .rev()
+                        .find(|s| s.name == step && s.service_name == service_name)
+                });
+                if let Some(step) = step {
+                    step.status = match error {
+                        Some(_) => "failed",
+                        None => "succeeded",
+                    }
+                    .to_owned();
+                }
+            }
+            JournalEntry::Finished { id } => {
+                operations.remove(&id);
+            }
+        }
+    }
+
+    order.into_iter().filter_map(|id| operations.remove(&id)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step_started(id: &str, step: &str, service_name: &str) -> JournalEntry {
+        JournalEntry::StepStarted {
+            id: id.to_owned(),
+            step: step.to_owned(),
+            service_name: service_name.to_owned(),
+        }
+    }
+
+    fn step_finished(id: &str, step: &str, service_name: &str) -> JournalEntry {
+        JournalEntry::StepFinished {
+            id: id.to_owned(),
+            step: step.to_owned(),
+            service_name: service_name.to_owned(),
+            error: None,
+        }
+    }
+
+    #[test]
+    pub fn test_unfinished_operations_replayed() {
+        let path = PathBuf::from("/tmp/provisiond_tests/test_unfinished_operations_replayed");
+        let _ = fs::remove_dir_all(&path);
+
+        let journal = OperationJournal::open(path.join("journal")).unwrap();
+        assert!(journal.interrupted().is_empty());
+        for (id, kind) in [("done", "delete"), ("apply", "apply")] {
+            journal
+                .append(&JournalEntry::Began {
+                    id: id.to_owned(),
+                    kind: kind.to_owned(),
+                    service_name: String::new(),
+                })
+                .unwrap();
+        }
+        journal.append(&step_started("done", "Delete Folder", "old")).unwrap();
+        journal.append(&step_finished("done", "Delete Folder", "old")).unwrap();
+        journal.append(&JournalEntry::Finished { id: "done".to_owned() }).unwrap();
+        journal.append(&step_started("apply", "Create Folder", "first")).unwrap();
+        journal.append(&step_finished("apply", "Create Folder", "first")).unwrap();
+        journal.append(&step_started("apply", "Write Compose File", "second")).unwrap();
+        drop(journal);
+        // Cut short in the middle of a line.
+        let mut file = OpenOptions::new().append(true).open(path.join("journal")).unwrap();
+        file.write_all(br#"{"event":"step_fin"#).unwrap();
+
+        let journal = OperationJournal::open(path.join("journal")).unwrap();
+        journal.append(&step_finished("apply", "Write Compose File", "second")).unwrap();
+        let journal = OperationJournal::open(path.join("journal")).unwrap();
+        let [apply] = journal.interrupted() else {
+            panic!("Only the unfinished operation should be replayed: {:?}", journal.interrupted());
+        };
+        assert_eq!(apply.id, "apply");
+        assert_eq!(apply.current_service(), "second");
+        assert_eq!(apply.current_steps().map(|step| step.status.as_str()).collect::<Vec<_>>(), ["succeeded"]);
+
+        journal.truncate();
+        assert!(OperationJournal::open(path.join("journal")).unwrap().interrupted().is_empty());
+
+        fs::remove_dir_all(&path).unwrap();
+    }
+}
@@ -0,0 +1,84 @@
+use std::time::Instant;
+
+use libprovision::hello_world::{Operation, OperationStatus, OperationStep};
+use tonic::{Code, Status};
+use tracing::Span;
+
+#[derive(Clone, Debug)]
+pub struct StepRecord {
+    pub name: String,
+    /// Apply operations touch several services, so each step names its own.
+    pub service_name: String,
+    pub status: OperationStatus,
+    pub error: Option<String>,
+    pub started_at: Instant,
+    /// Open until the step finishes.
+    pub span: Span,
+}
+
+#[derive(Clone, Debug)]
+pub struct OperationRecord {
+    pub id: String,
+    pub kind: String,
+    pub service_name: String,
+    pub status: OperationStatus,
+    pub steps: Vec<StepRecord>,
+    pub error: Option<Status>,
+    pub finished_at: Option<Instant>,
+}
+
+impl OperationRecord {
+    pub fn new(id: String, kind: String, service_name: String) -> Self {
+        Self {
+            id,
+            kind,
+            service_name,
+            status: OperationStatus::Running,
+            steps: Vec::new(),
+            error: None,
+            finished_at: None,
+        }
+    }
+
+    /// Produces the result the original request returned, so retries with the
+    /// same idempotency key see exactly what the first caller saw.
+    pub fn replay(&self) -> Result<String, Status> {
+        match self.status {
+            OperationStatus::Succeeded => Ok(self.id.clone()),
+            OperationStatus::Failed => Err(self
+                .error
+                .clone()
+                .unwrap_or_else(|| Status::new(Code::Internal, "operation failed"))),
+            _ => Err(Status::new(
+                Code::Aborted,
+                format!("operation '{id}' is still running", id = self.id),
+            )),
+        }
+    }
+}
+
+impl From<&OperationRecord> for Operation {
+    fn from(record: &OperationRecord) -> Self {
+        Operation {
+            operation_id: record.id.clone(),
+            kind: record.kind.clone(),
+            service_name: record.service_name.clone(),
+            status: record.status as i32,
+            steps: record
+                .steps
+                .iter()
+                .map(|step| OperationStep {
+                    name: step.name.clone(),
+                    status: step.status as i32,
+                    error: step.error.clone().unwrap_or_default(),
+                    service_name: step.service_name.clone(),
+                })
+                .collect(),
+            error: record
+                .error
+                .as_ref()
+                .map(|status| status.message().to_owned())
+                .unwrap_or_default(),
+        }
+    }
+}
@@ -0,0 +1,410 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use libprovision::hello_world::{Operation, OperationStatus};
+use tonic::{Code, Status};
+use tracing::field::Empty;
+use tracing::{Span, info, info_span, warn};
+use uuid::Uuid;
+
+use crate::metrics::Metrics;
+use crate::state::operation_journal::JournalEntry;
+use crate::state::operation_record::{OperationRecord, StepRecord};
+use crate::state::{InterruptedOperation, OperationJournal};
+use crate::telemetry;
+
+/// How long a finished operation can still be looked up or replayed.
+const DEFAULT_RETENTION: Duration = Duration::from_secs(60 * 60);
+
+pub enum BeginOutcome {
+    Started(String),
+    Existing(Box<OperationRecord>),
+}
+
+#[derive(Default)]
+struct Inner {
+    operations: HashMap<String, OperationRecord>,
+    idempotency_keys: HashMap<String, String>,
+    /// Set once the daemon is shutting down, no new operations start.
+    closed: bool,
+}
+
+pub struct OperationStore {
+    inner: Mutex<Inner>,
+    retention: Duration,
+    /// Step durations and failures are counted here.
+    metrics: Arc<Metrics>,
+    /// Operations and steps are written here before they run.
+    journal: Option<OperationJournal>,
+}
+
+impl OperationStore {
+    pub fn new(retention: Duration) -> Self {
+        Self {
+            inner: Mutex::new(Inner::default()),
+            retention,
+            metrics: Arc::new(Metrics::default()),
+            journal: None,
+        }
+    }
+
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Journals operations from here on. The ones the journal holds from an
+    /// earlier run count as running until they are recovered.
+    pub fn with_journal(mut self, journal: OperationJournal) -> Self {
+        let operations = &mut self.inner.get_mut().unwrap().operations;
+        for operation in journal.interrupted() {
+            operations.insert(operation.id.clone(), OperationRecord::from(operation));
+        }
+        self.journal = Some(journal);
+        self
+    }
+
+    /// Writes `entry` ahead of what it records. Without it a crash would
+    /// leave nothing to recover from, so the caller does not go on.
+    fn journal(&self, entry: JournalEntry) -> Result<(), Status> {
+        match &self.journal {
+            Some(journal) => journal
+                .append(&entry)
+                .map_err(|e| Status::new(Code::Internal, format!("Failed to write to the operation journal {e}"))),
+            None => Ok(()),
+        }
+    }
+
+    /// Writes an entry for something that already happened, failing to only
+    /// leaves more for `recover` to do.
+    fn journal_after(&self, entry: JournalEntry) {
+        if let Err(status) = self.journal(entry) {
+            warn!("{}", status.message());
+        }
+    }
+
+    /// Empties the journal once nothing is left running that it would be
+    /// needed for.
+    fn compact_journal(&self, inner: &Inner) {
+        if let Some(journal) = &self.journal
+            && inner.operations.values().all(|record| record.finished_at.is_some())
+        {
+            journal.truncate();
+        }
+    }
+
+    /// Registers a new operation, or hands back the existing one if the
+    /// idempotency key has been seen before.
+    pub fn begin(
+        &self,
+        kind: &'static str,
+        service_name: &str,
+        idempotency_key: &str,
+    ) -> Result<BeginOutcome, Status> {
+        let mut inner = self.inner.lock().unwrap();
+        self.prune(&mut inner);
+
+        if inner.closed {
+            return Err(Status::new(Code::Unavailable, "provisiond is shutting down"));
+        }
+
+        if !idempotency_key.is_empty()
+            && let Some(record) = inner
+                .idempotency_keys
+                .get(idempotency_key)
+                .and_then(|id| inner.operations.get(id))
+        {
+            if record.kind != kind || record.service_name != service_name {
+                return Err(Status::new(
+                    Code::FailedPrecondition,
+                    format!(
+                        "idempotency key '{idempotency_key}' was already used for {kind} of '{service_name}'",
+                        kind = record.kind,
+                        service_name = record.service_name
+                    ),
+                ));
+            }
+
+            info!("Replaying operation {id} for idempotency key {idempotency_key}", id = record.id);
+            telemetry::record_operation(&record.id, service_name);
+            return Ok(BeginOutcome::Existing(Box::new(record.clone())));
+        }
+
+        let id = Uuid::new_v4().to_string();
+        telemetry::record_operation(&id, service_name);
+        info!("Starting {kind} operation {id} for: {service_name}");
+        self.journal(JournalEntry::Began {
+            id: id.clone(),
+            kind: kind.to_owned(),
+            service_name: service_name.to_owned(),
+        })?;
+
+        inner.operations.insert(
+            id.clone(),
+            OperationRecord::new(id.clone(), kind.to_owned(), service_name.to_owned()),
+        );
+        if !idempotency_key.is_empty() {
+            inner
+                .idempotency_keys
+                .insert(idempotency_key.to_owned(), id.clone());
+        }
+
+        Ok(BeginOutcome::Started(id))
+    }
+
+    /// Records a step before it runs, failing if the journal cannot be
+    /// written, in which case the step must not run.
+    pub fn step_started(&self, id: &str, service_name: &str, step_name: &str) -> Result<(), Status> {
+        self.journal(JournalEntry::StepStarted {
+            id: id.to_owned(),
+            step: step_name.to_owned(),
+            service_name: service_name.to_owned(),
+        })?;
+        self.update(id, |record| {
+            record.steps.push(StepRecord {
+                name: step_name.to_owned(),
+                service_name: service_name.to_owned(),
+                status: OperationStatus::Running,
+                error: None,
+                started_at: Instant::now(),
+                span: info_span!("step", step = step_name, service_name, operation_id = id, error = Empty, otel.status_message = Empty),
+            })
+        });
+        Ok(())
+    }
+
+    pub fn step_finished(&self, id: &str, service_name: &str, step_name: &str, error: Option<String>) {
+        self.journal_after(JournalEntry::StepFinished {
+            id: id.to_owned(),
+            step: step_name.to_owned(),
+            service_name: service_name.to_owned(),
+            error: error.clone(),
+        });
+        self.update(id, |record| {
+            if let Some(step) = record
+                .steps
+                .iter_mut()
+                .rev()
+                .find(|s| s.name == step_name && s.service_name == service_name)
+            {
+                let elapsed = step.started_at.elapsed();
+                self.metrics.step_finished(step_name, elapsed, error.is_some());
+                step.span.in_scope(|| match &error {
+                    Some(error) => {
+                        step.span.record("error", error.as_str());
+                        step.span.record("otel.status_message", error.as_str());
+                        warn!(elapsed_ms = elapsed.as_millis() as u64, "Step failed");
+                    }
+                    None => info!(elapsed_ms = elapsed.as_millis() as u64, "Step finished"),
+                });
+                // Closes the span, retried lookups keep a copy of the record.
+                step.span = Span::none();
+                step.status = match error {
+                    Some(_) => OperationStatus::Failed,
+                    None => OperationStatus::Succeeded,
+                };
+                step.error = error;
+            }
+        });
+    }
+
+    /// Marks the operation as done and passes the result through, so handlers
+    /// can `return self.operations.finish(...)`.
+    pub fn finish(&self, id: &str, result: Result<(), Status>) -> Result<String, Status> {
+        self.journal_after(JournalEntry::Finished { id: id.to_owned() });
+        self.update(id, |record| {
+            record.finished_at = Some(Instant::now());
+            match &result {
+                Ok(()) => record.status = OperationStatus::Succeeded,
+                Err(status) => {
+                    record.status = OperationStatus::Failed;
+                    record.error = Some(status.clone());
+                }
+            }
+        });
+        self.compact_journal(&self.inner.lock().unwrap());
+
+        result.map(|_| id.to_owned())
+    }
+
+    /// Stops new operations from starting, the running ones carry on.
+    pub fn close(&self) {
+        self.inner.lock().unwrap().closed = true;
+    }
+
+    /// Operations that have not finished yet.
+    pub fn running(&self) -> Vec<InterruptedOperation> {
+        let inner = self.inner.lock().unwrap();
+        inner
+            .operations
+            .values()
+            .filter(|record| record.finished_at.is_none())
+            .map(InterruptedOperation::from)
+            .collect()
+    }
+
+    /// Marks an operation an earlier run of the daemon was stopped in the
+    /// middle of as failed with `error`, which says how it was recovered.
+    /// It can still be looked up afterwards.
+    pub fn record_interrupted(&self, operation: &InterruptedOperation, error: String) {
+        self.journal_after(JournalEntry::Finished { id: operation.id.clone() });
+
+        let mut record = OperationRecord::from(operation);
+        for step in &mut record.steps {
+            if step.status == OperationStatus::Running {
+                step.status = OperationStatus::Failed;
+            }
+        }
+        record.status = OperationStatus::Failed;
+        record.error = Some(Status::new(Code::Aborted, error));
+        record.finished_at = Some(Instant::now());
+
+        let mut inner = self.inner.lock().unwrap();
+        inner.operations.insert(record.id.clone(), record);
+        self.compact_journal(&inner);
+    }
+
+    pub fn get(&self, id: &str) -> Option<Operation> {
+        let mut inner = self.inner.lock().unwrap();
+        self.prune(&mut inner);
+        inner.operations.get(id).map(Operation::from)
+    }
+
+    fn update(&self, id: &str, f: impl FnOnce(&mut OperationRecord)) {
+        if let Some(record) = self.inner.lock().unwrap().operations.get_mut(id) {
+            f(record);
+        }
+    }
+
+    fn prune(&self, inner: &mut Inner) {
+        let retention = self.retention;
+        inner.operations.retain(|_, record| {
+            record
+                .finished_at
+                .is_none_or(|finished_at| finished_at.elapsed() < retention)
+        });
+
+        let Inner {
+            operations,
+            idempotency_keys,
+            ..
+        } = inner;
+        idempotency_keys.retain(|_, id| operations.contains_key(id));
+    }
+}
+
+impl Default for OperationStore {
+    fn default() -> Self {
+        Self::new(DEFAULT_RETENTION)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn started(outcome: BeginOutcome) -> String {
+        match outcome {
+            BeginOutcome::Started(id) => id,
+            BeginOutcome::Existing(record) => panic!("Expected a new operation, got {}", record.id),
+        }
+    }
+
+    #[test]
+    pub fn test_operation_recorded() {
+        let store = OperationStore::default();
+
+        let id = started(store.begin("create", "test_service", "").unwrap());
+        store.step_started(&id, "test_service", "Create Folder").unwrap();
+        store.step_finished(&id, "test_service", "Create Folder", None);
+        store.finish(&id, Ok(())).expect("Operation should succeed");
+
+        let operation = store.get(&id).expect("Operation should be retrievable");
+
+        assert_eq!(operation.status, OperationStatus::Succeeded as i32);
+        assert_eq!(operation.service_name, "test_service");
+        assert_eq!(operation.steps.len(), 1);
+        assert_eq!(operation.steps[0].status, OperationStatus::Succeeded as i32);
+    }
+
+    #[test]
+    pub fn test_idempotency_key_replays_result() {
+        let store = OperationStore::default();
+
+        let id = started(store.begin("create", "test_service", "key").unwrap());
+        let _ = store.finish(&id, Err(Status::new(Code::AlreadyExists, "exists")));
+
+        let replayed = match store.begin("create", "test_service", "key").unwrap() {
+            BeginOutcome::Existing(record) => record,
+            BeginOutcome::Started(_) => panic!("Retry should not start a new operation"),
+        };
+
+        assert_eq!(replayed.id, id);
+        assert_eq!(replayed.replay().unwrap_err().code(), Code::AlreadyExists);
+    }
+
+    #[test]
+    pub fn test_idempotency_key_reused_for_other_service() {
+        let store = OperationStore::default();
+
+        let _ = started(store.begin("create", "test_service", "key").unwrap());
+        let res = store.begin("create", "other_service", "key");
+
+        assert_eq!(res.err().map(|s| s.code()), Some(Code::FailedPrecondition));
+    }
+
+    #[test]
+    pub fn test_closed_store_starts_nothing() {
+        let store = OperationStore::default();
+
+        let id = started(store.begin("update", "test_service", "").unwrap());
+        store.step_started(&id, "test_service", "Write Compose File").unwrap();
+        store.close();
+
+        let res = store.begin("create", "other_service", "");
+        assert_eq!(res.err().map(|s| s.code()), Some(Code::Unavailable));
+
+        let running = store.running();
+        assert_eq!(running.len(), 1);
+        assert_eq!(running[0].steps[0].status, "running");
+
+        store.record_interrupted(&running[0], "interrupted".to_owned());
+        let operation = store.get(&id).unwrap();
+        assert_eq!(operation.status, OperationStatus::Failed as i32);
+        assert_eq!(operation.error, "interrupted");
+    }
+
+    #[test]
+    pub fn test_finished_operations_expire() {
+        let store = OperationStore::new(Duration::ZERO);
+
+        let id = started(store.begin("delete", "test_service", "key").unwrap());
+        assert!(store.get(&id).is_some(), "Running operations never expire");
+
+        store.finish(&id, Ok(())).unwrap();
+
+        assert!(store.get(&id).is_none(), "Finished operation should have expired");
+        let _ = started(store.begin("delete", "test_service", "key").unwrap());
+    }
+
+    #[test]
+    pub fn test_unjournaled_operations_do_not_start() {
+        let path = std::path::PathBuf::from("/tmp/provisiond_tests/test_unjournaled_operations_do_not_start");
+        let _ = std::fs::remove_dir_all(&path);
+        let store = OperationStore::default().with_journal(OperationJournal::open(path.join("journal")).unwrap());
+
+        let id = started(store.begin("create", "test_service", "").unwrap());
+        store.journal.as_ref().unwrap().fail_writes();
+
+        let res = store.step_started(&id, "test_service", "Create Folder");
+        assert_eq!(res.err().map(|s| s.code()), Some(Code::Internal));
+        let res = store.begin("create", "other_service", "");
+        assert_eq!(res.err().map(|s| s.code()), Some(Code::Internal));
+
+        assert!(store.get(&id).unwrap().steps.is_empty(), "The step should not have been recorded");
+        assert_eq!(store.running().len(), 1, "Only the journaled operation should have started");
+
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+}
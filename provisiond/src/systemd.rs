@@ -0,0 +1,126 @@
+use std::io;
+use std::net::TcpListener;
+use std::os::fd::FromRawFd;
+use std::os::linux::net::SocketAddrExt;
+use std::os::unix::net::{SocketAddr, UnixDatagram};
+use std::time::Duration;
+
+use tracing::{info, warn};
+
+/// The first socket systemd passes on socket activation, sd_listen_fds(3).
+const LISTEN_FDS_START: i32 = 3;
+
+/// Tells systemd about the daemon's state, such as `READY=1`, see
+/// sd_notify(3). Does nothing unless systemd runs the daemon with
+/// `Type=notify` or a watchdog.
+pub fn notify(state: &str) {
+    let Ok(socket) = std::env::var("NOTIFY_SOCKET") else {
+        return;
+    };
+    if let Err(e) = notify_to(&socket, state) {
+        warn!("Failed to notify systemd of {state} on {socket}: {e}");
+    }
+}
+
+fn notify_to(socket: &str, state: &str) -> io::Result<()> {
+    // A leading @ names a socket in the abstract namespace.
+    let addr = match socket.strip_prefix('@') {
+        Some(name) => SocketAddr::from_abstract_name(name)?,
+        None => SocketAddr::from_pathname(socket)?,
+    };
+    UnixDatagram::unbound()?.send_to_addr(state.as_bytes(), &addr)?;
+    Ok(())
+}
+
+/// How often to tell the watchdog the daemon is alive, half of what
+/// `WatchdogSec` allows as systemd recommends. None without a watchdog.
+pub fn watchdog_interval() -> Option<Duration> {
+    let usec = std::env::var("WATCHDOG_USEC").ok()?;
+    let pid = std::env::var("WATCHDOG_PID").ok();
+    half_interval(&usec, pid.as_deref(), std::process::id())
+}
+
+fn half_interval(usec: &str, pid: Option<&str>, own_pid: u32) -> Option<Duration> {
+    // The variables are only meant for us if the pid is unset or ours.
+    if pid.is_some_and(|pid| pid.parse() != Ok(own_pid)) {
+        return None;
+    }
+    match usec.parse::<u64>() {
+        Ok(usec) if usec > 0 => Some(Duration::from_micros(usec) / 2),
+        _ => None,
+    }
+}
+
+/// Pings the watchdog every `interval`. It runs on the runtime that serves
+/// requests, so systemd restarts the daemon if that stops making progress.
+pub async fn keep_alive(interval: Duration) {
+    let mut ticks = tokio::time::interval(interval);
+    loop {
+        ticks.tick().await;
+        notify("WATCHDOG=1");
+    }
+}
+
+/// The socket systemd listens on for the daemon with socket activation, if
+/// it passed one.
+pub fn activated_listener() -> io::Result<Option<TcpListener>> {
+    let fds = std::env::var("LISTEN_FDS").ok();
+    let pid = std::env::var("LISTEN_PID").ok();
+    if passed_fds(fds.as_deref(), pid.as_deref(), std::process::id()) == 0 {
+        return Ok(None);
+    }
+
+    // SAFETY: systemd hands the daemon fd 3 open and nothing else owns it.
+    let inherited = unsafe { TcpListener::from_raw_fd(LISTEN_FDS_START) };
+    // The inherited fd is not closed on exec, the copy is, so commands the
+    // daemon runs do not hold on to the socket.
+    let listener = inherited.try_clone()?;
+    drop(inherited);
+    listener.set_nonblocking(true)?;
+
+    info!("Listening on {} passed by systemd", listener.local_addr()?);
+    Ok(Some(listener))
+}
+
+fn passed_fds(fds: Option<&str>, pid: Option<&str>, own_pid: u32) -> u32 {
+    match (fds.map(str::parse::<u32>), pid.map(str::parse::<u32>)) {
+        (Some(Ok(fds)), Some(Ok(pid))) if pid == own_pid => fds,
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    pub fn test_environment_read_only_for_own_pid() {
+        assert_eq!(half_interval("30000000", None, 7), Some(Duration::from_secs(15)));
+        assert_eq!(half_interval("30000000", Some("7"), 7), Some(Duration::from_secs(15)));
+        assert_eq!(half_interval("30000000", Some("8"), 7), None);
+        assert_eq!(half_interval("0", None, 7), None);
+
+        assert_eq!(passed_fds(Some("1"), Some("7"), 7), 1);
+        assert_eq!(passed_fds(Some("1"), Some("8"), 7), 0);
+        assert_eq!(passed_fds(Some("1"), None, 7), 0);
+        assert_eq!(passed_fds(None, None, 7), 0);
+    }
+
+    #[test]
+    pub fn test_state_sent_to_notify_socket() {
+        let path = PathBuf::from("/tmp/provisiond_tests/test_state_sent_to_notify_socket");
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        let socket = path.join("notify");
+        let systemd = UnixDatagram::bind(&socket).unwrap();
+
+        notify_to(socket.to_str().unwrap(), "READY=1").unwrap();
+
+        let mut buf = [0; 64];
+        let len = systemd.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"READY=1");
+
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+}
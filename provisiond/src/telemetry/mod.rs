@@ -0,0 +1,128 @@
+mod rpc_span;
+
+use std::cell::RefCell;
+use std::io::IsTerminal;
+use std::str::FromStr;
+
+use opentelemetry::trace::TracerProvider;
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::Resource;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use tracing::Span;
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::EnvFilter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+
+pub use rpc_span::RpcSpanLayer;
+
+/// Sent by clients so their logs can be matched with the daemon's.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+/// Returned with calls that started an operation.
+pub const OPERATION_ID_HEADER: &str = "x-operation-id";
+
+tokio::task_local! {
+    /// The operation the current call started, for the response metadata.
+    static OPERATION_ID: RefCell<Option<String>>;
+}
+
+/// How log lines are written to stderr.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum LogFormat {
+    /// tracing's human readable lines.
+    #[default]
+    Text,
+    /// One JSON object per line.
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format {
+            // `logfmt` is what the text format used to be called.
+            "text" | "logfmt" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!("unknown log format '{format}', expected text or json")),
+        }
+    }
+}
+
+/// Where spans are posted for a collector at `endpoint`, such as
+/// `http://127.0.0.1:4318`.
+fn traces_endpoint(endpoint: &str) -> String {
+    format!("{endpoint}/v1/traces", endpoint = endpoint.trim_end_matches('/'))
+}
+
+/// Sets up logging for `tracing` and `log` with a `RUST_LOG` style filter,
+/// with spans sent to an OTLP collector over HTTP at `otlp_endpoint` if
+/// there is one. The returned provider has to be shut down before exiting
+/// so the last spans are sent.
+pub fn init(filter: &str, format: LogFormat, otlp_endpoint: Option<&str>) -> Result<Option<SdkTracerProvider>, String> {
+    let filter = EnvFilter::builder()
+        .with_default_directive(LevelFilter::INFO.into())
+        .parse(filter)
+        .map_err(|e| e.to_string())?;
+
+    let provider = match otlp_endpoint {
+        Some(endpoint) => {
+            let exporter = SpanExporter::builder()
+                .with_http()
+                .with_endpoint(traces_endpoint(endpoint))
+                .build()
+                .map_err(|e| e.to_string())?;
+            let resource = Resource::builder().with_service_name("provisiond").build();
+            Some(SdkTracerProvider::builder().with_batch_exporter(exporter).with_resource(resource).build())
+        }
+        None => None,
+    };
+    let spans = provider
+        .as_ref()
+        .map(|provider| tracing_opentelemetry::layer().with_tracer(provider.tracer("provisiond")));
+
+    // journald shows colour codes as they are.
+    let text = (format == LogFormat::Text)
+        .then(|| tracing_subscriber::fmt::layer().with_ansi(std::io::stderr().is_terminal()).with_writer(std::io::stderr));
+    let json = (format == LogFormat::Json)
+        .then(|| tracing_subscriber::fmt::layer().json().flatten_event(true).with_span_list(true).with_writer(std::io::stderr));
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(text)
+        .with(json)
+        .with(spans)
+        .try_init()
+        .map_err(|e| e.to_string())?;
+    Ok(provider)
+}
+
+/// Adds the operation to the current span, and to the response metadata
+/// when called while serving an RPC.
+pub fn record_operation(operation_id: &str, service_name: &str) {
+    let span = Span::current();
+    span.record("operation_id", operation_id);
+    if !service_name.is_empty() {
+        span.record("service_name", service_name);
+    }
+    let _ = OPERATION_ID.try_with(|current| *current.borrow_mut() = Some(operation_id.to_owned()));
+}
+
+/// Adds the service a call is about to the current span.
+pub fn record_service(service_name: &str) {
+    Span::current().record("service_name", service_name);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_formats_and_endpoints_parsed() {
+        assert_eq!("logfmt".parse(), Ok(LogFormat::Text));
+        assert_eq!("json".parse(), Ok(LogFormat::Json));
+        assert!("yaml".parse::<LogFormat>().is_err());
+
+        assert_eq!(traces_endpoint("http://127.0.0.1:4318"), "http://127.0.0.1:4318/v1/traces");
+        assert_eq!(traces_endpoint("http://collector/otlp/"), "http://collector/otlp/v1/traces");
+    }
+}
@@ -0,0 +1,106 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use tonic::codegen::Service;
+use tonic::codegen::http::{HeaderValue, Request, Response};
+use tonic::metadata::MetadataMap;
+use tonic::transport::server::TcpConnectInfo;
+use tower_layer::Layer;
+use tracing::field::Empty;
+use tracing::{Instrument, info_span};
+
+use crate::access::Policy;
+use crate::telemetry::{OPERATION_ID, OPERATION_ID_HEADER, REQUEST_ID_HEADER};
+
+/// Runs every gRPC call in an `rpc` span and returns the operation it
+/// started in the response metadata.
+#[derive(Clone)]
+pub struct RpcSpanLayer {
+    policy: Arc<Policy>,
+}
+
+impl RpcSpanLayer {
+    /// Callers are named after their policy subject where the token is known.
+    pub fn new(policy: Arc<Policy>) -> Self {
+        Self { policy }
+    }
+}
+
+impl<S> Layer<S> for RpcSpanLayer {
+    type Service = RpcSpan<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RpcSpan {
+            inner,
+            policy: self.policy.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct RpcSpan<S> {
+    inner: S,
+    policy: Arc<Policy>,
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for RpcSpan<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    ReqBody: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<ReqBody>) -> Self::Future {
+        let method = request.uri().path().trim_start_matches('/').to_owned();
+        let request_id = request
+            .headers()
+            .get(REQUEST_ID_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .to_owned();
+        let peer = request
+            .extensions()
+            .get::<TcpConnectInfo>()
+            .and_then(|info| info.remote_addr())
+            .map_or_else(|| "unknown".to_owned(), |address| address.to_string());
+        let metadata = MetadataMap::from_headers(request.headers().clone());
+        let caller = match self.policy.authenticate(&metadata) {
+            Ok(subject) => subject.name.clone(),
+            Err(_) => peer,
+        };
+
+        let span = info_span!(
+            "rpc",
+            otel.kind = "server",
+            method = %method,
+            caller = %caller,
+            request_id = %request_id,
+            service_name = Empty,
+            operation_id = Empty,
+        );
+
+        // The clone is the one that was not polled ready, the ready one is used.
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let call = async move {
+            let mut result = inner.call(request).await;
+            let operation_id = OPERATION_ID.with(|operation_id| operation_id.borrow().clone());
+            if let (Ok(response), Some(operation_id)) = (&mut result, operation_id)
+                && let Ok(value) = HeaderValue::from_str(&operation_id)
+            {
+                response.headers_mut().insert(OPERATION_ID_HEADER, value);
+            }
+            result
+        };
+        Box::pin(OPERATION_ID.scope(Default::default(), call).instrument(span))
+    }
+}